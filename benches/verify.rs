@@ -0,0 +1,70 @@
+//! Compare the cost of `Hasher::is_valid` and `Hasher::verify_into`.
+//!
+//! Run with `cargo bench --bench verify`. For each algorithm, the mean duration and the number of
+//! heap allocations per verification are printed.
+
+use libreauth::pass::{Algorithm, HashBuilder, Hasher, VerifyScratch};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+const PASSWORD: &str = "correct horse battery staple";
+const ITERATIONS: usize = 50;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn measure<F: FnMut() -> bool>(name: &str, mut verify: F) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        assert!(verify());
+    }
+    let elapsed = start.elapsed() / ITERATIONS as u32;
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / ITERATIONS;
+    println!(
+        "{:<24} {:>12?} {:>6} allocations",
+        name, elapsed, allocations
+    );
+}
+
+fn checker(algorithm: Algorithm) -> Hasher {
+    let stored = HashBuilder::new()
+        .algorithm(algorithm)
+        .finalize()
+        .unwrap()
+        .hash(PASSWORD)
+        .unwrap();
+    HashBuilder::from_phc(&stored).unwrap()
+}
+
+fn main() {
+    for (name, algorithm) in [("pbkdf2", Algorithm::Pbkdf2), ("argon2", Algorithm::Argon2)] {
+        let checker = checker(algorithm);
+        measure(&format!("{} is_valid", name), || checker.is_valid(PASSWORD));
+        let mut scratch = VerifyScratch::new();
+        measure(&format!("{} verify_into", name), || {
+            checker.verify_into(PASSWORD, &mut scratch).unwrap()
+        });
+    }
+}
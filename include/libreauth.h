@@ -57,6 +57,7 @@ typedef enum {
     LIBREAUTH_HASH_KECCAK_256           = 13,
     LIBREAUTH_HASH_KECCAK_384           = 14,
     LIBREAUTH_HASH_KECCAK_512           = 15,
+    LIBREAUTH_HASH_BLAKE2B              = 16,
 } libreauth_hash_function;
 
 
@@ -82,16 +83,25 @@ typedef enum {
     LIBREAUTH_PASS_SUCCESS                  = 0,
     LIBREAUTH_PASS_PASSWORD_TOO_SHORT       = 1,
     LIBREAUTH_PASS_PASSWORD_TOO_LONG        = 2,
+    LIBREAUTH_PASS_PASSWORD_CONTAINS_CONTEXT = 3,
+    LIBREAUTH_PASS_PASSWORD_CONTAINS_UNASSIGNED = 4,
+    LIBREAUTH_PASS_PASSWORD_WHITESPACE_ONLY = 5,
     LIBREAUTH_PASS_INVALID_PASSWORD_FORMAT  = 10,
     LIBREAUTH_PASS_INCOMPATIBLE_OPTION      = 11,
+    LIBREAUTH_PASS_ALGORITHM_NOT_AVAILABLE  = 12,
+    LIBREAUTH_PASS_RANDOMNESS_FAILURE       = 13,
+    LIBREAUTH_PASS_TIMEOUT                  = 14,
+    LIBREAUTH_PASS_ALGORITHM_NOT_ALLOWED    = 15,
     LIBREAUTH_PASS_NOT_ENOUGH_SPACE         = 20,
     LIBREAUTH_PASS_NULL_PTR                 = 21,
-    LIBREAUTH_PASS_INVALID_KEY_LEN          = 22
+    LIBREAUTH_PASS_INVALID_KEY_LEN          = 22,
+    LIBREAUTH_PASS_PASSWORD_MISMATCH        = 23
 } libreauth_pass_errno;
 
 typedef enum {
     LIBREAUTH_PASS_BYTES        = 0,
-    LIBREAUTH_PASS_CHARACTERS   = 1
+    LIBREAUTH_PASS_CHARACTERS   = 1,
+    LIBREAUTH_PASS_GRAPHEMES    = 2
 } libreauth_pass_len_calc;
 
 typedef enum {
@@ -134,6 +144,7 @@ libreauth_pass_errno    libreauth_pass_init_from_phc(struct libreauth_pass_cfg *
 libreauth_pass_errno    libreauth_pass_hash(const struct libreauth_pass_cfg *cfg, const char *pass, char *hash, size_t hash_len);
 int32_t                 libreauth_pass_is_valid(const char *pass, const char *ref);
 int32_t                 libreauth_pass_is_valid_xhmac(const char *pass, const char *ref, const void *key, size_t key_len);
+libreauth_pass_errno    libreauth_pass_verify_and_upgrade(const char *pass, const char *ref, const struct libreauth_pass_cfg *cfg, char *dest, size_t dest_len);
 
 
 /*
@@ -150,9 +161,11 @@ typedef enum {
     LIBREAUTH_OATH_INVALID_KEY_LEN   = 11,
     LIBREAUTH_OATH_CODE_TOO_SMALL    = 12,
     LIBREAUTH_OATH_CODE_TOO_BIG      = 13,
+    LIBREAUTH_OATH_INVALID_BASE      = 14,
 
     LIBREAUTH_OATH_INVALID_KEY       = 20,
     LIBREAUTH_OATH_INVALID_PERIOD    = 21,
+    LIBREAUTH_OATH_KEY_TOO_SHORT     = 22,
 
     LIBREAUTH_OATH_INVALID_UTF8      = 30
 } libreauth_oath_errno;
@@ -170,6 +183,7 @@ struct libreauth_hotp_cfg {
     const char                   *output_base;
     libreauth_hash_function       hash_function;
     uint64_t                      look_ahead;
+    size_t                        min_key_len; /* minimum key length, in bytes (default: 16) */
 };
 
 libreauth_oath_errno libreauth_hotp_init(struct libreauth_hotp_cfg *cfg);
@@ -190,6 +204,7 @@ struct libreauth_totp_cfg {
     size_t                        output_len;
     const void                   *output_base;
     libreauth_hash_function       hash_function;
+    size_t                        min_key_len; /* minimum key length, in bytes (default: 16) */
 };
 
 libreauth_oath_errno libreauth_totp_init(struct libreauth_totp_cfg *cfg);
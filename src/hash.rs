@@ -1,7 +1,7 @@
 //! Hash functions used in the library
 
-use std::fmt;
-use std::str::FromStr;
+use core::fmt;
+use core::str::FromStr;
 
 pub enum HashFunctionError {
     ImportError,
@@ -78,6 +78,10 @@ pub enum HashFunctionError {
 ///             <td>Keccak512</td>
 ///             <td>LIBREAUTH_HASH_KECCAK_512</td>
 ///         </tr>
+///         <tr>
+///             <td>Blake2b</td>
+///             <td>LIBREAUTH_HASH_BLAKE2B</td>
+///         </tr>
 ///     </tbody>
 /// </table>
 #[repr(C)]
@@ -98,6 +102,7 @@ pub enum HashFunction {
     Keccak256 = 13,
     Keccak384 = 14,
     Keccak512 = 15,
+    Blake2b = 16,
 }
 
 impl fmt::Display for HashFunction {
@@ -118,6 +123,7 @@ impl fmt::Display for HashFunction {
             HashFunction::Keccak256 => "Keccak256",
             HashFunction::Keccak384 => "Keccak384",
             HashFunction::Keccak512 => "Keccak512",
+            HashFunction::Blake2b => "BLAKE2b",
         };
         write!(f, "{}", s)
     }
@@ -143,6 +149,7 @@ impl FromStr for HashFunction {
             "keccak256" => HashFunction::Keccak256,
             "keccak384" => HashFunction::Keccak384,
             "keccak512" => HashFunction::Keccak512,
+            "blake2b" => HashFunction::Blake2b,
             _ => {
                 return Err(HashFunctionError::ImportError);
             }
@@ -6,6 +6,19 @@
 //! LibreAuth is a collection of tools for user authentication.
 //!
 //!
+//! ## no_std support
+//!
+//! The `oath` module can be used without the standard library, as long as an allocator is
+//! available, by disabling the default features. In this case, the functions relying on the
+//! system clock and the base32 decoding are not available.
+//!
+//! ```toml
+//! libreauth = { version = "*", default-features = false, features = ["oath"] }
+//! ```
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 #[cfg(feature = "cbindings")]
 pub(crate) mod c_helpers;
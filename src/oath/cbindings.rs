@@ -19,6 +19,7 @@ macro_rules! otp_init {
                 c.output_len = crate::oath::DEFAULT_OTP_OUT_LEN;
                 c.output_base = std::ptr::null();
                 c.hash_function = crate::oath::DEFAULT_OTP_HASH;
+                c.min_key_len = crate::oath::DEFAULT_MIN_KEY_LEN;
                 $(
                     c.$field = $value;
                 )*
@@ -37,6 +38,7 @@ macro_rules! otp_init {
 /// - [output_len](crate::oath::HOTPBuilder::output_len)
 /// - [output_base](crate::oath::HOTPBuilder::output_base)
 /// - [hash_function](crate::hash::HashFunction)
+/// - [min_key_len](crate::oath::HOTPBuilder::min_key_len)
 #[repr(C)]
 pub struct HOTPcfg {
     key: *const u8,
@@ -46,6 +48,7 @@ pub struct HOTPcfg {
     output_base: *const libc::c_char,
     hash_function: HashFunction,
     look_ahead: u64,
+    min_key_len: libc::size_t,
 }
 
 /// [C binding] TOTP configuration storage
@@ -62,6 +65,7 @@ pub struct HOTPcfg {
 /// - [output_len](crate::oath::TOTPBuilder::output_len)
 /// - [output_base](crate::oath::TOTPBuilder::output_base)
 /// - [hash_function](crate::hash::HashFunction)
+/// - [min_key_len](crate::oath::TOTPBuilder::min_key_len)
 #[repr(C)]
 pub struct TOTPcfg {
     key: *const u8,
@@ -74,6 +78,7 @@ pub struct TOTPcfg {
     output_len: libc::size_t,
     output_base: *const libc::c_char,
     hash_function: HashFunction,
+    min_key_len: libc::size_t,
 }
 
 fn write_code(code: &[u8], dest: &mut [u8]) {
@@ -209,6 +214,7 @@ pub extern "C" fn libreauth_hotp_generate(cfg: *const HOTPcfg, code: *mut u8) ->
         .output_len(cfg.output_len as usize)
         .output_base(&output_base)
         .hash_function(cfg.hash_function)
+        .min_key_len(cfg.min_key_len)
         .counter(cfg.counter)
         .look_ahead(cfg.look_ahead)
         .finalize()
@@ -251,7 +257,7 @@ pub extern "C" fn libreauth_hotp_generate(cfg: *const HOTPcfg, code: *mut u8) ->
 /// ```
 #[no_mangle]
 pub extern "C" fn libreauth_hotp_is_valid(cfg: *mut HOTPcfg, code: *const u8, sync: i32) -> i32 {
-    let mut cfg = get_value_or_false!(get_mut_cfg(cfg));
+    let cfg = get_value_or_false!(get_mut_cfg(cfg));
     let code = get_value_or_false!(get_code(code, cfg.output_len as usize));
     let output_base = get_value_or_false!(get_output_base(cfg.output_base));
     let key = get_value_or_false!(get_key(cfg.key, cfg.key_len as usize));
@@ -260,6 +266,7 @@ pub extern "C" fn libreauth_hotp_is_valid(cfg: *mut HOTPcfg, code: *const u8, sy
         .output_len(cfg.output_len as usize)
         .output_base(&output_base)
         .hash_function(cfg.hash_function)
+        .min_key_len(cfg.min_key_len)
         .counter(cfg.counter)
         .look_ahead(cfg.look_ahead)
         .finalize()
@@ -315,6 +322,7 @@ pub unsafe extern "C" fn libreauth_hotp_get_uri(
         .output_len(cfg.output_len as usize)
         .output_base(&output_base)
         .hash_function(cfg.hash_function)
+        .min_key_len(cfg.min_key_len)
         .counter(cfg.counter)
         .look_ahead(cfg.look_ahead)
         .finalize()
@@ -423,6 +431,7 @@ pub extern "C" fn libreauth_totp_generate(cfg: *const TOTPcfg, code: *mut u8) ->
         .output_len(cfg.output_len as usize)
         .output_base(&output_base)
         .hash_function(cfg.hash_function)
+        .min_key_len(cfg.min_key_len)
         .timestamp(cfg.timestamp)
         .period(cfg.period)
         .initial_time(cfg.initial_time)
@@ -474,6 +483,7 @@ pub extern "C" fn libreauth_totp_is_valid(cfg: *const TOTPcfg, code: *const u8)
         .output_len(cfg.output_len as usize)
         .output_base(&output_base)
         .hash_function(cfg.hash_function)
+        .min_key_len(cfg.min_key_len)
         .timestamp(cfg.timestamp)
         .period(cfg.period)
         .initial_time(cfg.initial_time)
@@ -525,6 +535,7 @@ pub unsafe extern "C" fn libreauth_totp_get_uri(
         .output_len(cfg.output_len as usize)
         .output_base(&output_base)
         .hash_function(cfg.hash_function)
+        .min_key_len(cfg.min_key_len)
         .timestamp(cfg.timestamp)
         .period(cfg.period)
         .initial_time(cfg.initial_time)
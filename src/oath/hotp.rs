@@ -1,20 +1,31 @@
 #[cfg(feature = "oath-uri")]
 use super::DEFAULT_KEY_URI_PARAM_POLICY;
+#[cfg(feature = "std")]
+use super::{decode_base32, Base32Alphabet};
 use super::{
-    ErrorCode, HashFunction, DEFAULT_LOOK_AHEAD, DEFAULT_OTP_HASH, DEFAULT_OTP_OUT_BASE,
-    DEFAULT_OTP_OUT_LEN,
+    decode_key, Counter, ErrorCode, HashFunction, KeyEncoding, DEFAULT_LOOK_AHEAD,
+    DEFAULT_MIN_KEY_LEN, DEFAULT_OTP_HASH, DEFAULT_OTP_OUT_BASE, DEFAULT_OTP_OUT_LEN,
+    MAX_CODE_LENGTH, MIN_CODE_LENGTH,
 };
 #[cfg(feature = "oath-uri")]
 use crate::oath::key_uri::{KeyUriBuilder, UriType};
-use hmac::{Hmac, Mac};
+use alloc::borrow::ToOwned;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use blake2::Blake2b512;
+use hmac::{Hmac, Mac, SimpleHmac};
 use sha1::Sha1;
 use sha2::{Sha224, Sha256, Sha384, Sha512, Sha512_224, Sha512_256};
 use sha3::{Keccak224, Keccak256, Keccak384, Keccak512, Sha3_224, Sha3_256, Sha3_384, Sha3_512};
+#[cfg(feature = "oath-uri")]
 use std::collections::HashMap;
 
 macro_rules! compute_hmac {
     ($obj: ident, $hash: ty, $input: ident) => {{
-        let mut hmac = Hmac::<$hash>::new_from_slice(&$obj.key.as_slice()).unwrap();
+        compute_hmac!($obj, Hmac, $hash, $input)
+    }};
+    ($obj: ident, $mac: ident, $hash: ty, $input: ident) => {{
+        let mut hmac = $mac::<$hash>::new_from_slice(&$obj.key.as_slice()).unwrap();
         hmac.update(&$input);
         hmac.finalize().into_bytes().to_vec()
     }};
@@ -64,7 +75,7 @@ impl HOTP {
         code.iter().collect()
     }
 
-    fn raw_generate(&self, counter: u64) -> String {
+    fn raw_value(&self, counter: u64) -> u32 {
         let msg = [
             ((counter >> 56) & 0xff) as u8,
             ((counter >> 48) & 0xff) as u8,
@@ -91,14 +102,20 @@ impl HOTP {
             HashFunction::Keccak256 => compute_hmac!(self, Keccak256, msg),
             HashFunction::Keccak384 => compute_hmac!(self, Keccak384, msg),
             HashFunction::Keccak512 => compute_hmac!(self, Keccak512, msg),
+            HashFunction::Blake2b => compute_hmac!(self, SimpleHmac, Blake2b512, msg),
         };
-        let hs = result.as_slice();
-        let nb = self.reduce_result(hs);
-        self.format_result(nb)
+        self.reduce_result(result.as_slice())
+    }
+
+    fn raw_generate(&self, counter: u64) -> String {
+        self.format_result(self.raw_value(counter))
     }
 
     /// Generate the HOTP value.
     ///
+    /// The code is always exactly `output_len` characters long: leading zeros (or, more
+    /// generally, the first character of the output base) are preserved.
+    ///
     /// ## Examples
     /// ```
     /// let key_ascii = "12345678901234567890".to_owned();
@@ -116,6 +133,30 @@ impl HOTP {
         self.raw_generate(self.counter)
     }
 
+    /// Generate the HOTP value as an integer, which avoids parsing the code in numeric
+    /// pipelines. This is only available with the default decimal output base (`0123456789`),
+    /// otherwise [InvalidBase](ErrorCode::InvalidBase) is returned.
+    ///
+    /// Leading zeros are lost: the integer `1234` corresponds to the 6-digit code `001234`.
+    ///
+    /// ## Examples
+    /// ```
+    /// let hotp = libreauth::oath::HOTPBuilder::new()
+    ///     .ascii_key("12345678901234567890")
+    ///     .counter(9)
+    ///     .finalize()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(hotp.generate(), "520489");
+    /// assert_eq!(hotp.generate_u32().unwrap(), 520489);
+    /// ```
+    pub fn generate_u32(&self) -> Result<u32, ErrorCode> {
+        if self.output_base != DEFAULT_OTP_OUT_BASE {
+            return Err(ErrorCode::InvalidBase);
+        }
+        Ok(self.raw_value(self.counter))
+    }
+
     /// Returns the internal counter value.
     pub fn get_counter(&self) -> u64 {
         self.counter
@@ -131,11 +172,11 @@ impl HOTP {
         if code.len() != self.output_len {
             return (false, self.counter);
         }
-        let mut results = HashMap::new();
+        let mut valid_counter = None;
         let end = self.counter + 1 + self.look_ahead;
         for counter in self.counter..end {
             let r1 = self.raw_generate(counter);
-            let ref_code = r1.as_str().as_bytes();
+            let ref_code = r1.as_bytes();
             let code = code.as_bytes();
             let (code, ref_code) = match self.hash_function {
                 HashFunction::Sha1 => (
@@ -198,10 +239,16 @@ impl HOTP {
                     compute_hmac!(self, Keccak512, code),
                     compute_hmac!(self, Keccak512, ref_code),
                 ),
+                HashFunction::Blake2b => (
+                    compute_hmac!(self, SimpleHmac, Blake2b512, code),
+                    compute_hmac!(self, SimpleHmac, Blake2b512, ref_code),
+                ),
             };
-            results.insert(code == ref_code, counter);
+            if code == ref_code {
+                valid_counter = Some(counter);
+            }
         }
-        match results.get(&true) {
+        match valid_counter {
             Some(c) => (true, c + 1),
             None => (false, self.counter),
         }
@@ -322,6 +369,37 @@ impl HOTP {
     }
 }
 
+/// Checks if the given code is valid for the specified shared secret and counter, without
+/// using a builder. The code is checked in constant time, as with [HOTP::is_valid]. An invalid
+/// configuration, such as a number of digits too small, is considered invalid.
+///
+/// ## Examples
+/// ```
+/// use libreauth::hash::HashFunction;
+/// use libreauth::oath::verify_hotp;
+///
+/// let key = b"12345678901234567890";
+/// assert!(verify_hotp(key, 1, "287082", 6, HashFunction::Sha1));
+/// ```
+pub fn verify_hotp(
+    key: &[u8],
+    counter: impl Into<Counter>,
+    code: &str,
+    digits: u8,
+    hash: HashFunction,
+) -> bool {
+    match HOTPBuilder::new()
+        .key(key)
+        .counter(counter)
+        .output_len(usize::from(digits))
+        .hash_function(hash)
+        .finalize()
+    {
+        Ok(hotp) => hotp.is_valid(code),
+        Err(_) => false,
+    }
+}
+
 /// Builds an HOTP object.
 ///
 /// ## Examples
@@ -379,6 +457,7 @@ pub struct HOTPBuilder {
     hash_function: HashFunction,
     runtime_error: Option<ErrorCode>,
     look_ahead: u64,
+    min_key_len: usize,
 }
 
 impl Default for HOTPBuilder {
@@ -398,14 +477,15 @@ impl HOTPBuilder {
             hash_function: DEFAULT_OTP_HASH,
             runtime_error: None,
             look_ahead: DEFAULT_LOOK_AHEAD,
+            min_key_len: DEFAULT_MIN_KEY_LEN,
         }
     }
 
     builder_common!(HOTPBuilder);
 
     /// Sets the counter. Default is 0.
-    pub fn counter(&mut self, counter: u64) -> &mut HOTPBuilder {
-        self.counter = counter;
+    pub fn counter(&mut self, counter: impl Into<Counter>) -> &mut HOTPBuilder {
+        self.counter = counter.into().0;
         self
     }
 
@@ -420,22 +500,16 @@ impl HOTPBuilder {
         if let Some(e) = self.runtime_error {
             return Err(e);
         }
-        match self.code_length() {
-            n if n < 1_000_000 => return Err(ErrorCode::CodeTooSmall),
-            n if n > 2_147_483_648 => return Err(ErrorCode::CodeTooBig),
-            _ => (),
-        }
-        match self.key {
-            Some(ref k) => Ok(HOTP {
-                key: k.clone(),
-                counter: self.counter,
-                output_len: self.output_len,
-                output_base: self.output_base.clone(),
-                hash_function: self.hash_function,
-                look_ahead: self.look_ahead,
-            }),
-            None => Err(ErrorCode::InvalidKey),
-        }
+        self.check_code_length()?;
+        let key = self.check_key()?;
+        Ok(HOTP {
+            key: key.clone(),
+            counter: self.counter,
+            output_len: self.output_len,
+            output_base: self.output_base.clone(),
+            hash_function: self.hash_function,
+            look_ahead: self.look_ahead,
+        })
     }
 }
 
@@ -445,6 +519,7 @@ mod tests {
     use crate::hash::HashFunction;
     #[cfg(feature = "oath-uri")]
     use crate::oath::ParametersVisibility;
+    use crate::oath::{Base32Alphabet, ErrorCode};
 
     #[test]
     fn test_hotp_key_simple() {
@@ -457,10 +532,7 @@ mod tests {
         assert_eq!(hotp.key, key);
         assert_eq!(hotp.counter, 0);
         assert_eq!(hotp.output_len, 6);
-        match hotp.hash_function {
-            HashFunction::Sha1 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(hotp.hash_function, HashFunction::Sha1));
 
         let code = hotp.generate();
         assert_eq!(code.len(), 6);
@@ -484,10 +556,7 @@ mod tests {
         assert_eq!(hotp.key, key);
         assert_eq!(hotp.counter, 5);
         assert_eq!(hotp.output_len, 8);
-        match hotp.hash_function {
-            HashFunction::Sha256 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(hotp.hash_function, HashFunction::Sha256));
 
         let code = hotp.generate();
         assert_eq!(code.len(), 8);
@@ -506,10 +575,7 @@ mod tests {
         assert_eq!(hotp.key, key);
         assert_eq!(hotp.counter, 0);
         assert_eq!(hotp.output_len, 6);
-        match hotp.hash_function {
-            HashFunction::Sha1 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(hotp.hash_function, HashFunction::Sha1));
 
         let code = hotp.generate();
         assert_eq!(code.len(), 6);
@@ -534,10 +600,7 @@ mod tests {
         assert_eq!(hotp.key, key);
         assert_eq!(hotp.counter, 5);
         assert_eq!(hotp.output_len, 8);
-        match hotp.hash_function {
-            HashFunction::Sha256 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(hotp.hash_function, HashFunction::Sha256));
 
         let code = hotp.generate();
         assert_eq!(code.len(), 8);
@@ -556,10 +619,7 @@ mod tests {
         assert_eq!(hotp.key, key);
         assert_eq!(hotp.counter, 0);
         assert_eq!(hotp.output_len, 6);
-        match hotp.hash_function {
-            HashFunction::Sha1 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(hotp.hash_function, HashFunction::Sha1));
 
         let code = hotp.generate();
         assert_eq!(code.len(), 6);
@@ -584,10 +644,7 @@ mod tests {
         assert_eq!(hotp.key, key);
         assert_eq!(hotp.counter, 5);
         assert_eq!(hotp.output_len, 8);
-        match hotp.hash_function {
-            HashFunction::Sha512 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(hotp.hash_function, HashFunction::Sha512));
 
         let code = hotp.generate();
         assert_eq!(code.len(), 8);
@@ -609,10 +666,7 @@ mod tests {
         assert_eq!(hotp.key, key);
         assert_eq!(hotp.counter, 0);
         assert_eq!(hotp.output_len, 6);
-        match hotp.hash_function {
-            HashFunction::Sha1 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(hotp.hash_function, HashFunction::Sha1));
 
         let code = hotp.generate();
         assert_eq!(code.len(), 6);
@@ -637,16 +691,40 @@ mod tests {
         assert_eq!(hotp.key, key);
         assert_eq!(hotp.counter, 5);
         assert_eq!(hotp.output_len, 8);
-        match hotp.hash_function {
-            HashFunction::Sha512 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(hotp.hash_function, HashFunction::Sha512));
 
         let code = hotp.generate();
         assert_eq!(code.len(), 8);
         assert_eq!(code, "16848329");
     }
 
+    #[test]
+    fn test_hotp_base32key_alphabets() {
+        let key = vec![
+            49, 50, 51, 52, 53, 54, 55, 56, 57, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 48,
+        ];
+        let rfc = HOTPBuilder::new()
+            .base32_key_with(Base32Alphabet::Rfc4648, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ")
+            .finalize()
+            .unwrap();
+        let zbase = HOTPBuilder::new()
+            .base32_key_with(Base32Alphabet::ZBase32, "gr3dgpbiga5uoqjogr3dgpbiga5uoqjo")
+            .finalize()
+            .unwrap();
+        assert_eq!(rfc.key, key);
+        assert_eq!(zbase.key, key);
+        assert_eq!(rfc.generate(), "755224");
+        assert_eq!(zbase.generate(), "755224");
+    }
+
+    #[test]
+    fn test_invalid_zbase32key() {
+        assert!(HOTPBuilder::new()
+            .base32_key_with(Base32Alphabet::ZBase32, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ")
+            .finalize()
+            .is_err());
+    }
+
     #[test]
     fn test_hotp_base64key_simple() {
         let key = vec![
@@ -662,10 +740,7 @@ mod tests {
         assert_eq!(hotp.key, key);
         assert_eq!(hotp.counter, 0);
         assert_eq!(hotp.output_len, 6);
-        match hotp.hash_function {
-            HashFunction::Sha1 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(hotp.hash_function, HashFunction::Sha1));
 
         let code = hotp.generate();
         assert_eq!(code.len(), 6);
@@ -690,116 +765,300 @@ mod tests {
         assert_eq!(hotp.key, key);
         assert_eq!(hotp.counter, 5);
         assert_eq!(hotp.output_len, 8);
-        match hotp.hash_function {
-            HashFunction::Sha512 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(hotp.hash_function, HashFunction::Sha512));
 
         let code = hotp.generate();
         assert_eq!(code.len(), 8);
         assert_eq!(code, "16848329");
     }
 
+    #[test]
+    fn test_hotp_base64urlkey() {
+        let key = [251, 255, 191].repeat(7);
+        let from_base64 = HOTPBuilder::new()
+            .base64_key("+/+/+/+/+/+/+/+/+/+/+/+/+/+/")
+            .finalize()
+            .unwrap();
+        let from_base64url = HOTPBuilder::new()
+            .base64url_key("-_-_-_-_-_-_-_-_-_-_-_-_-_-_")
+            .finalize()
+            .unwrap();
+        let from_base32 = HOTPBuilder::new()
+            .base32_key("7P737677X7577P737677X7577P737677X4")
+            .finalize()
+            .unwrap();
+        assert_eq!(from_base64.key, key);
+        assert_eq!(from_base64url.key, key);
+        assert_eq!(from_base32.key, key);
+        assert_eq!(from_base64url.generate(), from_base64.generate());
+        assert_eq!(from_base64url.generate(), from_base32.generate());
+
+        let padded = HOTPBuilder::new()
+            .base64url_key("MTIzNDU2Nzg5MDEyMzQ1Njc4OTA=")
+            .finalize()
+            .unwrap();
+        assert_eq!(padded.generate(), "755224");
+        assert!(HOTPBuilder::new()
+            .base64url_key("+/+/+/+/+/+/+/+/+/+/+/+/+/+/")
+            .finalize()
+            .is_err());
+    }
+
+    #[test]
+    fn test_generate_u32() {
+        let mut hotp = HOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .finalize()
+            .unwrap();
+        for _ in 0..10 {
+            let code = hotp.generate();
+            assert_eq!(hotp.generate_u32().unwrap().to_string(), code);
+            hotp.increment_counter();
+        }
+
+        let hotp = HOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .output_base("0123456789abcdef")
+            .finalize()
+            .unwrap();
+        assert!(matches!(hotp.generate_u32(), Err(ErrorCode::InvalidBase)));
+        let hotp = HOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .output_base("abcdefghij")
+            .finalize()
+            .unwrap();
+        assert!(matches!(hotp.generate_u32(), Err(ErrorCode::InvalidBase)));
+    }
+
+    #[test]
+    fn test_key_expecting() {
+        let hex = "3132333435363738393031323334353637383930";
+        let base32 = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let hotp = HOTPBuilder::new()
+            .hex_key_expecting(hex, 20)
+            .finalize()
+            .unwrap();
+        assert_eq!(hotp.generate(), "755224");
+        let hotp = HOTPBuilder::new()
+            .base32_key_expecting(base32, 20)
+            .finalize()
+            .unwrap();
+        assert_eq!(hotp.generate(), "755224");
+
+        assert!(matches!(
+            HOTPBuilder::new()
+                .hex_key_expecting(&hex[..38], 20)
+                .finalize(),
+            Err(ErrorCode::InvalidKeyLen)
+        ));
+        assert!(matches!(
+            HOTPBuilder::new()
+                .base32_key_expecting("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOI", 20)
+                .finalize(),
+            Err(ErrorCode::InvalidKeyLen)
+        ));
+        assert!(matches!(
+            HOTPBuilder::new().hex_key_expecting("zz", 1).finalize(),
+            Err(ErrorCode::InvalidKey)
+        ));
+    }
+
     #[test]
     fn test_nokey() {
-        match HOTPBuilder::new().finalize() {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
+        assert!(HOTPBuilder::new().finalize().is_err());
+    }
+
+    #[test]
+    fn test_min_key_len() {
+        let short_key = "0123456789";
+        match HOTPBuilder::new().ascii_key(short_key).finalize() {
+            Err(ErrorCode::KeyTooShort) => {}
+            _ => panic!("a 10 bytes key should be rejected"),
+        }
+        let hotp = HOTPBuilder::new()
+            .ascii_key(short_key)
+            .min_key_len(10)
+            .finalize()
+            .unwrap();
+        assert!(hotp.is_valid(&hotp.generate()));
+        match HOTPBuilder::new().ascii_key("0123456789012345").finalize() {
+            Ok(_) => {}
+            Err(e) => panic!("{:?}", e),
         }
     }
 
+    #[test]
+    fn test_error_display() {
+        let errors = [
+            (
+                HOTPBuilder::new().hex_key("!@#$%^&").finalize().err(),
+                "invalid key",
+            ),
+            (
+                HOTPBuilder::new()
+                    .hex_key_expecting("3132", 20)
+                    .finalize()
+                    .err(),
+                "invalid key length",
+            ),
+            (
+                HOTPBuilder::new()
+                    .ascii_key("12345678901234567890")
+                    .output_len(4)
+                    .finalize()
+                    .err(),
+                "the code is too small to be secure",
+            ),
+            (
+                HOTPBuilder::new()
+                    .ascii_key("12345678901234567890")
+                    .output_len(10)
+                    .finalize()
+                    .err(),
+                "the code is too big",
+            ),
+        ];
+        for (error, message) in errors.iter() {
+            assert_eq!(error.unwrap().to_string(), *message);
+        }
+        let hotp = HOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .output_base("0123456789abcdef")
+            .finalize()
+            .unwrap();
+        assert_eq!(
+            hotp.generate_u32().unwrap_err().to_string(),
+            "the output base is not the decimal one"
+        );
+    }
+
     #[test]
     fn test_invalid_hexkey() {
         let key = "!@#$%^&".to_owned();
-        match HOTPBuilder::new().hex_key(&key).finalize() {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+        assert!(HOTPBuilder::new().hex_key(&key).finalize().is_err());
     }
 
     #[test]
     fn test_invalid_base32key() {
         let key = "!@#$%^&".to_owned();
-        match HOTPBuilder::new().base32_key(&key).finalize() {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+        assert!(HOTPBuilder::new().base32_key(&key).finalize().is_err());
     }
 
     #[test]
     fn test_empty_output_base() {
         let key_ascii = "12345678901234567890".to_owned();
         let output_base = "";
-        match HOTPBuilder::new()
+        assert!(HOTPBuilder::new()
             .ascii_key(&key_ascii)
-            .output_base(&output_base)
+            .output_base(output_base)
             .finalize()
-        {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+            .is_err());
     }
 
     #[test]
     fn test_invalid_output_base() {
         let key_ascii = "12345678901234567890".to_owned();
         let output_base = "1";
-        match HOTPBuilder::new()
+        assert!(HOTPBuilder::new()
             .ascii_key(&key_ascii)
-            .output_base(&output_base)
+            .output_base(output_base)
             .finalize()
-        {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+            .is_err());
     }
 
     #[test]
     fn test_small_result_base10() {
         let key_ascii = "12345678901234567890".to_owned();
-        match HOTPBuilder::new()
+        assert!(HOTPBuilder::new()
             .ascii_key(&key_ascii)
             .output_len(5)
             .finalize()
-        {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+            .is_err());
     }
 
     #[test]
     fn test_big_result_base10() {
         let key_ascii = "12345678901234567890".to_owned();
-        for nb in vec![10, 42, 69, 1024, 0xffffff] {
-            match HOTPBuilder::new()
+        for nb in [10, 42, 69, 1024, 0xffffff] {
+            assert!(HOTPBuilder::new()
                 .ascii_key(&key_ascii)
                 .output_len(nb)
                 .finalize()
-            {
-                Ok(_) => assert!(false),
-                Err(_) => assert!(true),
-            }
+                .is_err());
         }
     }
 
     #[test]
     fn test_result_ok_base10() {
         let key_ascii = "12345678901234567890".to_owned();
-        match HOTPBuilder::new()
+        assert!(HOTPBuilder::new()
             .ascii_key(&key_ascii)
             .output_len(6)
             .finalize()
-        {
-            Ok(_) => assert!(true),
-            Err(_) => assert!(false),
-        }
-        match HOTPBuilder::new()
+            .is_ok());
+        assert!(HOTPBuilder::new()
             .ascii_key(&key_ascii)
             .output_len(9)
             .finalize()
-        {
-            Ok(_) => assert!(true),
-            Err(_) => assert!(false),
+            .is_ok());
+    }
+
+    #[test]
+    fn test_result_len_boundary_base10() {
+        let key_ascii = "12345678901234567890".to_owned();
+        let hotp = HOTPBuilder::new()
+            .ascii_key(&key_ascii)
+            .output_len(9)
+            .finalize()
+            .unwrap();
+        assert_eq!(hotp.generate().len(), 9);
+        for nb in [10, 11, 20, usize::MAX] {
+            assert!(matches!(
+                HOTPBuilder::new()
+                    .ascii_key(&key_ascii)
+                    .output_len(nb)
+                    .finalize(),
+                Err(ErrorCode::CodeTooBig)
+            ));
+        }
+        assert!(matches!(
+            HOTPBuilder::new()
+                .ascii_key(&key_ascii)
+                .output_len(0)
+                .finalize(),
+            Err(ErrorCode::CodeTooSmall)
+        ));
+    }
+
+    #[test]
+    fn test_result_len_base2() {
+        let key_ascii = "12345678901234567890".to_owned();
+        for nb in [20, 31] {
+            let hotp = HOTPBuilder::new()
+                .ascii_key(&key_ascii)
+                .output_base("01")
+                .output_len(nb)
+                .finalize()
+                .unwrap();
+            assert_eq!(hotp.generate().len(), nb);
+        }
+        assert!(matches!(
+            HOTPBuilder::new()
+                .ascii_key(&key_ascii)
+                .output_base("01")
+                .output_len(19)
+                .finalize(),
+            Err(ErrorCode::CodeTooSmall)
+        ));
+        for nb in [32, 64, 65, 1024, 0xffffff] {
+            assert!(matches!(
+                HOTPBuilder::new()
+                    .ascii_key(&key_ascii)
+                    .output_base("01")
+                    .output_len(nb)
+                    .finalize(),
+                Err(ErrorCode::CodeTooBig)
+            ));
         }
     }
 
@@ -807,54 +1066,67 @@ mod tests {
     fn test_small_result_base64() {
         let key_ascii = "12345678901234567890".to_owned();
         let base = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ+/";
-        match HOTPBuilder::new()
+        assert!(HOTPBuilder::new()
             .ascii_key(&key_ascii)
-            .output_base(&base)
+            .output_base(base)
             .output_len(3)
             .finalize()
-        {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+            .is_err());
     }
 
     #[test]
     fn test_big_result_base64() {
         let key_ascii = "12345678901234567890".to_owned();
         let base = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ+/";
-        match HOTPBuilder::new()
+        assert!(HOTPBuilder::new()
             .ascii_key(&key_ascii)
-            .output_base(&base)
+            .output_base(base)
             .output_len(6)
             .finalize()
-        {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+            .is_err());
     }
 
     #[test]
     fn test_result_ok_base64() {
         let key_ascii = "12345678901234567890".to_owned();
         let base = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ+/";
-        match HOTPBuilder::new()
+        assert!(HOTPBuilder::new()
             .ascii_key(&key_ascii)
-            .output_base(&base)
+            .output_base(base)
             .output_len(4)
             .finalize()
-        {
-            Ok(_) => assert!(true),
-            Err(_) => assert!(false),
-        }
-        match HOTPBuilder::new()
+            .is_ok());
+        assert!(HOTPBuilder::new()
             .ascii_key(&key_ascii)
-            .output_base(&base)
+            .output_base(base)
             .output_len(5)
             .finalize()
-        {
-            Ok(_) => assert!(true),
-            Err(_) => assert!(false),
+            .is_ok());
+    }
+
+    #[test]
+    fn test_leading_zeros() {
+        let key_ascii = "12345678901234567890".to_owned();
+        let examples = [
+            (30, "026920"),
+            (36, "003784"),
+            (44, "000152"),
+            (62, "005080"),
+        ];
+        for (counter, code) in examples.iter() {
+            let hotp = HOTPBuilder::new()
+                .ascii_key(&key_ascii)
+                .counter(*counter)
+                .finalize()
+                .unwrap();
+            assert_eq!(hotp.generate(), *code);
+            assert!(hotp.is_valid(code));
+            assert!(!hotp.is_valid(code.trim_start_matches('0')));
         }
+
+        let hotp = HOTPBuilder::new().ascii_key(&key_ascii).finalize().unwrap();
+        assert_eq!(hotp.format_result(1234), "001234");
+        assert_eq!(hotp.format_result(0), "000000");
     }
 
     #[test]
@@ -886,10 +1158,10 @@ mod tests {
             .unwrap();
         let mut hotp3 = HOTPBuilder::new()
             .ascii_key(&key_ascii)
-            .output_base(&hex_base)
+            .output_base(hex_base)
             .finalize()
             .unwrap();
-        for count in 0..examples.len() {
+        for (count, example) in examples.iter().enumerate() {
             let counter = count as u64;
             assert_eq!(hotp1.counter, counter);
             assert_eq!(hotp2.counter, counter);
@@ -897,9 +1169,9 @@ mod tests {
             let code1 = hotp1.generate();
             let code2 = hotp2.generate();
             let code3 = hotp3.generate();
-            assert_eq!(code1, examples[count][0]);
-            assert_eq!(code2, examples[count][0]);
-            assert_eq!(code3, examples[count][1]);
+            assert_eq!(code1, example[0]);
+            assert_eq!(code2, example[0]);
+            assert_eq!(code3, example[1]);
             hotp1.increment_counter();
             hotp2.increment_counter();
             hotp3.increment_counter();
@@ -919,7 +1191,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -932,7 +1204,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -945,7 +1217,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -958,7 +1230,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -971,7 +1243,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -984,7 +1256,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -997,7 +1269,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -1010,7 +1282,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -1023,7 +1295,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -1036,7 +1308,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -1049,7 +1321,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -1062,7 +1334,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -1075,7 +1347,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -1088,7 +1360,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -1101,7 +1373,36 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
+    }
+
+    #[test]
+    fn test_valid_blake2b_code() {
+        let key_ascii = "12345678901234567890".to_owned();
+        let user_code = "737565".to_owned();
+        let valid = HOTPBuilder::new()
+            .ascii_key(&key_ascii)
+            .hash_function(HashFunction::Blake2b)
+            .finalize()
+            .unwrap()
+            .is_valid(&user_code);
+        assert!(valid);
+    }
+
+    #[test]
+    fn test_blake2b_codes() {
+        let key_ascii = "12345678901234567890".to_owned();
+        let codes = ["737565", "409498", "661680", "066333", "366265"];
+        for (counter, code) in codes.iter().enumerate() {
+            let value = HOTPBuilder::new()
+                .ascii_key(&key_ascii)
+                .hash_function(HashFunction::Blake2b)
+                .counter(counter as u64)
+                .finalize()
+                .unwrap()
+                .generate();
+            assert_eq!(&value, code);
+        }
     }
 
     #[test]
@@ -1114,7 +1415,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1127,7 +1428,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1140,7 +1441,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1153,7 +1454,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1166,7 +1467,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1179,7 +1480,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1192,7 +1493,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1205,7 +1506,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1218,7 +1519,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1231,7 +1532,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1244,7 +1545,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1257,7 +1558,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1270,7 +1571,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1283,7 +1584,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1296,7 +1597,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1308,7 +1609,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1320,7 +1621,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -1397,4 +1698,38 @@ mod tests {
         assert!(uri.contains("&foo=bar+baz"));
         assert!(uri.contains("&foo+2=%C3%A8_%C3%A9"));
     }
+
+    #[test]
+    fn test_verify_hotp() {
+        let key = b"12345678901234567890";
+        let codes = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+        for (counter, code) in codes.iter().enumerate() {
+            assert!(super::verify_hotp(
+                key,
+                counter as u64,
+                code,
+                6,
+                HashFunction::Sha1
+            ));
+            assert!(!super::verify_hotp(
+                key,
+                counter as u64 + 1,
+                code,
+                6,
+                HashFunction::Sha1
+            ));
+            assert!(!super::verify_hotp(
+                key,
+                counter as u64,
+                code,
+                6,
+                HashFunction::Sha256
+            ));
+        }
+        assert!(!super::verify_hotp(key, 0, "55224", 5, HashFunction::Sha1));
+        assert!(!super::verify_hotp(b"", 0, "755224", 6, HashFunction::Sha1));
+    }
 }
@@ -0,0 +1,156 @@
+#[cfg(feature = "std")]
+use super::ErrorCode;
+#[cfg(feature = "std")]
+use alloc::string::String;
+use alloc::vec::Vec;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig, STANDARD};
+use base64::engine::DecodePaddingMode;
+use base64::{alphabet, Engine};
+
+const BASE64URL: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Encodings of a shared secret.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub enum KeyEncoding {
+    /// Hexadecimal, case-insensitive when decoding and lowercase when encoding.
+    Hex,
+    /// Base32 using the [RFC 4648](https://tools.ietf.org/html/rfc4648#section-6) alphabet,
+    /// without padding.
+    #[cfg(feature = "std")]
+    Base32,
+    /// Base32 using the [RFC 4648](https://tools.ietf.org/html/rfc4648#section-6) alphabet,
+    /// with padding.
+    #[cfg(feature = "std")]
+    Base32Padded,
+    /// Base64 using the standard alphabet, with padding.
+    Base64,
+    /// Base64 using the URL and filename safe alphabet. Padding is optional when decoding and
+    /// omitted when encoding.
+    Base64Url,
+    /// ASCII string, used as is.
+    Ascii,
+}
+
+pub(crate) fn decode_key(key: &str, encoding: KeyEncoding) -> Option<Vec<u8>> {
+    match encoding {
+        KeyEncoding::Hex => hex::decode(key).ok(),
+        #[cfg(feature = "std")]
+        KeyEncoding::Base32 => base32::decode(base32::Alphabet::RFC4648 { padding: false }, key),
+        #[cfg(feature = "std")]
+        KeyEncoding::Base32Padded => {
+            base32::decode(base32::Alphabet::RFC4648 { padding: true }, key)
+        }
+        KeyEncoding::Base64 => STANDARD.decode(key).ok(),
+        KeyEncoding::Base64Url => BASE64URL.decode(key).ok(),
+        KeyEncoding::Ascii if key.is_ascii() => Some(key.as_bytes().to_vec()),
+        KeyEncoding::Ascii => None,
+    }
+}
+
+#[cfg(feature = "std")]
+fn encode_key(key: &[u8], encoding: KeyEncoding) -> Option<String> {
+    Some(match encoding {
+        KeyEncoding::Hex => hex::encode(key),
+        KeyEncoding::Base32 => base32::encode(base32::Alphabet::RFC4648 { padding: false }, key),
+        KeyEncoding::Base32Padded => {
+            base32::encode(base32::Alphabet::RFC4648 { padding: true }, key)
+        }
+        KeyEncoding::Base64 => STANDARD.encode(key),
+        KeyEncoding::Base64Url => BASE64URL.encode(key),
+        KeyEncoding::Ascii if key.is_ascii() => String::from_utf8(key.to_vec()).ok()?,
+        KeyEncoding::Ascii => return None,
+    })
+}
+
+/// Decode a shared secret and encode it again using another encoding, for example in order to
+/// migrate stored secrets.
+///
+/// Returns [InvalidKey](ErrorCode::InvalidKey) if the key cannot be decoded or, when converting
+/// to [Ascii](KeyEncoding::Ascii), if the secret is not made of ASCII characters.
+///
+/// ## Examples
+/// ```
+/// use libreauth::oath::{reencode_key, KeyEncoding};
+///
+/// let key = "3132333435363738393031323334353637383930";
+/// let key = reencode_key(key, KeyEncoding::Hex, KeyEncoding::Base32).unwrap();
+/// assert_eq!(key, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+/// ```
+#[cfg(feature = "std")]
+pub fn reencode_key(key: &str, from: KeyEncoding, to: KeyEncoding) -> Result<String, ErrorCode> {
+    let raw = decode_key(key, from).ok_or(ErrorCode::InvalidKey)?;
+    encode_key(&raw, to).ok_or(ErrorCode::InvalidKey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENCODINGS: [KeyEncoding; 6] = [
+        KeyEncoding::Hex,
+        KeyEncoding::Base32,
+        KeyEncoding::Base32Padded,
+        KeyEncoding::Base64,
+        KeyEncoding::Base64Url,
+        KeyEncoding::Ascii,
+    ];
+
+    #[test]
+    fn test_round_trip() {
+        let key = b"12345678901234567890~?>";
+        for from in ENCODINGS {
+            let encoded = encode_key(key, from).unwrap();
+            assert_eq!(decode_key(&encoded, from).unwrap(), key);
+            for to in ENCODINGS {
+                let reencoded = reencode_key(&encoded, from, to).unwrap();
+                assert_eq!(reencoded, encode_key(key, to).unwrap());
+                assert_eq!(reencode_key(&reencoded, to, from).unwrap(), encoded);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encodings() {
+        let key = b"12345678901234567890~?>";
+        let expected = [
+            "31323334353637383930313233343536373839307e3f3e",
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQPY7T4",
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQPY7T4===",
+            "MTIzNDU2Nzg5MDEyMzQ1Njc4OTB+Pz4=",
+            "MTIzNDU2Nzg5MDEyMzQ1Njc4OTB-Pz4",
+            "12345678901234567890~?>",
+        ];
+        for (encoding, expected) in ENCODINGS.iter().zip(expected.iter()) {
+            assert_eq!(encode_key(key, *encoding).unwrap(), *expected);
+        }
+        assert_eq!(
+            reencode_key(
+                "MTIzNDU2Nzg5MDEyMzQ1Njc4OTB-Pz4=",
+                KeyEncoding::Base64Url,
+                KeyEncoding::Ascii
+            )
+            .unwrap(),
+            "12345678901234567890~?>"
+        );
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        for from in ENCODINGS {
+            assert!(matches!(
+                reencode_key("!@#\u{e9}", from, KeyEncoding::Hex),
+                Err(ErrorCode::InvalidKey)
+            ));
+        }
+        assert!(matches!(
+            reencode_key("ff00", KeyEncoding::Hex, KeyEncoding::Ascii),
+            Err(ErrorCode::InvalidKey)
+        ));
+    }
+}
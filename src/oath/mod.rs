@@ -33,6 +33,10 @@
 //! ```
 
 use crate::hash::HashFunction;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
 
 #[cfg(feature = "oath-uri")]
 const DEFAULT_KEY_URI_PARAM_POLICY: ParametersVisibility = ParametersVisibility::ShowNonDefault;
@@ -41,7 +45,44 @@ const DEFAULT_OTP_OUT_BASE: &str = "0123456789";
 const DEFAULT_OTP_OUT_LEN: usize = 6;
 const DEFAULT_TOTP_PERIOD: u32 = 30;
 const DEFAULT_TOTP_T0: u64 = 0;
+const DEFAULT_TOTP_ROUNDING: Rounding = Rounding::Floor;
 const DEFAULT_LOOK_AHEAD: u64 = 0;
+const DEFAULT_MIN_KEY_LEN: usize = 16; // in bytes
+const MIN_TOTP_DIGITS: usize = 6;
+const MAX_TOTP_DIGITS: usize = 8;
+const MIN_CODE_LENGTH: u64 = 1_000_000;
+const MAX_CODE_LENGTH: u64 = 1 << 31; // The HOTP truncated value has 31 bits.
+#[cfg(feature = "std")]
+const RFC4648_ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+#[cfg(feature = "std")]
+const ZBASE32_ALPHABET: &str = "ybndrfg8ejkmcpqxot1uwisza345h769";
+
+/// Alphabets available to decode a base32 encoded shared secret.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Base32Alphabet {
+    /// The standard alphabet defined in [RFC 4648](https://tools.ietf.org/html/rfc4648#section-6).
+    Rfc4648,
+    /// The human-oriented [z-base-32](https://philzimmermann.com/docs/human-oriented-base-32-encoding.txt) alphabet.
+    ZBase32,
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn decode_base32(alphabet: Base32Alphabet, key: &str) -> Option<Vec<u8>> {
+    match alphabet {
+        Base32Alphabet::Rfc4648 => decode_key(key, KeyEncoding::Base32),
+        Base32Alphabet::ZBase32 => {
+            let key = key
+                .chars()
+                .map(|c| {
+                    ZBASE32_ALPHABET
+                        .find(c)
+                        .and_then(|i| RFC4648_ALPHABET.chars().nth(i))
+                })
+                .collect::<Option<String>>()?;
+            decode_key(&key, KeyEncoding::Base32)
+        }
+    }
+}
 
 /// Error codes used both in the rust and C interfaces.
 ///
@@ -85,6 +126,10 @@ const DEFAULT_LOOK_AHEAD: u64 = 0;
 ///             <td>LIBREAUTH_OATH_CODE_TOO_BIG</td>
 ///         </tr>
 ///         <tr>
+///             <td>InvalidBase</td>
+///             <td>LIBREAUTH_OATH_INVALID_BASE</td>
+///         </tr>
+///         <tr>
 ///             <td>InvalidKey</td>
 ///             <td>LIBREAUTH_OATH_INVALID_KEY</td>
 ///         </tr>
@@ -93,6 +138,10 @@ const DEFAULT_LOOK_AHEAD: u64 = 0;
 ///             <td>LIBREAUTH_OATH_INVALID_PERIOD</td>
 ///         </tr>
 ///         <tr>
+///             <td>KeyTooShort</td>
+///             <td>LIBREAUTH_OATH_KEY_TOO_SHORT</td>
+///         </tr>
+///         <tr>
 ///             <td>InvalidUTF8</td>
 ///             <td>LIBREAUTH_OATH_INVALID_UTF8</td>
 ///         </tr>
@@ -110,13 +159,38 @@ pub enum ErrorCode {
     InvalidKeyLen = 11,
     CodeTooSmall = 12,
     CodeTooBig = 13,
+    InvalidBase = 14,
 
     InvalidKey = 20,
     InvalidPeriod = 21,
+    KeyTooShort = 22,
 
     InvalidUTF8 = 30,
 }
 
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ErrorCode::Success => "success",
+            ErrorCode::NullPtr => "null pointer",
+            ErrorCode::NotEnoughSpace => "not enough space in the output buffer",
+            ErrorCode::InvalidBaseLen => "the output base must have at least two characters",
+            ErrorCode::InvalidKeyLen => "invalid key length",
+            ErrorCode::CodeTooSmall => "the code is too small to be secure",
+            ErrorCode::CodeTooBig => "the code is too big",
+            ErrorCode::InvalidBase => "the output base is not the decimal one",
+            ErrorCode::InvalidKey => "invalid key",
+            ErrorCode::InvalidPeriod => "invalid period",
+            ErrorCode::KeyTooShort => "the key is too short to be secure",
+            ErrorCode::InvalidUTF8 => "invalid UTF-8 string",
+        };
+        f.write_str(s)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ErrorCode {}
+
 macro_rules! builder_common {
     ($t:ty) => {
         /// Sets the shared secret.
@@ -133,20 +207,47 @@ macro_rules! builder_common {
 
         /// Sets the shared secret. This secret is passed as an hexadecimal encoded string.
         pub fn hex_key(&mut self, key: &str) -> &mut $t {
-            match hex::decode(key) {
-                Ok(k) => {
+            match decode_key(key, KeyEncoding::Hex) {
+                Some(k) => {
+                    self.key = Some(k);
+                }
+                None => {
+                    self.runtime_error = Some(ErrorCode::InvalidKey);
+                }
+            }
+            self
+        }
+
+        /// Sets the shared secret. This secret is passed as an hexadecimal encoded string which,
+        /// once decoded, must be exactly `expected_bytes` long. This allows to detect a truncated
+        /// secret when it is set rather than when the codes do not match.
+        pub fn hex_key_expecting(&mut self, key: &str, expected_bytes: usize) -> &mut $t {
+            match decode_key(key, KeyEncoding::Hex) {
+                Some(k) if k.len() == expected_bytes => {
                     self.key = Some(k);
                 }
-                Err(_) => {
+                Some(_) => {
+                    self.runtime_error = Some(ErrorCode::InvalidKeyLen);
+                }
+                None => {
                     self.runtime_error = Some(ErrorCode::InvalidKey);
                 }
             }
             self
         }
 
-        /// Sets the shared secret. This secret is passed as a base32 encoded string.
+        /// Sets the shared secret. This secret is passed as a base32 encoded string using the
+        /// RFC 4648 alphabet.
+        #[cfg(feature = "std")]
         pub fn base32_key(&mut self, key: &str) -> &mut $t {
-            match base32::decode(base32::Alphabet::RFC4648 { padding: false }, &key) {
+            self.base32_key_with(Base32Alphabet::Rfc4648, key)
+        }
+
+        /// Sets the shared secret. This secret is passed as a base32 encoded string using the
+        /// specified alphabet.
+        #[cfg(feature = "std")]
+        pub fn base32_key_with(&mut self, alphabet: Base32Alphabet, key: &str) -> &mut $t {
+            match decode_base32(alphabet, key) {
                 Some(k) => {
                     self.key = Some(k);
                 }
@@ -157,30 +258,83 @@ macro_rules! builder_common {
             self
         }
 
+        /// Sets the shared secret. This secret is passed as a base32 encoded string using the
+        /// RFC 4648 alphabet which, once decoded, must be exactly `expected_bytes` long. This
+        /// allows to detect a truncated secret when it is set rather than when the codes do not
+        /// match.
+        #[cfg(feature = "std")]
+        pub fn base32_key_expecting(&mut self, key: &str, expected_bytes: usize) -> &mut $t {
+            match decode_key(key, KeyEncoding::Base32) {
+                Some(k) if k.len() == expected_bytes => {
+                    self.key = Some(k);
+                }
+                Some(_) => {
+                    self.runtime_error = Some(ErrorCode::InvalidKeyLen);
+                }
+                None => {
+                    self.runtime_error = Some(ErrorCode::InvalidKey);
+                }
+            }
+            self
+        }
+
         /// Sets the shared secret. This secret is passed as a base64 encoded string.
         pub fn base64_key(&mut self, key: &str) -> &mut $t {
-            use base64::Engine;
-            match base64::engine::general_purpose::STANDARD.decode(key) {
-                Ok(k) => {
+            match decode_key(key, KeyEncoding::Base64) {
+                Some(k) => {
                     self.key = Some(k);
                 }
-                Err(_) => {
+                None => {
                     self.runtime_error = Some(ErrorCode::InvalidKey);
                 }
             }
             self
         }
 
-        fn code_length(&self) -> usize {
-            let base_len = self.output_base.len();
-            let mut nb_bits = base_len;
-            for _ in 1..self.output_len {
-                nb_bits = match nb_bits.checked_mul(base_len) {
-                    Some(nb_bits) => nb_bits,
-                    None => return ::std::usize::MAX,
-                };
+        /// Sets the shared secret. This secret is passed as a base64 encoded string using the
+        /// URL and filename safe alphabet. Padding is optional.
+        pub fn base64url_key(&mut self, key: &str) -> &mut $t {
+            match decode_key(key, KeyEncoding::Base64Url) {
+                Some(k) => {
+                    self.key = Some(k);
+                }
+                None => {
+                    self.runtime_error = Some(ErrorCode::InvalidKey);
+                }
+            }
+            self
+        }
+
+        fn code_length(&self) -> Option<u64> {
+            let base_len = u64::try_from(self.output_base.len()).ok()?;
+            let output_len = u32::try_from(self.output_len).ok()?;
+            base_len.checked_pow(output_len)
+        }
+
+        /// Sets the minimal length of the shared secret, in bytes. A shorter secret is reported
+        /// when finalizing using [KeyTooShort](ErrorCode::KeyTooShort). Default is 16, as
+        /// required by RFC 4226; 20 is recommended when using Sha1.
+        ///
+        /// Lowering it should be restricted to tests and legacy secrets.
+        pub fn min_key_len(&mut self, len: usize) -> &mut $t {
+            self.min_key_len = len;
+            self
+        }
+
+        fn check_key(&self) -> Result<&Vec<u8>, ErrorCode> {
+            match self.key {
+                Some(ref k) if k.len() < self.min_key_len => Err(ErrorCode::KeyTooShort),
+                Some(ref k) => Ok(k),
+                None => Err(ErrorCode::InvalidKey),
+            }
+        }
+
+        fn check_code_length(&self) -> Result<(), ErrorCode> {
+            match self.code_length() {
+                Some(n) if n < MIN_CODE_LENGTH => Err(ErrorCode::CodeTooSmall),
+                Some(n) if n <= MAX_CODE_LENGTH => Ok(()),
+                _ => Err(ErrorCode::CodeTooBig),
             }
-            nb_bits
         }
 
         /// Sets the number of characters for the code. The minimum and maximum values depends the base. Default is 6.
@@ -203,16 +357,90 @@ macro_rules! builder_common {
     };
 }
 
+/// List the hash functions which can be used to generate HOTP and TOTP codes.
+///
+/// ## Examples
+/// ```
+/// use libreauth::hash::HashFunction;
+/// use libreauth::oath::available_hash_functions;
+///
+/// assert!(available_hash_functions().contains(&HashFunction::Sha1));
+/// ```
+pub fn available_hash_functions() -> Vec<HashFunction> {
+    vec![
+        HashFunction::Sha1,
+        HashFunction::Sha224,
+        HashFunction::Sha256,
+        HashFunction::Sha384,
+        HashFunction::Sha512,
+        HashFunction::Sha512Trunc224,
+        HashFunction::Sha512Trunc256,
+        HashFunction::Sha3_224,
+        HashFunction::Sha3_256,
+        HashFunction::Sha3_384,
+        HashFunction::Sha3_512,
+        HashFunction::Keccak224,
+        HashFunction::Keccak256,
+        HashFunction::Keccak384,
+        HashFunction::Keccak512,
+        HashFunction::Blake2b,
+    ]
+}
+
+/// Split a code into groups of `group` characters separated by `sep`, which makes long codes
+/// easier to read, e.g. `abcd-efgh-ijkl`. A `group` of zero leaves the code unchanged.
+///
+/// This is only meant for display: codes are generated and compared without separators. When
+/// using `-` or a whitespace as separator, [TOTPBuilder::normalize_input] removes them from the
+/// user input.
+///
+/// ## Examples
+/// ```
+/// use libreauth::oath::format_grouped;
+///
+/// assert_eq!(format_grouped("12345678", 4, ' '), "1234 5678");
+/// ```
+pub fn format_grouped(code: &str, group: usize, sep: char) -> String {
+    if group == 0 {
+        return code.to_string();
+    }
+    let mut res = String::with_capacity(code.len() + code.len() / group);
+    for (i, c) in code.chars().enumerate() {
+        if i != 0 && i % group == 0 {
+            res.push(sep);
+        }
+        res.push(c);
+    }
+    res
+}
+
 #[cfg(feature = "oath-uri")]
 mod key_uri;
 #[cfg(feature = "oath-uri")]
 pub use self::key_uri::{KeyUriBuilder, ParametersVisibility};
 
+mod key_encoding;
+use self::key_encoding::decode_key;
+#[cfg(not(feature = "std"))]
+use self::key_encoding::KeyEncoding;
+#[cfg(feature = "std")]
+pub use self::key_encoding::{reencode_key, KeyEncoding};
+
+mod units;
+pub use self::units::{Counter, Period, UnixTime};
+
 mod hotp;
+pub use self::hotp::verify_hotp;
 pub use self::hotp::HOTPBuilder;
 pub use self::hotp::HOTP;
 
 mod totp;
+#[cfg(feature = "std")]
+pub use self::totp::verify_multi;
+pub use self::totp::verify_multi_at;
+pub use self::totp::verify_totp;
+pub use self::totp::KeyPreprocessing;
+pub use self::totp::Rounding;
 pub use self::totp::TOTPBuilder;
 pub use self::totp::TOTP;
 
@@ -1,18 +1,81 @@
 #[cfg(feature = "oath-uri")]
 use super::DEFAULT_KEY_URI_PARAM_POLICY;
+#[cfg(feature = "std")]
+use super::{decode_base32, Base32Alphabet};
 use super::{
-    ErrorCode, HOTPBuilder, HashFunction, DEFAULT_OTP_HASH, DEFAULT_OTP_OUT_BASE,
-    DEFAULT_OTP_OUT_LEN, DEFAULT_TOTP_PERIOD, DEFAULT_TOTP_T0,
+    decode_key, ErrorCode, HOTPBuilder, HashFunction, KeyEncoding, Period, UnixTime,
+    DEFAULT_MIN_KEY_LEN, DEFAULT_OTP_HASH, DEFAULT_OTP_OUT_BASE, DEFAULT_OTP_OUT_LEN,
+    DEFAULT_TOTP_PERIOD, DEFAULT_TOTP_ROUNDING, DEFAULT_TOTP_T0, HOTP, MAX_CODE_LENGTH,
+    MAX_TOTP_DIGITS, MIN_CODE_LENGTH, MIN_TOTP_DIGITS,
 };
 #[cfg(feature = "oath-uri")]
 use crate::oath::key_uri::{KeyUriBuilder, UriType};
+use alloc::borrow::{Cow, ToOwned};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use sha2::{Digest, Sha256};
 #[cfg(feature = "oath-uri")]
 use std::collections::HashMap;
+#[cfg(feature = "std")]
 use std::time::SystemTime;
 
+/// Defines how the elapsed time is converted into a number of time steps.
+///
+/// Only `Floor` complies with [RFC 6238](https://tools.ietf.org/html/rfc6238). The other modes are
+/// non-standard and only exist in order to interoperate with servers having a different behavior
+/// on period boundaries.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rounding {
+    /// Each time step is the half-open interval `[T0 + k * X, T0 + (k + 1) * X)`. This is the
+    /// RFC 6238 behavior.
+    Floor,
+    /// Each time step is the closed-upper interval `(T0 + k * X, T0 + (k + 1) * X]`. At the exact
+    /// boundary, the previous time step is therefore used. Non-standard.
+    UpperClosed,
+}
+
+/// Preprocessing applied to the shared secret before using it as the HMAC key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyPreprocessing {
+    /// The key is used as is, as specified by RFC 6238. This is the correct behavior: HMAC
+    /// already replaces a key longer than the block size of the hash function by its digest.
+    None,
+    /// A key longer than the block size of the hash function is replaced by its SHA-256 digest,
+    /// whatever the hash function is. With SHA-256, this is exactly what HMAC does and the codes
+    /// are the same as with [None](KeyPreprocessing::None). With another hash function, this
+    /// reproduces non-compliant implementations and only exists for compatibility with them.
+    HmacBlock,
+}
+
+fn block_size(hash_function: HashFunction) -> usize {
+    match hash_function {
+        HashFunction::Sha1 | HashFunction::Sha224 | HashFunction::Sha256 => 64,
+        HashFunction::Sha384
+        | HashFunction::Sha512
+        | HashFunction::Sha512Trunc224
+        | HashFunction::Sha512Trunc256
+        | HashFunction::Blake2b => 128,
+        HashFunction::Sha3_224 | HashFunction::Keccak224 => 144,
+        HashFunction::Sha3_256 | HashFunction::Keccak256 => 136,
+        HashFunction::Sha3_384 | HashFunction::Keccak384 => 104,
+        HashFunction::Sha3_512 | HashFunction::Keccak512 => 72,
+    }
+}
+
+fn preprocess_key(key: &[u8], hash_function: HashFunction, method: KeyPreprocessing) -> Vec<u8> {
+    match method {
+        KeyPreprocessing::HmacBlock if key.len() > block_size(hash_function) => {
+            Sha256::digest(key).to_vec()
+        }
+        _ => key.to_vec(),
+    }
+}
+
 /// Generates and checks TOTP codes.
 pub struct TOTP {
     key: Vec<u8>,
+    #[cfg(feature = "std")]
     timestamp_offset: i64,
     positive_tolerance: u64,
     negative_tolerance: u64,
@@ -21,20 +84,42 @@ pub struct TOTP {
     output_len: usize,
     output_base: String,
     hash_function: HashFunction,
+    rounding: Rounding,
+    attempt_limiter: Option<Arc<dyn Fn() -> bool + Send + Sync>>,
+    normalize_input: bool,
 }
 
 impl TOTP {
-    fn get_counter(&self) -> u64 {
+    #[cfg(feature = "std")]
+    fn get_timestamp(&self) -> u64 {
         let timestamp = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap()
             .as_secs() as i64;
-        let timestamp = timestamp + self.timestamp_offset;
-        let timestamp = timestamp as u64;
-        if timestamp < self.initial_time {
-            panic!("The current Unix time is below the initial time.");
+        (timestamp + self.timestamp_offset) as u64
+    }
+
+    #[cfg(feature = "std")]
+    fn get_counter(&self) -> Option<u64> {
+        self.get_counter_at(self.get_timestamp())
+    }
+
+    /// Return the time step at the specified Unix time, or `None` if it is below the initial
+    /// time.
+    fn get_counter_at(&self, timestamp: u64) -> Option<u64> {
+        let elapsed = timestamp.checked_sub(self.initial_time)?;
+        let period = u64::from(self.period);
+        Some(match self.rounding {
+            Rounding::Floor => elapsed / period,
+            Rounding::UpperClosed => ((elapsed + period - 1) / period).saturating_sub(1),
+        })
+    }
+
+    fn get_generation_counter_at(&self, timestamp: u64) -> u64 {
+        match self.get_counter_at(timestamp) {
+            Some(counter) => counter,
+            None => panic!("The Unix time is below the initial time."),
         }
-        (timestamp - self.initial_time) / u64::from(self.period)
     }
 
     /// Generate the current TOTP value.
@@ -50,21 +135,94 @@ impl TOTP {
     /// let code = totp.generate();
     /// assert_eq!(code.len(), 6);
     /// ```
+    #[cfg(feature = "std")]
     pub fn generate(&self) -> String {
-        let counter = self.get_counter();
+        self.generate_for_counter(self.get_generation_counter_at(self.get_timestamp()))
+    }
+
+    /// Generate the TOTP value as an integer, which avoids parsing the code in numeric
+    /// pipelines. This is only available with the default decimal output base (`0123456789`),
+    /// otherwise [InvalidBase](ErrorCode::InvalidBase) is returned.
+    ///
+    /// Leading zeros are lost: the integer `1234` corresponds to the 6-digit code `001234`.
+    ///
+    /// ## Examples
+    /// ```
+    /// let totp = libreauth::oath::TOTPBuilder::new()
+    ///     .ascii_key("12345678901234567890")
+    ///     .finalize()
+    ///     .unwrap();
+    ///
+    /// let code = totp.generate_u32().unwrap();
+    /// assert!(code < 1_000_000);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn generate_u32(&self) -> Result<u32, ErrorCode> {
+        self.hotp_for_counter(self.get_generation_counter_at(self.get_timestamp()))
+            .generate_u32()
+    }
+
+    /// Generate the TOTP value of the next time step, for example in order to display the
+    /// upcoming code along with the current one.
+    ///
+    /// ## Examples
+    /// ```
+    /// let totp = libreauth::oath::TOTPBuilder::new()
+    ///     .ascii_key("12345678901234567890")
+    ///     .finalize()
+    ///     .unwrap();
+    ///
+    /// println!("Current code: {}", totp.generate());
+    /// println!("Next code: {}", totp.next_code());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn next_code(&self) -> String {
+        self.generate_for_counter(self.get_generation_counter_at(self.get_timestamp()) + 1)
+    }
+
+    /// Generate the TOTP value at the specified Unix time. The custom time set using the builder's
+    /// `timestamp` is ignored.
+    ///
+    /// ## Examples
+    /// ```
+    /// let key_ascii = "12345678901234567890".to_owned();
+    /// let totp = libreauth::oath::TOTPBuilder::new()
+    ///     .ascii_key(&key_ascii)
+    ///     .hash_function(libreauth::hash::HashFunction::Sha1)
+    ///     .output_len(8)
+    ///     .finalize()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(totp.generate_at(59), "94287082");
+    /// ```
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `timestamp` is below the builder's `initial_time`. The validation functions
+    /// reject the code instead.
+    pub fn generate_at(&self, timestamp: impl Into<UnixTime>) -> String {
+        self.generate_for_counter(self.get_generation_counter_at(timestamp.into().0))
+    }
+
+    fn hotp_for_counter(&self, counter: u64) -> HOTP {
         let hotp = HOTPBuilder::new()
             .key(&self.key.clone())
+            .min_key_len(0)
             .counter(counter)
             .output_len(self.output_len)
             .output_base(&self.output_base)
             .hash_function(self.hash_function)
             .finalize();
         match hotp {
-            Ok(h) => h.generate(),
+            Ok(h) => h,
             Err(e) => panic!("{:?}", e),
         }
     }
 
+    fn generate_for_counter(&self, counter: u64) -> String {
+        self.hotp_for_counter(counter).generate()
+    }
+
     /// Checks if the given code is valid. This implementation uses the [double HMAC verification](https://www.nccgroup.trust/us/about-us/newsroom-and-events/blog/2011/february/double-hmac-verification/) in order to prevent a timing side channel attack.
     ///
     /// ## Examples
@@ -77,23 +235,168 @@ impl TOTP {
     ///     .unwrap()
     ///     .is_valid(&user_code);
     /// ```
+    #[cfg(feature = "std")]
     pub fn is_valid(&self, code: &str) -> bool {
-        let base_counter = self.get_counter();
-        for counter in
-            (base_counter - self.negative_tolerance)..=(base_counter + self.positive_tolerance)
-        {
-            let hotp = HOTPBuilder::new()
-                .key(&self.key.clone())
-                .counter(counter)
-                .output_len(self.output_len)
-                .hash_function(self.hash_function)
-                .finalize();
-            let is_valid = match hotp {
-                Ok(h) => h.is_valid(code),
-                Err(e) => panic!("{:?}", e),
-            };
-            if is_valid {
-                return true;
+        self.is_valid_at(code, self.get_timestamp())
+    }
+
+    /// Checks if the given code is valid at the specified Unix time. The custom time set using
+    /// the builder's `timestamp` is ignored.
+    ///
+    /// This implementation uses the [double HMAC verification](https://www.nccgroup.trust/us/about-us/newsroom-and-events/blog/2011/february/double-hmac-verification/) in order to prevent a timing side channel attack.
+    pub fn is_valid_at(&self, code: &str, timestamp: impl Into<UnixTime>) -> bool {
+        match self.get_counter_at(timestamp.into().0) {
+            Some(counter) => self.is_valid_around(code, counter),
+            None => {
+                // The attempt is still reported to the limiter.
+                self.attempt_permitted();
+                false
+            }
+        }
+    }
+
+    /// Checks if the given code is valid for the current time step and, if so, returns the
+    /// number of seconds during which it remains valid, for example in order to display it. The
+    /// current time is read once, so both results are consistent. The tolerance is not taken
+    /// into account: a code of another time step is rejected.
+    ///
+    /// This implementation uses the [double HMAC verification](https://www.nccgroup.trust/us/about-us/newsroom-and-events/blog/2011/february/double-hmac-verification/) in order to prevent a timing side channel attack.
+    #[cfg(feature = "std")]
+    pub fn validate_and_remaining(&self, code: &str) -> Option<u64> {
+        self.validate_and_remaining_at(code, self.get_timestamp())
+    }
+
+    /// Same as [validate_and_remaining](TOTP::validate_and_remaining) at the specified Unix
+    /// time. The custom time set using the builder's `timestamp` is ignored.
+    ///
+    /// ## Examples
+    /// ```
+    /// let totp = libreauth::oath::TOTPBuilder::new()
+    ///     .ascii_key("12345678901234567890")
+    ///     .finalize()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(totp.validate_and_remaining_at("287082", 50), Some(10));
+    /// assert_eq!(totp.validate_and_remaining_at("287082", 60), None);
+    /// ```
+    pub fn validate_and_remaining_at(
+        &self,
+        code: &str,
+        timestamp: impl Into<UnixTime>,
+    ) -> Option<u64> {
+        let timestamp = timestamp.into().0;
+        let permitted = self.attempt_permitted();
+        let counter = self.get_counter_at(timestamp)?;
+        let code = self.normalize_code(code);
+        if !(self.is_valid_for_counter(code.as_ref(), counter) && permitted) {
+            return None;
+        }
+        let end = counter
+            .saturating_add(1)
+            .saturating_mul(u64::from(self.period))
+            .saturating_add(self.initial_time);
+        Some(end.saturating_sub(timestamp))
+    }
+
+    fn attempt_permitted(&self) -> bool {
+        match &self.attempt_limiter {
+            Some(limiter) => limiter(),
+            None => true,
+        }
+    }
+
+    fn normalize_code<'a>(&self, code: &'a str) -> Cow<'a, str> {
+        if self.normalize_input {
+            Cow::Owned(
+                code.chars()
+                    .filter(|c| !c.is_whitespace() && *c != '-')
+                    .collect(),
+            )
+        } else {
+            Cow::Borrowed(code)
+        }
+    }
+
+    fn is_valid_around(&self, code: &str, base_counter: u64) -> bool {
+        let permitted = self.attempt_permitted();
+        let code = self.normalize_code(code);
+        let code = code.as_ref();
+        let mut valid = false;
+        let first = base_counter.saturating_sub(self.negative_tolerance);
+        let last = base_counter.saturating_add(self.positive_tolerance);
+        for counter in first..=last {
+            if self.is_valid_for_counter(code, counter) {
+                valid = true;
+                break;
+            }
+        }
+        permitted && valid
+    }
+
+    fn is_valid_for_counter(&self, code: &str, counter: u64) -> bool {
+        let hotp = HOTPBuilder::new()
+            .key(&self.key.clone())
+            .min_key_len(0)
+            .counter(counter)
+            .output_len(self.output_len)
+            .output_base(&self.output_base)
+            .hash_function(self.hash_function)
+            .finalize();
+        match hotp {
+            Ok(h) => h.is_valid(code),
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+
+    /// Checks if the given code is valid while following the clock drift of the user's device.
+    ///
+    /// `stored_drift` is the number of time steps the device is known to be ahead (positive) or
+    /// behind (negative). It should be stored per user and initialized to zero. The code is
+    /// checked within `tolerance` time steps around the current time step shifted by
+    /// `stored_drift`. If the code is valid, `stored_drift` is moved by one time step toward the
+    /// time step that matched, so that a device with a consistently skewed clock is followed over
+    /// several validations.
+    ///
+    /// The drift is only updated after a successful validation and each call never accepts a
+    /// code more than `tolerance` time steps away from the stored drift. Therefore, an attacker
+    /// cannot use this function to widen the validation window without knowing valid codes.
+    /// The builder's tolerance settings are ignored.
+    ///
+    /// This implementation uses the [double HMAC verification](https://www.nccgroup.trust/us/about-us/newsroom-and-events/blog/2011/february/double-hmac-verification/) in order to prevent a timing side channel attack.
+    ///
+    /// ## Examples
+    /// ```
+    /// let key_ascii = "12345678901234567890".to_owned();
+    /// let totp = libreauth::oath::TOTPBuilder::new()
+    ///     .ascii_key(&key_ascii)
+    ///     .finalize()
+    ///     .unwrap();
+    ///
+    /// let mut drift = 0;
+    /// let code = totp.generate();
+    /// assert!(totp.validate_with_drift(&code, &mut drift, 1));
+    /// assert_eq!(drift, 0);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn validate_with_drift(&self, code: &str, stored_drift: &mut i64, tolerance: u8) -> bool {
+        let permitted = self.attempt_permitted();
+        let code = self.normalize_code(code);
+        let code = code.as_ref();
+        let base_counter = match self.get_counter() {
+            Some(counter) => counter as i64,
+            None => return false,
+        };
+        let tolerance = i64::from(tolerance);
+        for offset in (*stored_drift - tolerance)..=(*stored_drift + tolerance) {
+            let counter = base_counter + offset;
+            if counter < 0 {
+                continue;
+            }
+            if self.is_valid_for_counter(code, counter as u64) {
+                if permitted {
+                    *stored_drift += (offset - *stored_drift).signum();
+                }
+                return permitted;
             }
         }
         false
@@ -149,6 +452,79 @@ impl TOTP {
     }
 }
 
+/// Checks if the given code is valid for any of the specified TOTP objects, each of them having
+/// its own key, period, number of digits and hash function. This is useful while migrating from
+/// a configuration to another one.
+///
+/// Every TOTP object is checked, even if a previous one already matched, so the time spent does
+/// not reveal which one accepted the code. The cost therefore grows linearly with the number of
+/// TOTP objects and with their respective tolerances.
+///
+/// ## Examples
+/// ```
+/// let key_ascii = "12345678901234567890".to_owned();
+/// let totp_30 = libreauth::oath::TOTPBuilder::new()
+///     .ascii_key(&key_ascii)
+///     .finalize()
+///     .unwrap();
+/// let totp_60 = libreauth::oath::TOTPBuilder::new()
+///     .ascii_key(&key_ascii)
+///     .period(60)
+///     .finalize()
+///     .unwrap();
+///
+/// let code = totp_60.generate();
+/// assert!(libreauth::oath::verify_multi(&[totp_30, totp_60], &code));
+/// ```
+#[cfg(feature = "std")]
+pub fn verify_multi(totps: &[TOTP], code: &str) -> bool {
+    totps
+        .iter()
+        .fold(false, |valid, totp| totp.is_valid(code) | valid)
+}
+
+/// Checks if the given code is valid for any of the specified TOTP objects at the specified
+/// Unix time. See [verify_multi].
+pub fn verify_multi_at(totps: &[TOTP], code: &str, timestamp: impl Into<UnixTime>) -> bool {
+    let timestamp = timestamp.into();
+    totps.iter().fold(false, |valid, totp| {
+        totp.is_valid_at(code, timestamp) | valid
+    })
+}
+
+/// Checks if the given code is valid for the specified shared secret at the specified Unix time,
+/// without using a builder. The code is checked in constant time, as with [TOTP::is_valid_at],
+/// using the standard T0 of zero and no tolerance. An invalid configuration, such as a zero
+/// period, is considered invalid.
+///
+/// ## Examples
+/// ```
+/// use libreauth::hash::HashFunction;
+/// use libreauth::oath::verify_totp;
+///
+/// let key = b"12345678901234567890";
+/// assert!(verify_totp(key, 59, 30, "94287082", 8, HashFunction::Sha1));
+/// ```
+pub fn verify_totp(
+    key: &[u8],
+    timestamp: impl Into<UnixTime>,
+    period: impl Into<Period>,
+    code: &str,
+    digits: u8,
+    hash: HashFunction,
+) -> bool {
+    match TOTPBuilder::new()
+        .key(key)
+        .period(period)
+        .output_len(usize::from(digits))
+        .hash_function(hash)
+        .finalize()
+    {
+        Ok(totp) => totp.is_valid_at(code, timestamp),
+        Err(_) => false,
+    }
+}
+
 /// Builds a TOTP object.
 ///
 /// ## Examples
@@ -198,6 +574,7 @@ impl TOTP {
 /// ```
 pub struct TOTPBuilder {
     key: Option<Vec<u8>>,
+    #[cfg(feature = "std")]
     timestamp_offset: i64,
     positive_tolerance: u64,
     negative_tolerance: u64,
@@ -206,7 +583,14 @@ pub struct TOTPBuilder {
     output_len: usize,
     output_base: String,
     hash_function: HashFunction,
+    rounding: Rounding,
+    check_digits: bool,
+    allow_nonstandard_digits: bool,
+    attempt_limiter: Option<Arc<dyn Fn() -> bool + Send + Sync>>,
+    normalize_input: bool,
+    key_preprocessing: KeyPreprocessing,
     runtime_error: Option<ErrorCode>,
+    min_key_len: usize,
 }
 
 impl Default for TOTPBuilder {
@@ -220,6 +604,7 @@ impl TOTPBuilder {
     pub fn new() -> TOTPBuilder {
         TOTPBuilder {
             key: None,
+            #[cfg(feature = "std")]
             timestamp_offset: 0,
             positive_tolerance: 0,
             negative_tolerance: 0,
@@ -228,13 +613,21 @@ impl TOTPBuilder {
             output_len: DEFAULT_OTP_OUT_LEN,
             output_base: DEFAULT_OTP_OUT_BASE.to_string(),
             hash_function: DEFAULT_OTP_HASH,
+            rounding: DEFAULT_TOTP_ROUNDING,
+            check_digits: false,
+            allow_nonstandard_digits: false,
+            attempt_limiter: None,
+            normalize_input: false,
+            key_preprocessing: KeyPreprocessing::None,
             runtime_error: None,
+            min_key_len: DEFAULT_MIN_KEY_LEN,
         }
     }
 
     builder_common!(TOTPBuilder);
 
     /// Sets a custom value for the current Unix time instead of the real one.
+    #[cfg(feature = "std")]
     pub fn timestamp(&mut self, timestamp: i64) -> &mut TOTPBuilder {
         let current_timestamp = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
@@ -267,7 +660,12 @@ impl TOTPBuilder {
     }
 
     /// Sets the time step in seconds (X). May not be zero. Default is 30.
-    pub fn period(&mut self, period: u32) -> &mut TOTPBuilder {
+    ///
+    /// Any positive value is accepted. Combined with [timestamp](TOTPBuilder::timestamp), a
+    /// short period such as one second allows to test the expiry of codes quickly and
+    /// deterministically.
+    pub fn period(&mut self, period: impl Into<Period>) -> &mut TOTPBuilder {
+        let period = period.into().0;
         if period == 0 {
             self.runtime_error = Some(ErrorCode::InvalidPeriod);
         } else {
@@ -277,8 +675,75 @@ impl TOTPBuilder {
     }
 
     /// Sets the Unix time to start counting time steps (T0). Default is 0.
-    pub fn initial_time(&mut self, initial_time: u64) -> &mut TOTPBuilder {
-        self.initial_time = initial_time;
+    pub fn initial_time(&mut self, initial_time: impl Into<UnixTime>) -> &mut TOTPBuilder {
+        self.initial_time = initial_time.into().0;
+        self
+    }
+
+    /// Sets the way the elapsed time is converted into a number of time steps. Anything else
+    /// than [Rounding::Floor] is non-standard. Default is [Rounding::Floor].
+    pub fn rounding(&mut self, rounding: Rounding) -> &mut TOTPBuilder {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Sets the number of digits of the code. This is an alias of `output_len` which, unless
+    /// [allow_nonstandard_digits](TOTPBuilder::allow_nonstandard_digits) is set, only accepts
+    /// the 6 to 8 digits supported by most authenticator applications. Default is 6.
+    pub fn digits(&mut self, digits: u8) -> &mut TOTPBuilder {
+        self.output_len = digits as usize;
+        self.check_digits = true;
+        self
+    }
+
+    /// Allows [digits](TOTPBuilder::digits) to accept values outside of the 6 to 8 range.
+    /// Default is false.
+    pub fn allow_nonstandard_digits(&mut self, allow: bool) -> &mut TOTPBuilder {
+        self.allow_nonstandard_digits = allow;
+        self
+    }
+
+    /// Sets a function called before each code validation in order to know whether or not
+    /// another attempt is permitted, for example using an application-specific rate limiter.
+    ///
+    /// When the function returns false, the code is rejected. It is however still checked, so
+    /// a denied attempt takes the same time as a normal one and the response time does not
+    /// reveal the limiter's decision. The function is called once per validation, regardless
+    /// of the tolerance.
+    ///
+    /// ## Examples
+    /// ```
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    ///
+    /// let attempts = AtomicU32::new(0);
+    /// let totp = libreauth::oath::TOTPBuilder::new()
+    ///     .ascii_key("12345678901234567890")
+    ///     .attempt_limiter(move || attempts.fetch_add(1, Ordering::Relaxed) < 5)
+    ///     .finalize()
+    ///     .unwrap();
+    /// ```
+    pub fn attempt_limiter(
+        &mut self,
+        limiter: impl Fn() -> bool + Send + Sync + 'static,
+    ) -> &mut TOTPBuilder {
+        self.attempt_limiter = Some(Arc::new(limiter));
+        self
+    }
+
+    /// Sets whether or not whitespace and hyphens are removed from the submitted code before
+    /// checking it, so that codes typed as `123 456` or `123-456`, or pasted with a trailing
+    /// space, are accepted. The comparison of the resulting code remains constant-time. This
+    /// should not be enabled with an output base containing those characters. Default is false.
+    pub fn normalize_input(&mut self, normalize: bool) -> &mut TOTPBuilder {
+        self.normalize_input = normalize;
+        self
+    }
+
+    /// Sets the preprocessing applied to the shared secret. Only [KeyPreprocessing::None] is
+    /// RFC-compliant, [KeyPreprocessing::HmacBlock] exists for compatibility with non-compliant
+    /// implementations. Default is [KeyPreprocessing::None].
+    pub fn key_preprocessing(&mut self, method: KeyPreprocessing) -> &mut TOTPBuilder {
+        self.key_preprocessing = method;
         self
     }
 
@@ -287,32 +752,41 @@ impl TOTPBuilder {
         if let Some(e) = self.runtime_error {
             return Err(e);
         }
-        match self.code_length() {
-            n if n < 1_000_000 => return Err(ErrorCode::CodeTooSmall),
-            n if n > 2_147_483_648 => return Err(ErrorCode::CodeTooBig),
-            _ => (),
-        }
-        match self.key {
-            Some(ref k) => Ok(TOTP {
-                key: k.clone(),
-                timestamp_offset: self.timestamp_offset,
-                positive_tolerance: self.positive_tolerance,
-                negative_tolerance: self.negative_tolerance,
-                initial_time: self.initial_time,
-                period: self.period,
-                output_len: self.output_len,
-                output_base: self.output_base.clone(),
-                hash_function: self.hash_function,
-            }),
-            None => Err(ErrorCode::InvalidKey),
+        if self.check_digits && !self.allow_nonstandard_digits {
+            if self.output_len < MIN_TOTP_DIGITS {
+                return Err(ErrorCode::CodeTooSmall);
+            }
+            if self.output_len > MAX_TOTP_DIGITS {
+                return Err(ErrorCode::CodeTooBig);
+            }
         }
+        self.check_code_length()?;
+        let key = self.check_key()?;
+        Ok(TOTP {
+            key: preprocess_key(key, self.hash_function, self.key_preprocessing),
+            #[cfg(feature = "std")]
+            timestamp_offset: self.timestamp_offset,
+            positive_tolerance: self.positive_tolerance,
+            negative_tolerance: self.negative_tolerance,
+            initial_time: self.initial_time,
+            period: self.period,
+            output_len: self.output_len,
+            output_base: self.output_base.clone(),
+            hash_function: self.hash_function,
+            rounding: self.rounding,
+            attempt_limiter: self.attempt_limiter.clone(),
+            normalize_input: self.normalize_input,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::TOTPBuilder;
+    use super::{verify_multi_at, KeyPreprocessing, Rounding, TOTPBuilder};
     use crate::hash::HashFunction;
+    use crate::oath::ErrorCode;
+    use crate::oath::HOTPBuilder;
+    use sha2::{Digest, Sha256};
 
     #[test]
     fn test_totp_key_simple() {
@@ -324,10 +798,7 @@ mod tests {
 
         assert_eq!(totp.key, key);
         assert_eq!(totp.output_len, 6);
-        match totp.hash_function {
-            HashFunction::Sha1 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(totp.hash_function, HashFunction::Sha1));
 
         let code = totp.generate();
         assert_eq!(code.len(), 6);
@@ -368,10 +839,7 @@ mod tests {
 
         assert_eq!(totp.key, key);
         assert_eq!(totp.output_len, 6);
-        match totp.hash_function {
-            HashFunction::Sha1 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(totp.hash_function, HashFunction::Sha1));
 
         let code = totp.generate();
         assert_eq!(code.len(), 6);
@@ -413,10 +881,7 @@ mod tests {
 
         assert_eq!(totp.key, key);
         assert_eq!(totp.output_len, 6);
-        match totp.hash_function {
-            HashFunction::Sha1 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(totp.hash_function, HashFunction::Sha1));
 
         let code = totp.generate();
         assert_eq!(code.len(), 6);
@@ -461,10 +926,7 @@ mod tests {
 
         assert_eq!(totp.key, key);
         assert_eq!(totp.output_len, 6);
-        match totp.hash_function {
-            HashFunction::Sha1 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(totp.hash_function, HashFunction::Sha1));
 
         let code = totp.generate();
         assert_eq!(code.len(), 6);
@@ -509,10 +971,7 @@ mod tests {
 
         assert_eq!(totp.key, key);
         assert_eq!(totp.output_len, 6);
-        match totp.hash_function {
-            HashFunction::Sha1 => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(totp.hash_function, HashFunction::Sha1));
 
         let code = totp.generate();
         assert_eq!(code.len(), 6);
@@ -545,131 +1004,98 @@ mod tests {
 
     #[test]
     fn test_nokey() {
-        match TOTPBuilder::new().finalize() {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+        assert!(TOTPBuilder::new().finalize().is_err());
     }
 
     #[test]
     fn test_invalid_hexkey() {
         let key = "!@#$%^&".to_owned();
-        match TOTPBuilder::new().hex_key(&key).finalize() {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+        assert!(TOTPBuilder::new().hex_key(&key).finalize().is_err());
     }
 
     #[test]
     fn test_invalid_base32key() {
         let key = "!@#$%^&".to_owned();
-        match TOTPBuilder::new().base32_key(&key).finalize() {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+        assert!(TOTPBuilder::new().base32_key(&key).finalize().is_err());
     }
 
     #[test]
     fn test_small_result_base10() {
         let key_ascii = "12345678901234567890".to_owned();
-        match TOTPBuilder::new()
+        assert!(TOTPBuilder::new()
             .ascii_key(&key_ascii)
             .output_len(5)
             .finalize()
-        {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+            .is_err());
     }
 
     #[test]
     fn test_big_result_base10() {
         let key_ascii = "12345678901234567890".to_owned();
-        for nb in vec![10, 42, 69, 1024, 0xffffff] {
-            match TOTPBuilder::new()
+        for nb in [10, 42, 69, 1024, 0xffffff] {
+            assert!(TOTPBuilder::new()
                 .ascii_key(&key_ascii)
                 .output_len(nb)
                 .finalize()
-            {
-                Ok(_) => assert!(false),
-                Err(_) => assert!(true),
-            }
+                .is_err());
         }
     }
 
     #[test]
     fn test_result_ok_base10() {
         let key_ascii = "12345678901234567890".to_owned();
-        match TOTPBuilder::new()
+        assert!(TOTPBuilder::new()
             .ascii_key(&key_ascii)
             .output_len(6)
             .finalize()
-        {
-            Ok(_) => assert!(true),
-            Err(_) => assert!(false),
-        }
-        match TOTPBuilder::new()
+            .is_ok());
+        assert!(TOTPBuilder::new()
             .ascii_key(&key_ascii)
             .output_len(9)
             .finalize()
-        {
-            Ok(_) => assert!(true),
-            Err(_) => assert!(false),
-        }
+            .is_ok());
     }
 
     #[test]
     fn test_small_result_base64() {
         let key_ascii = "12345678901234567890".to_owned();
         let base = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ+/";
-        match TOTPBuilder::new()
+        assert!(TOTPBuilder::new()
             .ascii_key(&key_ascii)
-            .output_base(&base)
+            .output_base(base)
             .output_len(3)
             .finalize()
-        {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+            .is_err());
     }
 
     #[test]
     fn test_big_result_base64() {
         let key_ascii = "12345678901234567890".to_owned();
         let base = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ+/";
-        match TOTPBuilder::new()
+        assert!(TOTPBuilder::new()
             .ascii_key(&key_ascii)
-            .output_base(&base)
+            .output_base(base)
             .output_len(6)
             .finalize()
-        {
-            Ok(_) => assert!(false),
-            Err(_) => assert!(true),
-        }
+            .is_err());
     }
 
     #[test]
     fn test_result_ok_base64() {
         let key_ascii = "12345678901234567890".to_owned();
         let base = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ+/";
-        match TOTPBuilder::new()
+        assert!(TOTPBuilder::new()
             .ascii_key(&key_ascii)
-            .output_base(&base)
+            .output_base(base)
             .output_len(4)
             .finalize()
-        {
-            Ok(_) => assert!(true),
-            Err(_) => assert!(false),
-        }
-        match TOTPBuilder::new()
+            .is_ok());
+        assert!(TOTPBuilder::new()
             .ascii_key(&key_ascii)
-            .output_base(&base)
+            .output_base(base)
             .output_len(5)
             .finalize()
-        {
-            Ok(_) => assert!(true),
-            Err(_) => assert!(false),
-        }
+            .is_ok());
     }
 
     #[test]
@@ -697,6 +1123,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_rfc6238_examples_at() {
+        let key_hex = "3132333435363738393031323334353637383930".to_owned();
+        let examples = [
+            (59, "94287082"),
+            (1111111109, "07081804"),
+            (1111111111, "14050471"),
+            (1234567890, "89005924"),
+            (2000000000, "69279037"),
+            (20000000000, "65353130"),
+        ];
+        let totp = TOTPBuilder::new()
+            .hex_key(&key_hex)
+            .output_len(8)
+            .finalize()
+            .unwrap();
+        for &(timestamp, ref_code) in examples.iter() {
+            assert_eq!(totp.generate_at(timestamp), ref_code);
+            assert!(totp.is_valid_at(ref_code, timestamp));
+            assert!(!totp.is_valid_at(ref_code, timestamp + 30));
+        }
+    }
+
     #[test]
     fn test_rfc6238_examples_sha256() {
         let key_hex = "3132333435363738393031323334353637383930313233343536373839303132".to_owned();
@@ -758,7 +1207,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, true);
+        assert!(valid);
     }
 
     #[test]
@@ -781,7 +1230,7 @@ mod tests {
                 .tolerance(tolerance)
                 .finalize()
                 .unwrap()
-                .is_valid(&user_code.to_owned());
+                .is_valid(user_code);
             assert_eq!(valid, validity);
         }
     }
@@ -806,7 +1255,7 @@ mod tests {
                 .positive_tolerance(tolerance)
                 .finalize()
                 .unwrap()
-                .is_valid(&user_code.to_owned());
+                .is_valid(user_code);
             assert_eq!(valid, validity);
         }
     }
@@ -831,11 +1280,62 @@ mod tests {
                 .negative_tolerance(tolerance)
                 .finalize()
                 .unwrap()
-                .is_valid(&user_code.to_owned());
+                .is_valid(user_code);
             assert_eq!(valid, validity);
         }
     }
 
+    #[test]
+    fn test_rounding() {
+        let key_ascii = "12345678901234567890".to_owned();
+        let examples = [
+            (29, Rounding::Floor, "755224"),
+            (29, Rounding::UpperClosed, "755224"),
+            (30, Rounding::Floor, "287082"),
+            (30, Rounding::UpperClosed, "755224"),
+            (31, Rounding::Floor, "287082"),
+            (31, Rounding::UpperClosed, "287082"),
+        ];
+        for &(timestamp, rounding, ref_code) in examples.iter() {
+            let code = TOTPBuilder::new()
+                .ascii_key(&key_ascii)
+                .timestamp(timestamp)
+                .rounding(rounding)
+                .finalize()
+                .unwrap()
+                .generate();
+            assert_eq!(code, ref_code);
+        }
+    }
+
+    #[test]
+    fn test_validate_with_drift() {
+        let key_ascii = "12345678901234567890".to_owned();
+        let timestamp = 1234567890;
+        let totp = TOTPBuilder::new()
+            .ascii_key(&key_ascii)
+            .timestamp(timestamp)
+            .finalize()
+            .unwrap();
+        let device_code = HOTPBuilder::new()
+            .ascii_key(&key_ascii)
+            .counter(timestamp as u64 / 30 + 2)
+            .finalize()
+            .unwrap()
+            .generate();
+
+        let mut drift = 0;
+        assert!(!totp.validate_with_drift(&device_code, &mut drift, 1));
+        assert_eq!(drift, 0);
+        for expected_drift in [1, 2, 2, 2] {
+            assert!(totp.validate_with_drift(&device_code, &mut drift, 2));
+            assert_eq!(drift, expected_drift);
+        }
+        assert!(totp.validate_with_drift(&device_code, &mut drift, 0));
+        assert!(!totp.validate_with_drift(&totp.generate(), &mut drift, 1));
+        assert_eq!(drift, 2);
+    }
+
     #[test]
     fn test_invalid_code() {
         let key_ascii = "12345678901234567890".to_owned();
@@ -847,7 +1347,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -861,7 +1361,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -875,7 +1375,7 @@ mod tests {
             .finalize()
             .unwrap()
             .is_valid(&user_code);
-        assert_eq!(valid, false);
+        assert!(!valid);
     }
 
     #[test]
@@ -977,4 +1477,414 @@ mod tests {
             .finalize();
         assert_eq!(uri, "otpauth://totp/Provider1:alice@example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Provider1&base=%C3%A8_%C3%A9%C3%B6%E2%82%AC%E2%80%A6%C3%B7%E2%80%94%E2%98%BA");
     }
+
+    #[test]
+    fn test_digits() {
+        let key_ascii = "12345678901234567890".to_owned();
+        for digits in 6..=8 {
+            let totp = TOTPBuilder::new()
+                .ascii_key(&key_ascii)
+                .digits(digits)
+                .finalize()
+                .unwrap();
+            assert_eq!(totp.output_len, digits as usize);
+            assert_eq!(totp.generate().len(), digits as usize);
+        }
+        assert!(matches!(
+            TOTPBuilder::new()
+                .ascii_key(&key_ascii)
+                .digits(4)
+                .finalize(),
+            Err(ErrorCode::CodeTooSmall)
+        ));
+        assert!(matches!(
+            TOTPBuilder::new()
+                .ascii_key(&key_ascii)
+                .digits(9)
+                .finalize(),
+            Err(ErrorCode::CodeTooBig)
+        ));
+    }
+
+    #[test]
+    fn test_nonstandard_digits() {
+        let key_ascii = "12345678901234567890".to_owned();
+        let totp = TOTPBuilder::new()
+            .ascii_key(&key_ascii)
+            .output_base("0123456789ABCDEF")
+            .digits(5)
+            .allow_nonstandard_digits(true)
+            .finalize()
+            .unwrap();
+        assert_eq!(totp.generate().len(), 5);
+        let totp = TOTPBuilder::new()
+            .ascii_key(&key_ascii)
+            .allow_nonstandard_digits(true)
+            .digits(9)
+            .finalize()
+            .unwrap();
+        assert_eq!(totp.generate().len(), 9);
+    }
+
+    #[test]
+    fn test_verify_multi() {
+        let totp_30 = TOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .finalize()
+            .unwrap();
+        let totp_60 = TOTPBuilder::new()
+            .ascii_key("09876543210987654321")
+            .period(60)
+            .finalize()
+            .unwrap();
+        let code = totp_60.generate_at(1_234_567_890);
+        assert_ne!(code, totp_30.generate_at(1_234_567_890));
+        assert!(!verify_multi_at(&[totp_30], &code, 1_234_567_890));
+
+        let totp_30 = TOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .finalize()
+            .unwrap();
+        let totps = [totp_30, totp_60];
+        assert!(verify_multi_at(&totps, &code, 1_234_567_890));
+        assert!(!verify_multi_at(&totps, &code, 1_234_567_890 + 3600));
+    }
+
+    #[test]
+    fn test_attempt_limiter() {
+        use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+        use std::sync::Arc;
+
+        let permitted = Arc::new(AtomicBool::new(false));
+        let calls = Arc::new(AtomicU32::new(0));
+        let (p, c) = (permitted.clone(), calls.clone());
+        let totp = TOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .tolerance(1)
+            .attempt_limiter(move || {
+                c.fetch_add(1, Ordering::SeqCst);
+                p.load(Ordering::SeqCst)
+            })
+            .finalize()
+            .unwrap();
+        let code = totp.generate_at(1_234_567_890);
+        assert!(!totp.is_valid_at(&code, 1_234_567_890));
+        assert!(!totp.is_valid_at("000000", 1_234_567_890));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        permitted.store(true, Ordering::SeqCst);
+        assert!(totp.is_valid_at(&code, 1_234_567_890));
+        assert!(!totp.is_valid_at("000000", 1_234_567_890));
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        permitted.store(false, Ordering::SeqCst);
+        let code = totp.generate();
+        let mut drift = 0;
+        assert!(!totp.validate_with_drift(&code, &mut drift, 1));
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn test_next_code() {
+        let now = 1_234_567_905;
+        for rounding in [Rounding::Floor, Rounding::UpperClosed] {
+            let totp = TOTPBuilder::new()
+                .ascii_key("12345678901234567890")
+                .timestamp(now as i64)
+                .rounding(rounding)
+                .finalize()
+                .unwrap();
+            let next = totp.next_code();
+            assert_eq!(next, totp.generate_at(now + 30));
+            assert_ne!(next, totp.generate_at(now));
+        }
+    }
+
+    #[test]
+    fn test_generate_u32() {
+        let totp = TOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .output_len(8)
+            .timestamp(1111111109)
+            .finalize()
+            .unwrap();
+        assert_eq!(totp.generate(), "07081804");
+        assert_eq!(totp.generate_u32().unwrap(), 7081804);
+        assert_eq!(format!("{:08}", totp.generate_u32().unwrap()), "07081804");
+
+        let totp = TOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .output_base("0123456789abcdef")
+            .finalize()
+            .unwrap();
+        assert!(matches!(totp.generate_u32(), Err(ErrorCode::InvalidBase)));
+    }
+
+    #[test]
+    fn test_normalize_input() {
+        for normalize in [false, true] {
+            let totp = TOTPBuilder::new()
+                .ascii_key("12345678901234567890")
+                .timestamp(1111111109)
+                .normalize_input(normalize)
+                .finalize()
+                .unwrap();
+            assert_eq!(totp.generate(), "081804");
+            assert!(totp.is_valid("081804"));
+            for code in [
+                "081 804",
+                "081-804",
+                "081804 ",
+                " 081\t804\n",
+                "0-8-1 8-0-4",
+            ] {
+                assert_eq!(totp.is_valid(code), normalize);
+                let mut drift = 0;
+                assert_eq!(totp.validate_with_drift(code, &mut drift, 1), normalize);
+            }
+            assert!(!totp.is_valid("081 805"));
+            assert!(!totp.is_valid("081_804"));
+        }
+    }
+
+    #[test]
+    fn test_format_grouped() {
+        use crate::oath::format_grouped;
+
+        assert_eq!(format_grouped("abcdefghijkl", 4, '-'), "abcd-efgh-ijkl");
+        assert_eq!(format_grouped("abcdefghij", 4, ' '), "abcd efgh ij");
+        assert_eq!(format_grouped("abcd", 4, '-'), "abcd");
+        assert_eq!(format_grouped("abcd", 0, '-'), "abcd");
+        assert_eq!(format_grouped("", 4, '-'), "");
+
+        let totp = TOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .output_len(8)
+            .normalize_input(true)
+            .finalize()
+            .unwrap();
+        let code = totp.generate_at(59);
+        let grouped = format_grouped(&code, 4, '-');
+        assert_eq!(grouped, "9428-7082");
+        assert!(totp.is_valid_at(&grouped, 59));
+    }
+
+    #[test]
+    fn test_validate_and_remaining() {
+        let totp = TOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .tolerance(1)
+            .finalize()
+            .unwrap();
+        let code = totp.generate_at(59);
+        assert_eq!(totp.validate_and_remaining_at(&code, 30), Some(30));
+        assert_eq!(totp.validate_and_remaining_at(&code, 58), Some(2));
+        assert_eq!(totp.validate_and_remaining_at(&code, 59), Some(1));
+        // Accepted by the tolerance, but not valid for the current time step.
+        assert!(totp.is_valid_at(&code, 60));
+        assert_eq!(totp.validate_and_remaining_at(&code, 60), None);
+        assert_eq!(totp.validate_and_remaining_at("000000", 59), None);
+
+        let totp = TOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .rounding(Rounding::UpperClosed)
+            .initial_time(100)
+            .finalize()
+            .unwrap();
+        let code = totp.generate_at(160);
+        assert_eq!(totp.validate_and_remaining_at(&code, 131), Some(29));
+        assert_eq!(totp.validate_and_remaining_at(&code, 160), Some(0));
+        assert_eq!(totp.validate_and_remaining_at(&code, 161), None);
+    }
+
+    #[test]
+    fn test_small_period() {
+        let key = "12345678901234567890";
+        for period in [1, 2, 10] {
+            let now = 1_234_567_890;
+            let totp = TOTPBuilder::new()
+                .ascii_key(key)
+                .period(period)
+                .timestamp(now as i64)
+                .finalize()
+                .unwrap();
+            let code = totp.generate();
+            let counter = now / u64::from(period);
+            let hotp = HOTPBuilder::new()
+                .ascii_key(key)
+                .counter(counter)
+                .finalize()
+                .unwrap();
+            assert_eq!(code, hotp.generate());
+            assert_eq!(totp.next_code(), totp.generate_at(now + u64::from(period)));
+            assert!(totp.is_valid(&code));
+            assert!(totp.is_valid_at(&code, now));
+            assert!(!totp.is_valid_at(&code, now + u64::from(period)));
+        }
+
+        let totp = TOTPBuilder::new()
+            .ascii_key(key)
+            .period(1)
+            .rounding(Rounding::UpperClosed)
+            .finalize()
+            .unwrap();
+        assert_eq!(totp.generate_at(1), totp.generate_at(0));
+        assert_ne!(totp.generate_at(2), totp.generate_at(1));
+    }
+
+    #[test]
+    fn test_tolerance_near_initial_time() {
+        let totp = TOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .period(1)
+            .initial_time(100)
+            .tolerance(2)
+            .finalize()
+            .unwrap();
+        let code = totp.generate_at(101);
+        assert!(totp.is_valid_at(&code, 100));
+        assert!(totp.is_valid_at(&code, 103));
+        assert!(!totp.is_valid_at(&code, 104));
+    }
+
+    #[test]
+    fn test_before_initial_time() {
+        let totp = TOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .initial_time(100)
+            .tolerance(1)
+            .finalize()
+            .unwrap();
+        let code = totp.generate_at(100);
+        assert!(totp.is_valid_at(&code, 100));
+        assert!(!totp.is_valid_at(&code, 99));
+        assert!(!totp.is_valid_at(&code, 0));
+        assert_eq!(totp.validate_and_remaining_at(&code, 99), None);
+        assert!(!verify_multi_at(&[totp], &code, 99));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_generate_before_initial_time() {
+        let totp = TOTPBuilder::new()
+            .ascii_key("12345678901234567890")
+            .initial_time(100)
+            .finalize()
+            .unwrap();
+        totp.generate_at(99);
+    }
+
+    #[test]
+    fn test_verify_totp() {
+        let key = b"12345678901234567890";
+        let examples = [
+            (59, "94287082"),
+            (1111111109, "07081804"),
+            (1111111111, "14050471"),
+            (1234567890, "89005924"),
+            (2000000000, "69279037"),
+            (20000000000, "65353130"),
+        ];
+        for &(timestamp, code) in examples.iter() {
+            assert!(super::verify_totp(
+                key,
+                timestamp,
+                30,
+                code,
+                8,
+                HashFunction::Sha1
+            ));
+            assert!(!super::verify_totp(
+                key,
+                timestamp + 30,
+                30,
+                code,
+                8,
+                HashFunction::Sha1
+            ));
+            assert!(!super::verify_totp(
+                key,
+                timestamp,
+                30,
+                code,
+                8,
+                HashFunction::Sha256
+            ));
+        }
+        let key = b"12345678901234567890123456789012";
+        assert!(super::verify_totp(
+            key,
+            59,
+            30,
+            "46119246",
+            8,
+            HashFunction::Sha256
+        ));
+        let key = b"1234567890123456789012345678901234567890123456789012345678901234";
+        assert!(super::verify_totp(
+            key,
+            59,
+            30,
+            "90693936",
+            8,
+            HashFunction::Sha512
+        ));
+        assert!(!super::verify_totp(
+            key,
+            59,
+            0,
+            "90693936",
+            8,
+            HashFunction::Sha512
+        ));
+    }
+
+    #[test]
+    fn test_key_preprocessing() {
+        let short_key = [0x42; 64];
+        let long_key = [0x42; 65];
+        let build = |key: &[u8], hash_function, method| {
+            TOTPBuilder::new()
+                .key(key)
+                .hash_function(hash_function)
+                .key_preprocessing(method)
+                .finalize()
+                .unwrap()
+                .generate_at(59)
+        };
+        for hash_function in [HashFunction::Sha1, HashFunction::Sha256] {
+            assert_eq!(
+                build(&short_key, hash_function, KeyPreprocessing::None),
+                build(&short_key, hash_function, KeyPreprocessing::HmacBlock)
+            );
+        }
+        assert_ne!(
+            build(&long_key, HashFunction::Sha1, KeyPreprocessing::None),
+            build(&long_key, HashFunction::Sha1, KeyPreprocessing::HmacBlock)
+        );
+        assert_eq!(
+            build(&long_key, HashFunction::Sha256, KeyPreprocessing::None),
+            build(&long_key, HashFunction::Sha256, KeyPreprocessing::HmacBlock)
+        );
+        let digest = Sha256::digest(long_key);
+        assert_eq!(
+            build(&long_key, HashFunction::Sha1, KeyPreprocessing::HmacBlock),
+            build(&digest, HashFunction::Sha1, KeyPreprocessing::None)
+        );
+    }
+
+    #[test]
+    fn test_min_key_len() {
+        let short_key = "0123456789";
+        assert!(matches!(
+            TOTPBuilder::new().ascii_key(short_key).finalize(),
+            Err(ErrorCode::KeyTooShort)
+        ));
+        let totp = TOTPBuilder::new()
+            .ascii_key(short_key)
+            .min_key_len(10)
+            .finalize()
+            .unwrap();
+        assert!(totp.is_valid_at(&totp.generate_at(1_000_000), 1_000_000));
+    }
 }
@@ -0,0 +1,99 @@
+/// A Unix time, in seconds.
+///
+/// The functions expecting a Unix time accept this type or, for convenience, a bare `u64`. This
+/// prevents other values, such as a [Counter], from being used by mistake.
+///
+/// ## Examples
+/// ```
+/// use libreauth::oath::{TOTPBuilder, UnixTime};
+///
+/// let totp = TOTPBuilder::new()
+///     .ascii_key("12345678901234567890")
+///     .output_len(8)
+///     .finalize()
+///     .unwrap();
+/// assert_eq!(totp.generate_at(UnixTime(59)), "94287082");
+/// assert_eq!(totp.generate_at(59), "94287082");
+/// ```
+///
+/// ```compile_fail
+/// use libreauth::oath::{Counter, TOTPBuilder};
+///
+/// let totp = TOTPBuilder::new()
+///     .ascii_key("12345678901234567890")
+///     .finalize()
+///     .unwrap();
+/// totp.generate_at(Counter(1));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UnixTime(pub u64);
+
+impl From<u64> for UnixTime {
+    fn from(timestamp: u64) -> Self {
+        UnixTime(timestamp)
+    }
+}
+
+impl From<UnixTime> for u64 {
+    fn from(timestamp: UnixTime) -> Self {
+        timestamp.0
+    }
+}
+
+/// A TOTP time step, in seconds.
+///
+/// The functions expecting a period accept this type or, for convenience, a bare `u32`. This
+/// prevents other values, such as a [UnixTime], from being used by mistake.
+///
+/// ## Examples
+/// ```compile_fail
+/// use libreauth::oath::{TOTPBuilder, UnixTime};
+///
+/// let totp = TOTPBuilder::new()
+///     .ascii_key("12345678901234567890")
+///     .period(UnixTime(30))
+///     .finalize();
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Period(pub u32);
+
+impl From<u32> for Period {
+    fn from(period: u32) -> Self {
+        Period(period)
+    }
+}
+
+impl From<Period> for u32 {
+    fn from(period: Period) -> Self {
+        period.0
+    }
+}
+
+/// An HOTP counter.
+///
+/// The functions expecting a counter accept this type or, for convenience, a bare `u64`. This
+/// prevents other values, such as a [UnixTime], from being used by mistake.
+///
+/// ## Examples
+/// ```compile_fail
+/// use libreauth::oath::{HOTPBuilder, UnixTime};
+///
+/// let hotp = HOTPBuilder::new()
+///     .ascii_key("12345678901234567890")
+///     .counter(UnixTime(1))
+///     .finalize();
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Counter(pub u64);
+
+impl From<u64> for Counter {
+    fn from(counter: u64) -> Self {
+        Counter(counter)
+    }
+}
+
+impl From<Counter> for u64 {
+    fn from(counter: Counter) -> Self {
+        counter.0
+    }
+}
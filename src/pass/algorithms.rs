@@ -0,0 +1,66 @@
+use super::Algorithm;
+
+/// Description of a password hashing algorithm, as returned by [available_algorithms].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AlgorithmInfo {
+    /// The algorithm.
+    pub algorithm: Algorithm,
+    /// Identifier used in the PHC format.
+    pub phc_id: &'static str,
+    /// Whether or not the algorithm is available in this build.
+    pub enabled: bool,
+}
+
+/// List the password hashing algorithms known by this library and whether or not each one is
+/// available in this build, for example in order to display it in an administration interface.
+///
+/// ## Examples
+/// ```
+/// use libreauth::pass::{available_algorithms, Algorithm};
+///
+/// let argon2 = available_algorithms()
+///     .into_iter()
+///     .find(|a| a.algorithm == Algorithm::Argon2)
+///     .unwrap();
+/// assert_eq!(argon2.phc_id, "argon2");
+/// assert!(argon2.enabled);
+/// ```
+pub fn available_algorithms() -> Vec<AlgorithmInfo> {
+    vec![
+        AlgorithmInfo {
+            algorithm: Algorithm::Argon2,
+            phc_id: "argon2",
+            enabled: true,
+        },
+        AlgorithmInfo {
+            algorithm: Algorithm::Pbkdf2,
+            phc_id: "pbkdf2",
+            enabled: true,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pass::HashBuilder;
+
+    #[test]
+    fn test_available_algorithms() {
+        let algorithms = available_algorithms();
+        for algorithm in [Algorithm::Argon2, Algorithm::Pbkdf2] {
+            let info = algorithms
+                .iter()
+                .find(|a| a.algorithm == algorithm)
+                .unwrap();
+            assert!(info.enabled);
+            let hash = HashBuilder::new()
+                .algorithm(algorithm)
+                .finalize()
+                .unwrap()
+                .hash("correct horse battery staple")
+                .unwrap();
+            assert!(hash.starts_with(&format!("${}$", info.phc_id)));
+        }
+    }
+}
@@ -1,5 +1,4 @@
-use super::{std_default, ErrorCode, HashingFunction, Normalization};
-use crate::key::KeyBuilder;
+use super::{generate_salt, std_default, ErrorCode, HashingFunction, Normalization};
 use std::collections::HashMap;
 
 const MIN_SALT_LENGTH: usize = 8; // in bytes
@@ -10,6 +9,8 @@ const MAX_PASSES: u32 = 1024;
 const DEFAULT_MEM_COST: u32 = 12; // 2^value KiB
 const MIN_MEM_COST: u32 = 7; // 2^value KiB
 const MAX_MEM_COST: u32 = 18; // 2^value KiB
+const MIN_MEM_KIB: u32 = 1 << MIN_MEM_COST;
+const MAX_MEM_KIB: u32 = 1 << MAX_MEM_COST;
 const DEFAULT_LANES: u32 = 4;
 const MIN_LANES: u32 = 1;
 const MAX_LANES: u32 = 128;
@@ -32,25 +33,44 @@ macro_rules! set_param {
     }};
 }
 
+fn get_variant_name(variant: argon2::Variant) -> &'static str {
+    match variant {
+        argon2::Variant::Argon2d => "d",
+        argon2::Variant::Argon2i => "i",
+        argon2::Variant::Argon2id => "id",
+    }
+}
+
 pub struct Argon2Hash {
+    variant: argon2::Variant,
     passes: u32,
-    mem_cost: u32,
+    mem_kib: u32,
     lanes: u32,
     output_len: u32,
     salt: Vec<u8>,
+    secret: Vec<u8>,
     norm: Normalization,
 }
 
 impl Argon2Hash {
     pub fn new() -> Argon2Hash {
         Argon2Hash {
+            salt: generate_salt(std_default::DEFAULT_SALT_LEN).unwrap_or_default(),
+            ..Argon2Hash::without_salt()
+        }
+    }
+
+    /// Create the hashing function with an empty salt, which must be set before hashing. Unlike
+    /// [new](Argon2Hash::new), this does not use the random number generator.
+    pub fn without_salt() -> Argon2Hash {
+        Argon2Hash {
+            variant: argon2::Variant::Argon2i,
             passes: DEFAULT_PASSES,
-            mem_cost: DEFAULT_MEM_COST,
+            mem_kib: 1 << DEFAULT_MEM_COST,
             lanes: DEFAULT_LANES,
             output_len: DEFAULT_OUTPUT_LEN,
-            salt: KeyBuilder::new()
-                .size(std_default::DEFAULT_SALT_LEN)
-                .as_vec(),
+            salt: Vec::new(),
+            secret: Vec::new(),
             norm: Normalization::Nfkc,
         }
     }
@@ -65,18 +85,52 @@ impl HashingFunction for Argon2Hash {
         let mut params = HashMap::new();
         set_normalization!(self, norm, params, "norm".to_string());
         params.insert("passes".to_string(), self.passes.to_string());
-        params.insert("mem".to_string(), self.mem_cost.to_string());
+        // Memory amounts that are not a power of two, which may come from the reference format,
+        // are stored using its own parameter.
+        if self.mem_kib.is_power_of_two() {
+            params.insert("mem".to_string(), self.mem_kib.trailing_zeros().to_string());
+        } else {
+            params.insert("m".to_string(), self.mem_kib.to_string());
+        }
         params.insert("lanes".to_string(), self.lanes.to_string());
         params.insert("len".to_string(), self.output_len.to_string());
+        if self.variant != argon2::Variant::Argon2i {
+            params.insert(
+                "variant".to_string(),
+                get_variant_name(self.variant).to_string(),
+            );
+        }
+        if !self.secret.is_empty() {
+            params.insert("secret".to_string(), "1".to_string());
+        }
         params
     }
 
     fn set_parameter(&mut self, name: &str, value: &str) -> Result<(), ErrorCode> {
         match name {
             "passes" => set_param!(self, passes, value, u32, MIN_PASSES, MAX_PASSES),
-            "mem" => set_param!(self, mem_cost, value, u32, MIN_MEM_COST, MAX_MEM_COST),
+            "mem" => match value.parse::<u32>() {
+                Ok(mem @ MIN_MEM_COST..=MAX_MEM_COST) => {
+                    self.mem_kib = 1 << mem;
+                    Ok(())
+                }
+                _ => Err(ErrorCode::InvalidPasswordFormat),
+            },
             "lanes" => set_param!(self, lanes, value, u32, MIN_LANES, MAX_LANES),
             "len" => set_param!(self, output_len, value, u32, MIN_OUTPUT_LEN, MAX_OUTPUT_LEN),
+            // Names used by the reference implementation.
+            "t" => set_param!(self, passes, value, u32, MIN_PASSES, MAX_PASSES),
+            "p" => set_param!(self, lanes, value, u32, MIN_LANES, MAX_LANES),
+            "m" => set_param!(self, mem_kib, value, u32, MIN_MEM_KIB, MAX_MEM_KIB),
+            "variant" => {
+                self.variant = match value {
+                    "d" => argon2::Variant::Argon2d,
+                    "i" => argon2::Variant::Argon2i,
+                    "id" => argon2::Variant::Argon2id,
+                    _ => return Err(ErrorCode::InvalidPasswordFormat),
+                };
+                Ok(())
+            }
             _ => Err(ErrorCode::InvalidPasswordFormat),
         }
     }
@@ -96,28 +150,51 @@ impl HashingFunction for Argon2Hash {
     }
 
     fn set_salt_len(&mut self, salt_len: usize) -> Result<(), ErrorCode> {
-        let salt = KeyBuilder::new().size(salt_len).as_vec();
+        let salt = generate_salt(salt_len)?;
         self.set_salt(salt)
     }
 
+    fn auto_salt_len(&self) -> usize {
+        std_default::DEFAULT_SALT_LEN
+    }
+
     fn set_normalization(&mut self, norm: Normalization) -> Result<(), ErrorCode> {
         self.norm = norm;
         Ok(())
     }
 
+    fn set_secret(&mut self, secret: &[u8]) -> Result<(), ErrorCode> {
+        self.secret = secret.to_vec();
+        Ok(())
+    }
+
+    /// The memory cost is increased first, then the number of passes.
+    fn increase_cost(&mut self, ratio: f64) {
+        if ratio <= 1.0 {
+            return;
+        }
+        let steps = ratio.log2().floor().min(f64::from(MAX_MEM_COST)) as u32;
+        let mem_kib = (u64::from(self.mem_kib) << steps).min(u64::from(MAX_MEM_KIB)) as u32;
+        let remaining = ratio * f64::from(self.mem_kib) / f64::from(mem_kib);
+        let passes = (f64::from(self.passes) * remaining).min(f64::from(MAX_PASSES));
+        self.mem_kib = mem_kib;
+        self.passes = self.passes.max(passes as u32);
+    }
+
     fn hash(&self, input: &[u8]) -> Vec<u8> {
-        let two: u32 = 2;
         let config = argon2::Config {
             ad: &[],
             hash_length: self.output_len,
             lanes: self.lanes,
-            mem_cost: two.pow(self.mem_cost),
-            secret: &[],
+            mem_cost: self.mem_kib,
+            secret: self.secret.as_slice(),
             thread_mode: argon2::ThreadMode::Sequential,
             time_cost: self.passes,
-            variant: argon2::Variant::Argon2i,
+            variant: self.variant,
             version: argon2::Version::Version13,
         };
+        // The working memory is allocated and released within rust-argon2, which does not
+        // wipe it and does not allow to provide it: it cannot be zeroized from here.
         argon2::hash_raw(input, self.salt.as_slice(), &config).unwrap()
     }
 }
@@ -134,16 +211,25 @@ mod tests {
     #[test]
     fn test_get_salt() {
         let h = Argon2Hash {
+            variant: argon2::Variant::Argon2i,
             passes: DEFAULT_PASSES,
-            mem_cost: DEFAULT_MEM_COST,
+            mem_kib: 1 << DEFAULT_MEM_COST,
             lanes: DEFAULT_LANES,
             output_len: DEFAULT_OUTPUT_LEN,
             salt: vec![0, 1, 2, 3, 4, 5],
+            secret: Vec::new(),
             norm: Normalization::Nfkc,
         };
         assert_eq!(h.get_salt().unwrap(), vec![0, 1, 2, 3, 4, 5]);
     }
 
+    #[test]
+    fn test_without_salt() {
+        let h = Argon2Hash::without_salt();
+        assert!(h.salt.is_empty());
+        assert_eq!(h.get_parameters(), Argon2Hash::new().get_parameters());
+    }
+
     #[test]
     fn test_salt_randomness() {
         assert_ne!(
@@ -160,11 +246,13 @@ mod tests {
     #[test]
     fn test_argon2_v13() {
         let h = Argon2Hash {
+            variant: argon2::Variant::Argon2i,
             passes: 2,
-            mem_cost: 16,
+            mem_kib: 1 << 16,
             lanes: 4,
             output_len: 24,
             salt: "somesalt".to_string().into_bytes(),
+            secret: Vec::new(),
             norm: Normalization::Nfkc,
         }
         .hash(&"password".to_string().into_bytes());
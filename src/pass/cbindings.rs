@@ -1,11 +1,12 @@
 use super::{
-    std_default, std_nist, Algorithm, ErrorCode, HashBuilder, LengthCalculationMethod,
-    Normalization, PasswordStorageStandard, DEFAULT_USER_VERSION, INTERNAL_VERSION,
+    std_default, std_nist, Algorithm, ErrorCode, HashBuilder, Hasher, LengthCalculationMethod,
+    Normalization, PasswordStorageStandard, Policy, DEFAULT_USER_VERSION,
 };
 use crate::hash::HashFunction;
 use crate::pass::XHMAC;
-use crate::{deref_ptr, deref_ptr_mut, get_slice, get_slice_mut, get_string};
+use crate::{deref_ptr, deref_ptr_mut, get_slice, get_slice_mut, get_string, get_value_or_errno};
 use std::ffi::CStr;
+use std::os::raw::c_char;
 
 /// [C binding]
 ///
@@ -149,7 +150,7 @@ pub unsafe extern "C" fn libreauth_pass_init_std(
 #[no_mangle]
 pub unsafe extern "C" fn libreauth_pass_init_from_phc(
     cfg: *mut PassCfg,
-    phc: *const libc::c_char,
+    phc: *const c_char,
 ) -> ErrorCode {
     let c: &mut PassCfg = deref_ptr_mut!(cfg, ErrorCode::NullPtr);
     let p = get_string!(phc);
@@ -166,11 +167,7 @@ pub unsafe extern "C" fn libreauth_pass_init_from_phc(
     c.length_calculation = checker.length_calculation;
     c.normalization = checker.normalization;
     c.standard = PasswordStorageStandard::NoStandard;
-    c.version = if checker.version >= INTERNAL_VERSION {
-        checker.version - INTERNAL_VERSION
-    } else {
-        checker.version
-    };
+    c.version = checker.version();
     c.xhmac_alg = checker.xhmax_alg;
     match checker.xhmac {
         XHMAC::Before(k) => {
@@ -207,7 +204,7 @@ pub unsafe extern "C" fn libreauth_pass_init_from_phc(
 #[no_mangle]
 pub unsafe extern "C" fn libreauth_pass_hash(
     cfg: *const PassCfg,
-    pass: *const libc::c_char,
+    pass: *const c_char,
     dest: *mut u8,
     dest_len: libc::size_t,
 ) -> ErrorCode {
@@ -217,6 +214,24 @@ pub unsafe extern "C" fn libreauth_pass_hash(
         return ErrorCode::NullPtr;
     }
     let buff = get_slice_mut!(dest, dest_len);
+    let hasher = get_value_or_errno!(get_hasher(c));
+    match hasher.hash(&password) {
+        Ok(h) => write_string(&h, buff),
+        Err(e) => e,
+    }
+}
+
+unsafe fn get_pepper(c: &PassCfg) -> Result<Vec<u8>, ErrorCode> {
+    if c.pepper.is_null() {
+        return Ok(vec![]);
+    }
+    match c.pepper_len {
+        0 => Err(ErrorCode::InvalidKeyLen),
+        l => Ok(get_slice!(c.pepper, l)),
+    }
+}
+
+unsafe fn get_hasher(c: &PassCfg) -> Result<Hasher, ErrorCode> {
     let mut builder = HashBuilder::new();
     builder
         .min_len(c.min_len)
@@ -227,14 +242,7 @@ pub unsafe extern "C" fn libreauth_pass_hash(
         .normalization(c.normalization)
         .version(c.version)
         .xhmac(c.xhmac_alg);
-    let key = if c.pepper.is_null() {
-        vec![]
-    } else {
-        match c.pepper_len {
-            0 => return ErrorCode::InvalidKeyLen,
-            l => get_slice!(c.pepper, l),
-        }
-    };
+    let key = get_pepper(c)?;
     if !key.is_empty() {
         match c.xhmac_type {
             XHMACType::Before => {
@@ -246,23 +254,82 @@ pub unsafe extern "C" fn libreauth_pass_hash(
             XHMACType::None => {}
         };
     }
-    let hasher = match builder.finalize() {
-        Ok(ch) => ch,
-        Err(e) => {
-            return e;
-        }
+    builder.finalize()
+}
+
+fn write_string(s: &str, buff: &mut [u8]) -> ErrorCode {
+    let b = s.as_bytes();
+    let len = b.len();
+    if len >= buff.len() {
+        return ErrorCode::NotEnoughSpace;
+    }
+    buff[..len].clone_from_slice(b);
+    buff[len] = 0;
+    ErrorCode::Success
+}
+
+/// [C binding] Check whether or not the supplied password is valid and, if so, whether or not
+/// the reference should be replaced by a new hash computed according to the given
+/// configuration. If it should, the new hash is stored in the supplied buffer. Otherwise, an
+/// empty string is stored.
+///
+/// The reference is outdated if it does not use the configuration's algorithm, normalization or
+/// version, or if its cost is lower than the default one of the configuration's algorithm. If
+/// the configuration has a XHMAC key, it is also used to check the reference.
+///
+/// Returns `LIBREAUTH_PASS_PASSWORD_MISMATCH` if the password is not valid.
+///
+/// # Parameters
+///
+/// - `pass`: password to check
+/// - `reference`: string representing a previously hashed password using LibreAuth's PHC notation
+/// - `cfg`: pointer to a `struct libreauth_pass_cfg` describing the target configuration
+/// - `dest`: buffer that will hold either the new hash or an empty string
+/// - `dest_len`: buffer's size, in bytes
+///
+/// # Safety
+///
+/// This function is a C binding and is therefore unsafe. It is not meant to be used in Rust.
+#[no_mangle]
+pub unsafe extern "C" fn libreauth_pass_verify_and_upgrade(
+    pass: *const c_char,
+    reference: *const c_char,
+    cfg: *const PassCfg,
+    dest: *mut u8,
+    dest_len: libc::size_t,
+) -> ErrorCode {
+    let c: &PassCfg = deref_ptr!(cfg, ErrorCode::NullPtr);
+    if pass.is_null() || reference.is_null() || dest.is_null() {
+        return ErrorCode::NullPtr;
+    }
+    let password = get_string!(pass);
+    let r = get_string!(reference);
+    let buff = get_slice_mut!(dest, dest_len);
+    let hasher = get_value_or_errno!(get_hasher(c));
+    let key = get_value_or_errno!(get_pepper(c));
+    let checker = if key.is_empty() {
+        HashBuilder::from_phc(r.as_str())
+    } else {
+        HashBuilder::from_phc_xhmac(r.as_str(), &key)
     };
+    let checker = get_value_or_errno!(checker);
+    if !checker.is_valid(&password) {
+        return ErrorCode::PasswordMismatch;
+    }
+    let target_cost = get_value_or_errno!(hasher.cost_summary());
+    let policy = Policy {
+        algorithm: Some(hasher.algorithm),
+        min_memory_kib: target_cost.memory_kib,
+        min_passes: target_cost.passes,
+        min_iterations: target_cost.iterations,
+        version: Some(c.version),
+        normalization: Some(hasher.normalization),
+    };
+    if !checker.needs_update_full(&policy).any() {
+        return write_string("", buff);
+    }
     match hasher.hash(&password) {
-        Ok(h) => {
-            let b = h.into_bytes();
-            let len = b.len();
-            if len >= dest_len {
-                return ErrorCode::NotEnoughSpace;
-            }
-            buff[..len].clone_from_slice(&b[..len]);
-            buff[len] = 0;
-            ErrorCode::Success
-        }
+        Ok(h) => write_string(&h, buff),
         Err(e) => e,
     }
 }
@@ -274,10 +341,7 @@ pub unsafe extern "C" fn libreauth_pass_hash(
 /// - `pass`: password to check
 /// - `reference`: string representing a previously hashed password using LibreAuth's PHC notation
 #[no_mangle]
-pub extern "C" fn libreauth_pass_is_valid(
-    pass: *const libc::c_char,
-    reference: *const libc::c_char,
-) -> i32 {
+pub extern "C" fn libreauth_pass_is_valid(pass: *const c_char, reference: *const c_char) -> i32 {
     libreauth_pass_is_valid_xhmac(pass, reference, std::ptr::null(), 0)
 }
 
@@ -291,8 +355,8 @@ pub extern "C" fn libreauth_pass_is_valid(
 /// - `key_len`: XHMAC key length, in bytes
 #[no_mangle]
 pub extern "C" fn libreauth_pass_is_valid_xhmac(
-    pass: *const libc::c_char,
-    reference: *const libc::c_char,
+    pass: *const c_char,
+    reference: *const c_char,
     key: *const u8,
     key_len: libc::size_t,
 ) -> i32 {
@@ -0,0 +1,138 @@
+use super::phc::PHCData;
+use super::{generate_salt, std_default, ErrorCode, HashBuilder, Hasher};
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+
+fn parse(data: &str) -> Result<PHCData, ErrorCode> {
+    PHCData::from_str(data).map_err(|_| ErrorCode::InvalidPasswordFormat)
+}
+
+/// Check whether or not two stored password hashes have been derived from the same password
+/// using the same algorithm, parameters and salt.
+///
+/// Unlike a string comparison, this does not depend on the order of the parameters. The hashes
+/// are compared in constant time.
+///
+/// ## Examples
+/// ```
+/// use libreauth::pass::same_derivation;
+///
+/// let a = "$pbkdf2$iter=21000,hmac=sha256$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+/// let b = "$pbkdf2$hmac=sha256,iter=21000$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+/// assert!(same_derivation(a, b).unwrap());
+/// ```
+pub fn same_derivation(a: &str, b: &str) -> Result<bool, ErrorCode> {
+    let a = parse(a)?;
+    let b = parse(b)?;
+    if a.id != b.id || a.parameters != b.parameters || a.salt != b.salt {
+        return Ok(false);
+    }
+    let (hash_a, hash_b) = match (a.hash, b.hash) {
+        (Some(ha), Some(hb)) => (ha, hb),
+        _ => return Ok(false),
+    };
+    let key = generate_salt(std_default::DEFAULT_SALT_LEN)?;
+    let mut mac_a = Hmac::<Sha512>::new_from_slice(&key)?;
+    mac_a.update(&hash_a);
+    let mut mac_b = Hmac::<Sha512>::new_from_slice(&key)?;
+    mac_b.update(&hash_b);
+    Ok(mac_a.verify(&mac_b.finalize().into_bytes()).is_ok())
+}
+
+/// Check whether or not a password matches any of the specified stored password hashes, for
+/// example in order to prevent the reuse of a previous password.
+///
+/// Every stored hash is parsed before checking the password, so a malformed one is reported as
+/// an error regardless of its position. The password is then checked against all of them.
+///
+/// ## Examples
+/// ```
+/// use libreauth::pass::{matches_any, HashBuilder};
+///
+/// let hasher = HashBuilder::new().finalize().unwrap();
+/// let old_hashes = [
+///     hasher.hash("correct horse battery staple").unwrap(),
+///     hasher.hash("Tr0ub4dor&3 is too short").unwrap(),
+/// ];
+/// let old_hashes: Vec<&str> = old_hashes.iter().map(|h| h.as_str()).collect();
+/// assert!(matches_any("correct horse battery staple", &old_hashes).unwrap());
+/// assert!(!matches_any("a brand new password", &old_hashes).unwrap());
+/// ```
+pub fn matches_any(password: &str, old_hashes: &[&str]) -> Result<bool, ErrorCode> {
+    let hashers = old_hashes
+        .iter()
+        .map(|h| HashBuilder::from_phc(h))
+        .collect::<Result<Vec<Hasher>, ErrorCode>>()?;
+    Ok(hashers
+        .iter()
+        .fold(false, |found, hasher| hasher.is_valid(password) | found))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pass::Algorithm;
+
+    fn get_old_hashes() -> Vec<String> {
+        let hasher = HashBuilder::new()
+            .algorithm(Algorithm::Pbkdf2)
+            .finalize()
+            .unwrap();
+        ["first password", "second password", "third password"]
+            .iter()
+            .map(|p| hasher.hash(p).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_matches_any() {
+        let old_hashes = get_old_hashes();
+        let old_hashes: Vec<&str> = old_hashes.iter().map(|h| h.as_str()).collect();
+        assert!(matches_any("second password", &old_hashes).unwrap());
+        assert!(!matches_any("fourth password", &old_hashes).unwrap());
+        assert!(!matches_any("second password", &[]).unwrap());
+    }
+
+    #[test]
+    fn test_matches_any_invalid() {
+        let old_hashes = get_old_hashes();
+        let mut old_hashes: Vec<&str> = old_hashes.iter().map(|h| h.as_str()).collect();
+        old_hashes.push("invalid");
+        assert!(matches!(
+            matches_any("second password", &old_hashes),
+            Err(ErrorCode::InvalidPasswordFormat)
+        ));
+    }
+
+    #[test]
+    fn test_same_derivation() {
+        let a = "$pbkdf2$iter=21000,hmac=sha256$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+        let b = "$pbkdf2$hmac=sha256,iter=21000$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+        assert!(same_derivation(a, a).unwrap());
+        assert!(same_derivation(a, b).unwrap());
+    }
+
+    #[test]
+    fn test_different_derivation() {
+        let a = "$pbkdf2$iter=21000,hmac=sha256$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+        let data = [
+            "$pbkdf2$iter=21000,hmac=sha256$RSF4Ag$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+            "$pbkdf2$iter=21000,hmac=sha256$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHg",
+            "$pbkdf2$iter=21001,hmac=sha256$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+            "$argon2$iter=21000,hmac=sha256$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+            "$pbkdf2$iter=21000,hmac=sha256$RSF4Aw",
+        ];
+        for b in data.iter() {
+            assert!(!same_derivation(a, b).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let a = "$pbkdf2$iter=21000,hmac=sha256$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+        assert!(matches!(
+            same_derivation(a, "invalid"),
+            Err(ErrorCode::InvalidPasswordFormat)
+        ));
+    }
+}
@@ -0,0 +1,71 @@
+use super::ErrorCode;
+use unicode_normalization::UnicodeNormalization;
+
+fn normalize(s: &str) -> String {
+    s.nfkc().collect::<String>().to_lowercase()
+}
+
+/// Check that the password does not contain any of the context-specific strings, such as the
+/// username or the email address. It is meant to be called before hashing a new password.
+///
+/// The comparison is case-insensitive and both the password and the context strings are
+/// normalized using NFKC beforehand. Empty context strings are ignored.
+///
+/// ## Examples
+/// ```
+/// use libreauth::pass::{check_not_in_context, ErrorCode};
+///
+/// let context = ["jdoe", "john.doe@example.org"];
+/// assert!(check_not_in_context("correct horse battery staple", &context).is_ok());
+/// assert!(matches!(
+///     check_not_in_context("JDoe2024!", &context),
+///     Err(ErrorCode::PasswordContainsContext)
+/// ));
+/// ```
+pub fn check_not_in_context(password: &str, context: &[&str]) -> Result<(), ErrorCode> {
+    let password = normalize(password);
+    for ctx in context.iter().map(|c| normalize(c)) {
+        if !ctx.is_empty() && password.contains(&ctx) {
+            return Err(ErrorCode::PasswordContainsContext);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_match() {
+        let context = ["jdoe", "john.doe@example.org"];
+        assert!(matches!(
+            check_not_in_context("jdoe", &context),
+            Err(ErrorCode::PasswordContainsContext)
+        ));
+        assert!(matches!(
+            check_not_in_context("John.Doe@Example.org", &context),
+            Err(ErrorCode::PasswordContainsContext)
+        ));
+    }
+
+    #[test]
+    fn test_substring_match() {
+        let context = ["jdoe"];
+        assert!(matches!(
+            check_not_in_context("my-JDOE-password", &context),
+            Err(ErrorCode::PasswordContainsContext)
+        ));
+        assert!(matches!(
+            check_not_in_context("\u{ff4a}doe1234", &context),
+            Err(ErrorCode::PasswordContainsContext)
+        ));
+    }
+
+    #[test]
+    fn test_no_match() {
+        assert!(check_not_in_context("correct horse battery staple", &["jdoe"]).is_ok());
+        assert!(check_not_in_context("correct horse battery staple", &[""]).is_ok());
+        assert!(check_not_in_context("correct horse battery staple", &[]).is_ok());
+    }
+}
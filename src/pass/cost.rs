@@ -0,0 +1,102 @@
+use super::{Algorithm, ErrorCode, Hasher};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Summary of the effective cost of a single password hash computation.
+///
+/// The summary is computed by interpreting the parameters of the [Hasher](crate::pass::Hasher),
+/// the password is never hashed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CostSummary {
+    /// Algorithm used to hash the password.
+    pub algorithm: Algorithm,
+    /// Memory required, in KiB. Only relevant for Argon2.
+    pub memory_kib: Option<u64>,
+    /// Number of passes over the memory. Only relevant for Argon2.
+    pub passes: Option<u32>,
+    /// Number of iterations. Only relevant for PBKDF2.
+    pub iterations: Option<u32>,
+    /// Estimated number of compression function calls. For Argon2, this is the number of 1 KiB
+    /// blocks computed. For PBKDF2, each iteration requires two calls to the underlying hash
+    /// function's compression function.
+    pub work_factor: u64,
+}
+
+fn get_param<T: FromStr>(params: &HashMap<String, String>, name: &str) -> Result<T, ErrorCode> {
+    params
+        .get(name)
+        .ok_or(ErrorCode::InvalidPasswordFormat)?
+        .parse::<T>()
+        .map_err(|_| ErrorCode::InvalidPasswordFormat)
+}
+
+impl CostSummary {
+    pub(crate) fn from_hasher(hasher: &Hasher) -> Result<CostSummary, ErrorCode> {
+        let params = hasher.get_hash_func()?.get_parameters();
+        CostSummary::from_parameters(hasher.algorithm, &params)
+    }
+
+    /// Interpret the parameters returned by the hashing function.
+    pub(crate) fn from_parameters(
+        algorithm: Algorithm,
+        params: &HashMap<String, String>,
+    ) -> Result<CostSummary, ErrorCode> {
+        match algorithm {
+            Algorithm::Argon2 => {
+                let memory_kib = match get_param::<u32>(params, "mem") {
+                    Ok(mem) => 1u64 << mem,
+                    Err(_) => get_param(params, "m")?,
+                };
+                let passes: u32 = get_param(params, "passes")?;
+                Ok(CostSummary {
+                    algorithm,
+                    memory_kib: Some(memory_kib),
+                    passes: Some(passes),
+                    iterations: None,
+                    work_factor: memory_kib * u64::from(passes),
+                })
+            }
+            Algorithm::Pbkdf2 => {
+                let iterations: u32 = get_param(params, "iter")?;
+                Ok(CostSummary {
+                    algorithm,
+                    memory_kib: None,
+                    passes: None,
+                    iterations: Some(iterations),
+                    work_factor: 2 * u64::from(iterations),
+                })
+            }
+        }
+    }
+}
+
+/// Estimate the average time required to brute-force a single password hashed with the
+/// specified cost.
+///
+/// This is an order-of-magnitude estimate meant for documentation and compliance purposes, not a
+/// guarantee. The formula is:
+///
+/// `time = 2^(password_entropy_bits - 1) * work_factor / guesses_per_second`
+///
+/// where:
+/// - `2^(password_entropy_bits - 1)` is the average number of guesses, half of the key space;
+/// - `work_factor` comes from the [CostSummary];
+/// - `guesses_per_second` is the attacker's throughput expressed as the number of guesses per
+///   second against a hash having a work factor of 1, i.e. the number of compression function
+///   calls per second.
+///
+/// The result saturates at [Duration::MAX]. A non-positive throughput also yields
+/// [Duration::MAX].
+pub fn estimate_crack_cost(
+    summary: &CostSummary,
+    guesses_per_second: f64,
+    password_entropy_bits: f64,
+) -> Duration {
+    let guesses = (password_entropy_bits - 1.0).exp2();
+    let secs = guesses * summary.work_factor as f64 / guesses_per_second;
+    if secs.is_nan() || guesses_per_second <= 0.0 || secs >= Duration::MAX.as_secs_f64() {
+        return Duration::MAX;
+    }
+    Duration::from_secs_f64(secs.max(0.0))
+}
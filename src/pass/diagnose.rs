@@ -0,0 +1,292 @@
+use super::hash_builder::{get_algorithm, ARGON2_REFERENCE_VERSION, UNAVAILABLE_ALGORITHMS};
+use super::phc::{decode_metadata, PHCData};
+use super::{
+    argon2, pbkdf2, Algorithm, CostSummary, ErrorCode, HashingFunction, Normalization,
+    INTERNAL_VERSION, MAX_METADATA_LEN, MAX_PASSWORD_LEN, MAX_VERSION,
+};
+use crate::hash::HashFunction;
+use std::str::FromStr;
+
+const ARGON2_PARAMETERS: [&str; 8] = ["passes", "mem", "lanes", "len", "t", "p", "m", "variant"];
+const PBKDF2_PARAMETERS: [&str; 3] = ["iter", "hash", "hmac"];
+
+/// Problem found in a stored password hash by [diagnose].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// The string is not in the PHC format. No further analysis is possible.
+    InvalidFormat,
+    /// The algorithm identifier is not recognized.
+    UnknownAlgorithm(String),
+    /// The algorithm is well-known but not available in this library.
+    AlgorithmNotAvailable(String),
+    /// The version segment (`$v=`) is either missing, unsupported or unexpected.
+    InvalidVersion,
+    /// The salt is missing.
+    MissingSalt,
+    /// The salt length is not supported by the algorithm.
+    InvalidSalt,
+    /// The hash is missing.
+    MissingHash,
+    /// The parameter is not recognized.
+    UnknownParameter(String),
+    /// The parameter's value is invalid or out of range.
+    InvalidParameter(String),
+    /// The cost is lower than the default one.
+    WeakCost,
+}
+
+fn is_in_range(value: &str, min: usize, max: usize) -> bool {
+    matches!(value.parse::<usize>(), Ok(n) if (min..=max).contains(&n))
+}
+
+fn is_valid_common_parameter(name: &str, value: &str) -> Option<bool> {
+    Some(match name {
+        "norm" => Normalization::from_str(value).is_ok(),
+        "fold" | "fixedsalt" => value == "1",
+        "len-calc" => matches!(value, "bytes" | "chars" | "graphemes"),
+        "pmin" | "pmax" => is_in_range(value, 0, MAX_PASSWORD_LEN),
+        "meta" => decode_metadata(value).map_or(false, |m| m.len() <= MAX_METADATA_LEN),
+        "ts" => value.parse::<u64>().is_ok(),
+        "ver" => is_in_range(value, INTERNAL_VERSION, MAX_VERSION),
+        "xhmac" => matches!(value.to_lowercase().as_str(), "before" | "after" | "none"),
+        "xhmac-alg" => HashFunction::from_str(value).is_ok(),
+        // Those cannot be checked without the corresponding secret.
+        "mac" | "pmac" | "secret" => true,
+        _ => return None,
+    })
+}
+
+fn get_cost(
+    algorithm: Option<Algorithm>,
+    hash_func: &Option<Box<dyn HashingFunction>>,
+) -> Option<CostSummary> {
+    let params = hash_func.as_ref()?.get_parameters();
+    CostSummary::from_parameters(algorithm?, &params).ok()
+}
+
+/// Analyze a stored password hash and report every problem found instead of stopping at the
+/// first one, as [HashBuilder::from_phc](crate::pass::HashBuilder::from_phc) does. This is meant
+/// to help diagnose a suspicious hash: an empty list does not guarantee that the hash can be
+/// loaded, since some checks require a secret.
+///
+/// The cost is only checked if all the algorithm's parameters are valid. It is considered weak
+/// when its [work factor](CostSummary::work_factor) is lower than the default one.
+///
+/// ## Examples
+/// ```
+/// use libreauth::pass::{diagnose, Diagnostic};
+///
+/// let diagnostics = diagnose("$pbkdf2$iter=100000,hmac=md4,foo=bar");
+/// assert_eq!(diagnostics, vec![
+///     Diagnostic::MissingSalt,
+///     Diagnostic::MissingHash,
+///     Diagnostic::UnknownParameter("foo".to_string()),
+///     Diagnostic::InvalidParameter("hmac".to_string()),
+/// ]);
+/// ```
+pub fn diagnose(phc: &str) -> Vec<Diagnostic> {
+    let data = match PHCData::from_str(phc) {
+        Ok(data) => data,
+        Err(_) => {
+            return match phc.split('$').nth(1) {
+                Some(id) if UNAVAILABLE_ALGORITHMS.contains(&id) => {
+                    vec![Diagnostic::AlgorithmNotAvailable(id.to_string())]
+                }
+                _ => vec![Diagnostic::InvalidFormat],
+            };
+        }
+    };
+    let mut diagnostics = Vec::new();
+    let (algorithm, reference_variant) = match get_algorithm(&data.id) {
+        Ok((algorithm, variant)) => (Some(algorithm), variant),
+        Err(ErrorCode::AlgorithmNotAvailable) => {
+            diagnostics.push(Diagnostic::AlgorithmNotAvailable(data.id.clone()));
+            (None, None)
+        }
+        Err(_) => {
+            diagnostics.push(Diagnostic::UnknownAlgorithm(data.id.clone()));
+            (None, None)
+        }
+    };
+    let expected_version = reference_variant.map(|_| ARGON2_REFERENCE_VERSION);
+    if algorithm.is_some() && data.version != expected_version {
+        diagnostics.push(Diagnostic::InvalidVersion);
+    }
+    let mut hash_func: Option<Box<dyn HashingFunction>> = match algorithm {
+        Some(Algorithm::Argon2) => Some(Box::new(argon2::Argon2Hash::without_salt())),
+        Some(Algorithm::Pbkdf2) => Some(Box::new(pbkdf2::Pbkdf2Hash::without_salt())),
+        None => None,
+    };
+    match data.salt {
+        Some(salt) => {
+            if let Some(ref mut f) = hash_func {
+                if f.set_salt(salt).is_err() {
+                    diagnostics.push(Diagnostic::InvalidSalt);
+                }
+            }
+        }
+        None => diagnostics.push(Diagnostic::MissingSalt),
+    }
+    if data.hash.is_none() {
+        diagnostics.push(Diagnostic::MissingHash);
+    }
+
+    let mut parameters: Vec<(&String, &String)> = data.parameters.iter().collect();
+    parameters.sort();
+    let mut invalid_algorithm_param = false;
+    let default_cost = get_cost(algorithm, &hash_func);
+    if let (Some(f), Some(variant)) = (&mut hash_func, reference_variant) {
+        let _ = f.set_parameter("variant", variant);
+    }
+    for (name, value) in parameters {
+        if let Some(valid) = is_valid_common_parameter(name, value) {
+            if !valid {
+                diagnostics.push(Diagnostic::InvalidParameter(name.to_string()));
+            }
+            continue;
+        }
+        let known = match algorithm {
+            Some(Algorithm::Argon2) => ARGON2_PARAMETERS.contains(&name.as_str()),
+            Some(Algorithm::Pbkdf2) => PBKDF2_PARAMETERS.contains(&name.as_str()),
+            None => continue,
+        };
+        if !known {
+            diagnostics.push(Diagnostic::UnknownParameter(name.to_string()));
+            continue;
+        }
+        if let Some(ref mut f) = hash_func {
+            if f.set_parameter(name, value).is_err() {
+                diagnostics.push(Diagnostic::InvalidParameter(name.to_string()));
+                invalid_algorithm_param = true;
+            }
+        }
+    }
+    if let (Some(min), Some(max)) = (data.parameters.get("pmin"), data.parameters.get("pmax")) {
+        if let (Ok(min), Ok(max)) = (min.parse::<usize>(), max.parse::<usize>()) {
+            if min > max {
+                diagnostics.push(Diagnostic::InvalidParameter("pmin".to_string()));
+            }
+        }
+    }
+
+    if !invalid_algorithm_param {
+        if let (Some(cost), Some(default_cost)) = (get_cost(algorithm, &hash_func), default_cost) {
+            if cost.work_factor < default_cost.work_factor {
+                diagnostics.push(Diagnostic::WeakCost);
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Check the structure of a stored password hash without creating a
+/// [Hasher](crate::pass::Hasher), for example in order to cheaply validate the entries of a file
+/// before importing them. The checks are those of [diagnose], except that a weak cost is not
+/// considered an error. As for [diagnose], some checks require a secret and are not performed.
+///
+/// Returns [AlgorithmNotAvailable](ErrorCode::AlgorithmNotAvailable) if the algorithm is not
+/// available in this library and [InvalidPasswordFormat](ErrorCode::InvalidPasswordFormat) for any
+/// other problem.
+///
+/// ## Examples
+/// ```
+/// use libreauth::pass::validate_phc;
+///
+/// assert!(validate_phc("$pbkdf2$iter=45000,hmac=sha512$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc").is_ok());
+/// assert!(validate_phc("$pbkdf2$iter=45000,hmac=md4$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc").is_err());
+/// ```
+pub fn validate_phc(phc: &str) -> Result<(), ErrorCode> {
+    let diagnostics = diagnose(phc);
+    match diagnostics.iter().find(|d| **d != Diagnostic::WeakCost) {
+        Some(Diagnostic::AlgorithmNotAvailable(_)) => Err(ErrorCode::AlgorithmNotAvailable),
+        Some(_) => Err(ErrorCode::InvalidPasswordFormat),
+        None => Ok(()),
+    }
+}
+
+/// Check the structure of a stored password hash, as [validate_phc] does.
+pub fn is_valid_phc(phc: &str) -> bool {
+    validate_phc(phc).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid() {
+        let phc = "$pbkdf2$iter=45000,hmac=sha512$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+        assert!(diagnose(phc).is_empty());
+        let phc = "$argon2id$v=19$m=65536,t=2,p=1$c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc";
+        assert!(diagnose(phc).is_empty());
+    }
+
+    #[test]
+    fn test_multiple_problems() {
+        let phc = "$argon2$v=19$mem=4,passes=1,lanes=2,pmin=12,pmax=8,norm=nfx,foo=bar$c29tZQ";
+        assert_eq!(
+            diagnose(phc),
+            vec![
+                Diagnostic::InvalidVersion,
+                Diagnostic::InvalidSalt,
+                Diagnostic::MissingHash,
+                Diagnostic::UnknownParameter("foo".to_string()),
+                Diagnostic::InvalidParameter("mem".to_string()),
+                Diagnostic::InvalidParameter("norm".to_string()),
+                Diagnostic::InvalidParameter("pmin".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_weak_cost() {
+        let phc = "$argon2$mem=10,passes=2$c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc";
+        assert_eq!(diagnose(phc), vec![Diagnostic::WeakCost]);
+        let phc = "$pbkdf2$iter=10000$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+        assert_eq!(diagnose(phc), vec![Diagnostic::WeakCost]);
+    }
+
+    #[test]
+    fn test_validate_phc() {
+        for phc in [
+            "$pbkdf2$iter=45000,hmac=sha512$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+            "$pbkdf2$iter=10000$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+            "$argon2id$v=19$m=65536,t=2,p=1$c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc",
+        ] {
+            assert!(validate_phc(phc).is_ok());
+            assert!(is_valid_phc(phc));
+        }
+        for phc in [
+            "",
+            "not a hash",
+            "$pbkdf2$iter=45000",
+            "$pbkdf2$iter=45000$RSF4Aw",
+            "$pbkdf2$iter=1$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+            "$pbkdf2$iter=45000,foo=bar$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+            "$md5$x=1$c29tZXNhbHQ$c29tZXNhbHQ",
+        ] {
+            assert!(matches!(
+                validate_phc(phc),
+                Err(ErrorCode::InvalidPasswordFormat)
+            ));
+            assert!(!is_valid_phc(phc));
+        }
+        assert!(matches!(
+            validate_phc("$2b$12$R9h/cIPz0gi.URNNX3kh2OPST9/PgBkqquzi.Ss7KIUgO2t0jWMUW"),
+            Err(ErrorCode::AlgorithmNotAvailable)
+        ));
+    }
+
+    #[test]
+    fn test_algorithm() {
+        assert_eq!(
+            diagnose("$md5$x=1$c29tZXNhbHQ$c29tZXNhbHQ"),
+            vec![Diagnostic::UnknownAlgorithm("md5".to_string())]
+        );
+        assert_eq!(
+            diagnose("$2b$12$R9h/cIPz0gi.URNNX3kh2OPST9/PgBkqquzi.Ss7KIUgO2t0jWMUW"),
+            vec![Diagnostic::AlgorithmNotAvailable("2b".to_string())]
+        );
+        assert_eq!(diagnose("not a hash"), vec![Diagnostic::InvalidFormat]);
+    }
+}
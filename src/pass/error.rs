@@ -1,3 +1,5 @@
+use std::fmt;
+
 /// Error codes used both in the rust and C interfaces.
 ///
 /// ## C interface
@@ -24,6 +26,18 @@
 ///             <td>LIBREAUTH_PASS_PASSWORD_TOO_LONG</td>
 ///         </tr>
 ///         <tr>
+///             <td>PasswordContainsContext</td>
+///             <td>LIBREAUTH_PASS_PASSWORD_CONTAINS_CONTEXT</td>
+///         </tr>
+///         <tr>
+///             <td>PasswordContainsUnassigned</td>
+///             <td>LIBREAUTH_PASS_PASSWORD_CONTAINS_UNASSIGNED</td>
+///         </tr>
+///         <tr>
+///             <td>PasswordWhitespaceOnly</td>
+///             <td>LIBREAUTH_PASS_PASSWORD_WHITESPACE_ONLY</td>
+///         </tr>
+///         <tr>
 ///             <td>InvalidPasswordFormat</td>
 ///             <td>LIBREAUTH_PASS_INVALID_PASSWORD_FORMAT</td>
 ///         </tr>
@@ -32,6 +46,22 @@
 ///             <td>LIBREAUTH_PASS_INCOMPATIBLE_OPTION</td>
 ///         </tr>
 ///         <tr>
+///             <td>AlgorithmNotAvailable</td>
+///             <td>LIBREAUTH_PASS_ALGORITHM_NOT_AVAILABLE</td>
+///         </tr>
+///         <tr>
+///             <td>RandomnessFailure</td>
+///             <td>LIBREAUTH_PASS_RANDOMNESS_FAILURE</td>
+///         </tr>
+///         <tr>
+///             <td>Timeout</td>
+///             <td>LIBREAUTH_PASS_TIMEOUT</td>
+///         </tr>
+///         <tr>
+///             <td>AlgorithmNotAllowed</td>
+///             <td>LIBREAUTH_PASS_ALGORITHM_NOT_ALLOWED</td>
+///         </tr>
+///         <tr>
 ///             <td>NotEnoughSpace</td>
 ///             <td>LIBREAUTH_PASS_NOT_ENOUGH_SPACE</td>
 ///         </tr>
@@ -43,6 +73,10 @@
 ///             <td>InvalidKeyLen</td>
 ///             <td>LIBREAUTH_PASS_INVALID_KEY_LEN</td>
 ///         </tr>
+///         <tr>
+///             <td>PasswordMismatch</td>
+///             <td>LIBREAUTH_PASS_PASSWORD_MISMATCH</td>
+///         </tr>
 ///     </tbody>
 /// </table>
 #[repr(C)]
@@ -54,16 +88,79 @@ pub enum ErrorCode {
     PasswordTooShort = 1,
     /// The password is longer than the maximal length.
     PasswordTooLong = 2,
+    /// The password contains a context-specific string, such as the username.
+    PasswordContainsContext = 3,
+    /// The password contains an unassigned Unicode code point.
+    PasswordContainsUnassigned = 4,
+    /// The password is only made of whitespace characters and
+    /// [reject_whitespace_only](crate::pass::HashBuilder::reject_whitespace_only) is enabled.
+    PasswordWhitespaceOnly = 5,
     /// The input does not respect the [storage format](crate::pass).
     InvalidPasswordFormat = 10,
     /// Some options you specified are incompatible.
     IncompatibleOption = 11,
-    /// Used in C-bindings to indicate the storage does not have enough space to store the data.
+    /// The input uses a known password hashing algorithm which is not available in this build.
+    AlgorithmNotAvailable = 12,
+    /// The system's random number generator failed to provide a salt.
+    RandomnessFailure = 13,
+    /// The password could not be checked within the time budget given to
+    /// [Hasher::verify_with_timeout](crate::pass::Hasher::verify_with_timeout).
+    Timeout = 14,
+    /// The input uses a password hashing algorithm which is not in the list set using
+    /// [allowed_algorithms](crate::pass::HashBuilder::allowed_algorithms).
+    AlgorithmNotAllowed = 15,
+    /// The storage does not have enough space to store the data. Used in C-bindings and by
+    /// [Hasher::hash_and_copy](crate::pass::Hasher::hash_and_copy).
     NotEnoughSpace = 20,
     /// Used in C-bindings to indicate a NULL pointer.
     NullPtr = 21,
     /// Used in C-bindings to indicate an invalid key length.
     InvalidKeyLen = 22,
+    /// Used in C-bindings to indicate that the password does not match the reference hash.
+    PasswordMismatch = 23,
+}
+
+/// Reason why a password does not satisfy the hasher's requirements, as reported by
+/// [check_policy](crate::pass::Hasher::check_policy). The offending limit is carried along
+/// so that a precise message can be shown to the user; the password itself never is.
+///
+/// It is mapped to the corresponding [ErrorCode] by the hashing functions and the C interface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PasswordPolicyError {
+    /// The password is shorter than the minimal length.
+    TooShort { min: usize },
+    /// The password is longer than the maximal length.
+    TooLong { max: usize },
+    /// The password contains unassigned Unicode code points while
+    /// [strict_unicode](crate::pass::HashBuilder::strict_unicode) is enabled.
+    ContainsUnassigned,
+    /// The password is only made of whitespace characters while
+    /// [reject_whitespace_only](crate::pass::HashBuilder::reject_whitespace_only) is enabled.
+    WhitespaceOnly,
+}
+
+impl fmt::Display for PasswordPolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordPolicyError::TooShort { min } => write!(f, "too short: minimum {}", min),
+            PasswordPolicyError::TooLong { max } => write!(f, "too long: maximum {}", max),
+            PasswordPolicyError::ContainsUnassigned => {
+                write!(f, "contains unassigned code points")
+            }
+            PasswordPolicyError::WhitespaceOnly => write!(f, "only made of whitespace"),
+        }
+    }
+}
+
+impl From<PasswordPolicyError> for ErrorCode {
+    fn from(error: PasswordPolicyError) -> Self {
+        match error {
+            PasswordPolicyError::TooShort { .. } => ErrorCode::PasswordTooShort,
+            PasswordPolicyError::TooLong { .. } => ErrorCode::PasswordTooLong,
+            PasswordPolicyError::ContainsUnassigned => ErrorCode::PasswordContainsUnassigned,
+            PasswordPolicyError::WhitespaceOnly => ErrorCode::PasswordWhitespaceOnly,
+        }
+    }
 }
 
 impl From<crypto_mac::InvalidKeyLength> for ErrorCode {
@@ -1,11 +1,24 @@
+use super::hasher::{get_integrity_mac, get_param_mac, HashFuncHook};
 use super::{
     std_default, std_nist, Algorithm, ErrorCode, Hasher, LengthCalculationMethod, Normalization,
-    PasswordStorageStandard, DEFAULT_USER_VERSION, INTERNAL_VERSION, XHMAC,
+    PasswordStorageStandard, SegmentLayout, DEFAULT_USER_VERSION, FIXED_SALT, INTEGRITY_MAC,
+    INTERNAL_VERSION, MAX_METADATA_LEN, MAX_PASSWORD_LEN, MAX_VERSION, METADATA, MIN_SALT_LEN_BITS,
+    PARAM_MAC, TIMESTAMP, XHMAC,
 };
 use crate::hash::HashFunction;
-use crate::pass::phc::PHCData;
+use crate::pass::phc::{decode_metadata, PHCData};
+use hmac::Mac;
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Identifiers of well-known password hashing algorithms which are not available.
+pub(crate) const UNAVAILABLE_ALGORITHMS: [&str; 5] = ["bcrypt", "2a", "2b", "2y", "scrypt"];
+
+/// Version of the reference Argon2 implementation (`$v=19`), the only one supported.
+pub(crate) const ARGON2_REFERENCE_VERSION: u32 = 19;
 
 macro_rules! get_pepper {
     ($pepper: ident) => {
@@ -16,6 +29,38 @@ macro_rules! get_pepper {
     };
 }
 
+/// Return the algorithm designated by a PHC identifier and, for the reference Argon2 format, the
+/// variant. Identifiers are matched exactly: they are neither split nor interpreted as aliases,
+/// so that a crafted identifier cannot be mistaken for another algorithm.
+pub(crate) fn get_algorithm(id: &str) -> Result<(Algorithm, Option<&'static str>), ErrorCode> {
+    match id {
+        "argon2" => Ok((Algorithm::Argon2, None)),
+        "argon2d" => Ok((Algorithm::Argon2, Some("d"))),
+        "argon2i" => Ok((Algorithm::Argon2, Some("i"))),
+        "argon2id" => Ok((Algorithm::Argon2, Some("id"))),
+        "pbkdf2" => Ok((Algorithm::Pbkdf2, None)),
+        id if UNAVAILABLE_ALGORITHMS.contains(&id) => Err(ErrorCode::AlgorithmNotAvailable),
+        _ => Err(ErrorCode::InvalidPasswordFormat),
+    }
+}
+
+/// Parse a numeric parameter from a PHC string, rejecting values outside of the specified
+/// bounds.
+fn get_bounded_param(
+    phc: &mut PHCData,
+    name: &str,
+    default: usize,
+    bounds: RangeInclusive<usize>,
+) -> Result<usize, ErrorCode> {
+    match phc.parameters.remove(name) {
+        Some(v) => match v.parse::<usize>() {
+            Ok(n) if bounds.contains(&n) => Ok(n),
+            _ => Err(ErrorCode::InvalidPasswordFormat),
+        },
+        None => Ok(default),
+    }
+}
+
 /// Builds a Hasher object.
 ///
 /// ## Examples
@@ -56,7 +101,7 @@ macro_rules! get_pepper {
 ///     .min_len(12)
 ///     .algorithm(libreauth::pass::Algorithm::Pbkdf2)
 ///     .add_param("hmac", "sha256")
-///     .add_param("norm", "nfkd")
+///     .normalization(libreauth::pass::Normalization::Nfkd)
 ///     .finalize() {
 ///     Ok(h) => h,
 ///     Err(e) => panic!("{:?}", e),
@@ -76,6 +121,32 @@ pub struct HashBuilder {
     pub(crate) version: usize,
     pub(crate) xhmac: XHMAC,
     pub(crate) xhmax_alg: HashFunction,
+    pub(crate) assume_normalized: bool,
+    pub(crate) accepted_versions: Option<RangeInclusive<usize>>,
+    pub(crate) runtime_error: Option<ErrorCode>,
+    pub(crate) param_mac: Option<Vec<u8>>,
+    pub(crate) soft_max_len: Option<usize>,
+    pub(crate) prefix: Option<String>,
+    pub(crate) comparison_hash: HashFunction,
+    pub(crate) argon2_secret: Option<Vec<u8>>,
+    pub(crate) timing_hook: Option<Arc<dyn Fn(Duration) + Send + Sync>>,
+    pub(crate) fingerprint_key: Option<Vec<u8>>,
+    pub(crate) strict_unicode: bool,
+    pub(crate) min_work_factor: Option<u64>,
+    pub(crate) verify_only: bool,
+    pub(crate) case_fold: bool,
+    pub(crate) integrity_key: Option<Vec<u8>>,
+    pub(crate) timestamp_hashes: bool,
+    pub(crate) auto_salt_len: bool,
+    pub(crate) explicit_salt_len: bool,
+    pub(crate) skip_length_check_on_verify: bool,
+    pub(crate) reject_whitespace_only: bool,
+    pub(crate) internal_version: usize,
+    pub(crate) forbidden_hashes: Vec<HashFunction>,
+    pub(crate) metadata: Option<String>,
+    pub(crate) allowed_algorithms: Option<Vec<Algorithm>>,
+    pub(crate) fixed_salt: bool,
+    pub(super) hash_func_hook: Option<HashFuncHook>,
 }
 
 impl Default for HashBuilder {
@@ -95,7 +166,7 @@ impl HashBuilder {
         match std {
             PasswordStorageStandard::NoStandard => HashBuilder {
                 standard: PasswordStorageStandard::NoStandard,
-                normalization: std_default::DEFAULT_NORMALIZATION,
+                normalization: std.default_normalization(),
                 min_len: std_default::DEFAULT_PASSWORD_MIN_LEN,
                 max_len: std_default::DEFAULT_PASSWORD_MAX_LEN,
                 algorithm: std_default::DEFAULT_ALGORITHM,
@@ -107,10 +178,36 @@ impl HashBuilder {
                 version: DEFAULT_USER_VERSION + INTERNAL_VERSION,
                 xhmac: XHMAC::None,
                 xhmax_alg: std_default::DEFAULT_XHMAC_ALGORITHM,
+                assume_normalized: false,
+                accepted_versions: None,
+                runtime_error: None,
+                param_mac: None,
+                soft_max_len: None,
+                prefix: None,
+                comparison_hash: std_default::DEFAULT_COMPARISON_HASH,
+                argon2_secret: None,
+                timing_hook: None,
+                fingerprint_key: None,
+                strict_unicode: false,
+                min_work_factor: None,
+                verify_only: false,
+                case_fold: false,
+                integrity_key: None,
+                timestamp_hashes: false,
+                auto_salt_len: false,
+                explicit_salt_len: false,
+                skip_length_check_on_verify: false,
+                reject_whitespace_only: false,
+                internal_version: INTERNAL_VERSION,
+                forbidden_hashes: Vec::new(),
+                metadata: None,
+                allowed_algorithms: None,
+                fixed_salt: false,
+                hash_func_hook: None,
             },
             PasswordStorageStandard::Nist80063b => HashBuilder {
                 standard: PasswordStorageStandard::Nist80063b,
-                normalization: std_nist::DEFAULT_NORMALIZATION,
+                normalization: std.default_normalization(),
                 min_len: std_nist::DEFAULT_PASSWORD_MIN_LEN,
                 max_len: std_nist::DEFAULT_PASSWORD_MAX_LEN,
                 algorithm: std_nist::DEFAULT_ALGORITHM,
@@ -122,65 +219,332 @@ impl HashBuilder {
                 version: DEFAULT_USER_VERSION + INTERNAL_VERSION,
                 xhmac: XHMAC::None,
                 xhmax_alg: std_nist::DEFAULT_XHMAC_ALGORITHM,
+                assume_normalized: false,
+                accepted_versions: None,
+                runtime_error: None,
+                param_mac: None,
+                soft_max_len: None,
+                prefix: None,
+                comparison_hash: std_nist::DEFAULT_COMPARISON_HASH,
+                argon2_secret: None,
+                timing_hook: None,
+                fingerprint_key: None,
+                strict_unicode: false,
+                min_work_factor: None,
+                verify_only: false,
+                case_fold: false,
+                integrity_key: None,
+                timestamp_hashes: false,
+                auto_salt_len: false,
+                explicit_salt_len: false,
+                skip_length_check_on_verify: false,
+                reject_whitespace_only: false,
+                internal_version: INTERNAL_VERSION,
+                forbidden_hashes: Vec::new(),
+                metadata: None,
+                allowed_algorithms: None,
+                fixed_salt: false,
+                hash_func_hook: None,
             },
         }
     }
 
     /// Create a new Hasher object from a PHC formatted string.
+    ///
+    /// The metadata of the stored hash, such as its [algorithm](Hasher::algorithm),
+    /// [parameters](Hasher::parameters) and [version](Hasher::version), is available from the
+    /// returned Hasher without parsing the string again.
+    ///
+    /// Strings longer than 4096 bytes are rejected before being parsed. Use
+    /// [from_phc_max_len](HashBuilder::from_phc_max_len) to change this limit.
     pub fn from_phc(data: &str) -> Result<Hasher, ErrorCode> {
-        HashBuilder::from_phc_internal(data, None)
+        HashBuilder::from_phc_internal(data, None, None, None)
+    }
+
+    /// Create a new Hasher object from a PHC formatted string, as [from_phc](HashBuilder::from_phc)
+    /// does, but rejects with [InvalidPasswordFormat](ErrorCode::InvalidPasswordFormat) strings
+    /// longer than `max_len` bytes instead of 4096 bytes. The length is checked before any
+    /// parsing, which prevents huge untrusted inputs from consuming resources.
+    ///
+    /// ## Examples
+    /// ```
+    /// use libreauth::pass::{ErrorCode, HashBuilder};
+    ///
+    /// let stored_hash = "$pbkdf2$iter=21000,hmac=sha256$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+    /// assert!(HashBuilder::from_phc_max_len(stored_hash, 128).is_ok());
+    /// assert!(matches!(
+    ///     HashBuilder::from_phc_max_len(stored_hash, 32),
+    ///     Err(ErrorCode::InvalidPasswordFormat)
+    /// ));
+    /// ```
+    pub fn from_phc_max_len(data: &str, max_len: usize) -> Result<Hasher, ErrorCode> {
+        match PHCData::from_str_max_len(data, max_len) {
+            Ok(phc) => HashBuilder::from_phc_data(phc, None, None, None),
+            Err(_) => Err(HashBuilder::get_parse_error(data)),
+        }
+    }
+
+    /// Create a new Hasher object from a PHC formatted string stored as bytes, for example in a
+    /// binary database column, without requiring a prior UTF-8 validation. Any byte which is not
+    /// a printable ASCII character is rejected with
+    /// [InvalidPasswordFormat](ErrorCode::InvalidPasswordFormat).
+    pub fn from_phc_bytes(data: &[u8]) -> Result<Hasher, ErrorCode> {
+        if !data.iter().all(u8::is_ascii_graphic) {
+            return Err(ErrorCode::InvalidPasswordFormat);
+        }
+        let data = std::str::from_utf8(data).map_err(|_| ErrorCode::InvalidPasswordFormat)?;
+        HashBuilder::from_phc(data)
     }
 
     /// Create a new Hasher object from a PHC formatted string and an external pepper for an additional HMAC.
     pub fn from_phc_xhmac(data: &str, pepper: &[u8]) -> Result<Hasher, ErrorCode> {
-        HashBuilder::from_phc_internal(data, Some(pepper.to_vec()))
+        HashBuilder::from_phc_internal(data, Some(pepper.to_vec()), None, None)
     }
 
-    fn from_phc_internal(data: &str, pepper: Option<Vec<u8>>) -> Result<Hasher, ErrorCode> {
-        let mut phc = match PHCData::from_str(data) {
-            Ok(v) => v,
-            Err(_) => return Err(ErrorCode::InvalidPasswordFormat),
-        };
+    /// Create a new Hasher object from a PHC formatted string whose parameters are authenticated
+    /// using the specified secret. See [param_mac](HashBuilder::param_mac).
+    pub fn from_phc_param_mac(data: &str, secret: &[u8]) -> Result<Hasher, ErrorCode> {
+        HashBuilder::from_phc_internal(data, None, Some(secret.to_vec()), None)
+    }
+
+    /// Create a new Hasher object from a PHC formatted string whose integrity is authenticated
+    /// using the specified secret. See [integrity_key](HashBuilder::integrity_key).
+    pub fn from_phc_integrity(data: &str, secret: &[u8]) -> Result<Hasher, ErrorCode> {
+        let mut phc = PHCData::from_str(data).map_err(|_| HashBuilder::get_parse_error(data))?;
+        let tag = phc
+            .parameters
+            .remove(INTEGRITY_MAC)
+            .ok_or(ErrorCode::InvalidPasswordFormat)?;
+        let tag = hex::decode(tag).map_err(|_| ErrorCode::InvalidPasswordFormat)?;
+        get_integrity_mac(secret, &phc)?
+            .verify_slice(&tag)
+            .map_err(|_| ErrorCode::InvalidPasswordFormat)?;
+        let mut hasher = HashBuilder::from_phc_data(phc, None, None, None)?;
+        hasher.integrity_key = Some(secret.to_vec());
+        Ok(hasher)
+    }
+
+    /// Create a new Hasher object from a PHC formatted string and the secret key used by Argon2.
+    /// See [argon2_secret](HashBuilder::argon2_secret).
+    pub fn from_phc_argon2_secret(data: &str, key: &[u8]) -> Result<Hasher, ErrorCode> {
+        HashBuilder::from_phc_internal(data, None, None, Some(key.to_vec()))
+    }
+
+    /// Create a new Hasher object from a PHC formatted string which may start with unrecognized
+    /// segments, such as a function-family prefix. Those are kept and emitted unchanged when
+    /// hashing a password. A prefix segment which is itself an algorithm identifier is
+    /// ambiguous and therefore rejected.
+    ///
+    /// As with [from_phc](HashBuilder::from_phc), strings longer than 4096 bytes are rejected
+    /// before being parsed. The prefix may not have more than 4 segments.
+    pub fn from_phc_prefixed(data: &str) -> Result<Hasher, ErrorCode> {
+        match PHCData::from_str_prefixed(data) {
+            Ok(phc) => {
+                if let Some(ref prefix) = phc.prefix {
+                    if prefix.split('$').any(|s| get_algorithm(s).is_ok()) {
+                        return Err(ErrorCode::InvalidPasswordFormat);
+                    }
+                }
+                HashBuilder::from_phc_data(phc, None, None, None)
+            }
+            Err(_) => Err(HashBuilder::get_parse_error(data)),
+        }
+    }
+
+    /// Create a new Hasher object from a string whose salt and hash segments are in the
+    /// specified order.
+    ///
+    /// This is a migration aid meant to rescue hashes exported by a tool known to have swapped
+    /// those segments: the layout is never guessed. Once loaded, such a hash should be replaced
+    /// by a new one, which uses the standard order.
+    ///
+    /// ## Examples
+    /// ```
+    /// use libreauth::pass::{HashBuilder, SegmentLayout};
+    ///
+    /// let stored_hash = "$pbkdf2$iter=45000,hmac=sha512$LwCbGeQoBZIraYoDZ8Oe/PxdJHc$RSF4Aw";
+    /// let checker = HashBuilder::from_phc_with_layout(stored_hash, SegmentLayout::HashSalt);
+    /// assert!(checker.is_ok());
+    /// ```
+    pub fn from_phc_with_layout(data: &str, layout: SegmentLayout) -> Result<Hasher, ErrorCode> {
+        let mut phc = PHCData::from_str(data).map_err(|_| HashBuilder::get_parse_error(data))?;
+        if layout == SegmentLayout::HashSalt {
+            if phc.hash.is_none() {
+                return Err(ErrorCode::InvalidPasswordFormat);
+            }
+            std::mem::swap(&mut phc.salt, &mut phc.hash);
+        }
+        HashBuilder::from_phc_data(phc, None, None, None)
+    }
+
+    /// Create a new Hasher object from a PHC formatted string, rejecting any string that is not
+    /// in its canonical form (e.g. having a trailing `$` or an empty trailing segment).
+    pub fn from_phc_strict(data: &str) -> Result<Hasher, ErrorCode> {
+        match PHCData::from_str_strict(data) {
+            Ok(phc) => HashBuilder::from_phc_data(phc, None, None, None),
+            Err(_) => Err(HashBuilder::get_parse_error(data)),
+        }
+    }
+
+    fn from_phc_internal(
+        data: &str,
+        pepper: Option<Vec<u8>>,
+        param_mac: Option<Vec<u8>>,
+        argon2_secret: Option<Vec<u8>>,
+    ) -> Result<Hasher, ErrorCode> {
+        match PHCData::from_str(data) {
+            Ok(phc) => HashBuilder::from_phc_data(phc, pepper, param_mac, argon2_secret),
+            Err(_) => Err(HashBuilder::get_parse_error(data)),
+        }
+    }
+
+    /// Create a new Hasher object from a PHC formatted string, as [from_phc](HashBuilder::from_phc)
+    /// does, unless the stored hash uses an algorithm which is not allowed by this builder, in
+    /// which case [AlgorithmNotAllowed](ErrorCode::AlgorithmNotAllowed) is returned before any
+    /// other processing. See [allowed_algorithms](HashBuilder::allowed_algorithms).
+    ///
+    /// The other options of this builder are not used.
+    ///
+    /// ## Examples
+    /// ```
+    /// use libreauth::pass::{Algorithm, ErrorCode, HashBuilder};
+    ///
+    /// let stored_hash = "$pbkdf2$iter=21000,hmac=sha256$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+    /// let res = HashBuilder::new()
+    ///     .allowed_algorithms(&[Algorithm::Argon2])
+    ///     .load_phc(stored_hash);
+    /// assert!(matches!(res, Err(ErrorCode::AlgorithmNotAllowed)));
+    /// ```
+    pub fn load_phc(&self, data: &str) -> Result<Hasher, ErrorCode> {
+        let phc = PHCData::from_str(data).map_err(|_| HashBuilder::get_parse_error(data))?;
+        let (algorithm, _) = get_algorithm(&phc.id)?;
+        self.check_algorithm_allowed(algorithm)?;
+        HashBuilder::from_phc_data(phc, None, None, None)
+    }
+
+    fn check_algorithm_allowed(&self, algorithm: Algorithm) -> Result<(), ErrorCode> {
+        match &self.allowed_algorithms {
+            Some(allowed) if !allowed.contains(&algorithm) => Err(ErrorCode::AlgorithmNotAllowed),
+            _ => Ok(()),
+        }
+    }
+
+    /// Some algorithms, like bcrypt, do not use the PHC format: their identifier must therefore
+    /// be checked even if the string could not be parsed.
+    fn get_parse_error(data: &str) -> ErrorCode {
+        match data.split('$').nth(1) {
+            Some(id) if UNAVAILABLE_ALGORITHMS.contains(&id) => ErrorCode::AlgorithmNotAvailable,
+            _ => ErrorCode::InvalidPasswordFormat,
+        }
+    }
+
+    fn from_phc_data(
+        mut phc: PHCData,
+        pepper: Option<Vec<u8>>,
+        param_mac: Option<Vec<u8>>,
+        argon2_secret: Option<Vec<u8>>,
+    ) -> Result<Hasher, ErrorCode> {
+        let (algorithm, reference_variant) = get_algorithm(&phc.id)?;
+        // Hashes produced by the reference Argon2 implementation, and the libraries based on it,
+        // neither normalize the password nor restrict its length.
+        let is_reference = reference_variant.is_some();
+        match (reference_variant, phc.version) {
+            (Some(variant), Some(ARGON2_REFERENCE_VERSION)) => {
+                phc.parameters
+                    .insert("variant".to_string(), variant.to_string());
+                if let Some(ref hash) = phc.hash {
+                    phc.parameters
+                        .insert("len".to_string(), hash.len().to_string());
+                }
+            }
+            (None, None) => {
+                // The hash must be derived again using the length of the stored one.
+                if let (Algorithm::Argon2, Some(ref hash)) = (algorithm, &phc.hash) {
+                    phc.parameters
+                        .entry("len".to_string())
+                        .or_insert_with(|| hash.len().to_string());
+                }
+            }
+            _ => return Err(ErrorCode::InvalidPasswordFormat),
+        }
+        match (&param_mac, phc.parameters.remove(PARAM_MAC)) {
+            (Some(secret), Some(tag)) => {
+                let tag = hex::decode(tag).map_err(|_| ErrorCode::InvalidPasswordFormat)?;
+                get_param_mac(secret, &phc.id, &phc.parameters)?
+                    .verify_slice(&tag)
+                    .map_err(|_| ErrorCode::InvalidPasswordFormat)?;
+            }
+            (None, None) => {}
+            _ => return Err(ErrorCode::InvalidPasswordFormat),
+        }
+        match (&argon2_secret, phc.parameters.remove("secret")) {
+            (Some(_), Some(v)) if v == "1" => {}
+            (None, None) => {}
+            _ => return Err(ErrorCode::InvalidPasswordFormat),
+        }
         let lc = match phc.parameters.remove("len-calc") {
             Some(v) => match v.as_str() {
                 "bytes" => LengthCalculationMethod::Bytes,
                 "chars" => LengthCalculationMethod::Characters,
+                "graphemes" => LengthCalculationMethod::Graphemes,
                 _ => return Err(ErrorCode::InvalidPasswordFormat),
             },
             None => LengthCalculationMethod::Characters,
         };
-        let norm = match phc.parameters.remove("norm") {
-            Some(v) => match v.as_str() {
-                "nfd" => Normalization::Nfd,
-                "nfkd" => Normalization::Nfkd,
-                "nfc" => Normalization::Nfc,
-                "nfkc" => Normalization::Nfkc,
-                "none" => Normalization::None,
-                _ => return Err(ErrorCode::InvalidPasswordFormat),
-            },
-            None => Normalization::Nfkc,
+        let case_fold = match phc.parameters.remove("fold") {
+            Some(v) if v == "1" => true,
+            Some(_) => return Err(ErrorCode::InvalidPasswordFormat),
+            None => false,
         };
-        let max_l = match phc.parameters.remove("pmax") {
-            Some(v) => match v.parse::<usize>() {
-                Ok(l) => l,
-                Err(_) => return Err(ErrorCode::InvalidPasswordFormat),
-            },
-            None => std_default::DEFAULT_PASSWORD_MAX_LEN,
+        let fixed_salt = match phc.parameters.remove(FIXED_SALT) {
+            Some(v) if v == "1" => true,
+            Some(_) => return Err(ErrorCode::InvalidPasswordFormat),
+            None => false,
         };
-        let min_l = match phc.parameters.remove("pmin") {
-            Some(v) => match v.parse::<usize>() {
-                Ok(l) => l,
-                Err(_) => return Err(ErrorCode::InvalidPasswordFormat),
-            },
-            None => std_default::DEFAULT_PASSWORD_MIN_LEN,
+        let created_at = match phc.parameters.remove(TIMESTAMP) {
+            Some(v) => Some(
+                v.parse::<u64>()
+                    .map_err(|_| ErrorCode::InvalidPasswordFormat)?,
+            ),
+            None => None,
         };
-        let version = match phc.parameters.remove("ver") {
-            Some(v) => match v.parse::<usize>() {
-                Ok(l) => l,
-                Err(_) => return Err(ErrorCode::InvalidPasswordFormat),
+        let metadata = match phc.parameters.remove(METADATA) {
+            Some(v) => match decode_metadata(&v) {
+                Some(m) if m.len() <= MAX_METADATA_LEN => Some(m),
+                _ => return Err(ErrorCode::InvalidPasswordFormat),
             },
-            None => DEFAULT_USER_VERSION + INTERNAL_VERSION,
+            None => None,
+        };
+        let norm = match phc.parameters.remove("norm") {
+            Some(v) => Normalization::from_str(&v)?,
+            None if is_reference => Normalization::None,
+            None => Normalization::Nfkc,
         };
+        let max_l = get_bounded_param(
+            &mut phc,
+            "pmax",
+            std_default::DEFAULT_PASSWORD_MAX_LEN,
+            0..=MAX_PASSWORD_LEN,
+        )?;
+        let min_l = get_bounded_param(
+            &mut phc,
+            "pmin",
+            if is_reference {
+                0
+            } else {
+                std_default::DEFAULT_PASSWORD_MIN_LEN
+            },
+            0..=MAX_PASSWORD_LEN,
+        )?;
+        if min_l > max_l {
+            return Err(ErrorCode::InvalidPasswordFormat);
+        }
+        let version = get_bounded_param(
+            &mut phc,
+            "ver",
+            DEFAULT_USER_VERSION + INTERNAL_VERSION,
+            INTERNAL_VERSION..=MAX_VERSION,
+        )?;
         let xhmac = match phc.parameters.remove("xhmac") {
             Some(when) => match when.to_lowercase().as_str() {
                 "before" => XHMAC::Before(get_pepper!(pepper)),
@@ -204,11 +568,7 @@ impl HashBuilder {
             normalization: norm,
             min_len: min_l,
             max_len: max_l,
-            algorithm: match phc.id.as_str() {
-                "argon2" => Algorithm::Argon2,
-                "pbkdf2" => Algorithm::Pbkdf2,
-                _ => return Err(ErrorCode::InvalidPasswordFormat),
-            },
+            algorithm,
             parameters: phc.parameters.clone(),
             ref_hash: phc.hash,
             salt_len: match &phc.salt {
@@ -220,12 +580,46 @@ impl HashBuilder {
             version,
             xhmac,
             xhmax_alg,
+            assume_normalized: false,
+            accepted_versions: None,
+            runtime_error: None,
+            param_mac,
+            soft_max_len: None,
+            prefix: phc.prefix,
+            comparison_hash: std_default::DEFAULT_COMPARISON_HASH,
+            argon2_secret,
+            timing_hook: None,
+            fingerprint_key: None,
+            strict_unicode: false,
+            min_work_factor: None,
+            verify_only: false,
+            case_fold,
+            integrity_key: None,
+            timestamp_hashes: created_at.is_some(),
+            auto_salt_len: false,
+            explicit_salt_len: false,
+            skip_length_check_on_verify: false,
+            reject_whitespace_only: false,
+            internal_version: INTERNAL_VERSION,
+            forbidden_hashes: Vec::new(),
+            metadata,
+            allowed_algorithms: None,
+            fixed_salt,
+            hash_func_hook: None,
         };
-        hash_builder.finalize()
+        let mut hasher = hash_builder.finalize()?;
+        hasher.created_at = created_at;
+        Ok(hasher)
     }
 
     /// Check the compatibility between options and create a Hasher object.
+    ///
+    /// The parameters are applied to the hashing function, so an invalid parameter is reported
+    /// here instead of when hashing a password.
     pub fn finalize(&self) -> Result<Hasher, ErrorCode> {
+        if let Some(e) = self.runtime_error {
+            return Err(e);
+        }
         match self.standard {
             PasswordStorageStandard::Nist80063b => {
                 if !std_nist::is_valid(self) {
@@ -234,7 +628,20 @@ impl HashBuilder {
             }
             PasswordStorageStandard::NoStandard => {}
         }
-        Ok(Hasher {
+        if self.ref_salt.is_none() && self.salt_len == 0 {
+            return Err(ErrorCode::IncompatibleOption);
+        }
+        if self.argon2_secret.is_some() && self.algorithm != Algorithm::Argon2 {
+            return Err(ErrorCode::IncompatibleOption);
+        }
+        if self.case_fold && matches!(self.xhmac, XHMAC::Before(_)) {
+            return Err(ErrorCode::IncompatibleOption);
+        }
+        if self.integrity_key.is_some() && self.param_mac.is_some() {
+            return Err(ErrorCode::IncompatibleOption);
+        }
+        self.check_algorithm_allowed(self.algorithm)?;
+        let mut hasher = Hasher {
             normalization: self.normalization,
             min_len: self.min_len,
             max_len: self.max_len,
@@ -247,7 +654,54 @@ impl HashBuilder {
             version: self.version,
             xhmac: self.xhmac.clone(),
             xhmax_alg: self.xhmax_alg,
-        })
+            assume_normalized: self.assume_normalized,
+            accepted_versions: self.accepted_versions.clone(),
+            param_mac: self.param_mac.clone(),
+            soft_max_len: self.soft_max_len,
+            prefix: self.prefix.clone(),
+            comparison_hash: self.comparison_hash,
+            argon2_secret: self.argon2_secret.clone(),
+            timing_hook: self.timing_hook.clone(),
+            fingerprint_key: self.fingerprint_key.clone(),
+            strict_unicode: self.strict_unicode,
+            min_work_factor: self.min_work_factor,
+            verify_only: self.verify_only,
+            case_fold: self.case_fold,
+            integrity_key: self.integrity_key.clone(),
+            timestamp_hashes: self.timestamp_hashes,
+            created_at: None,
+            skip_length_check_on_verify: self.skip_length_check_on_verify,
+            reject_whitespace_only: self.reject_whitespace_only,
+            internal_version: self.internal_version,
+            metadata: self.metadata.clone(),
+            fixed_salt: self.fixed_salt,
+            hash_func_hook: self.hash_func_hook,
+        };
+        // The single trailing segment of an inline salt hash is parsed as the salt, although it
+        // is the reference hash which embeds the salt.
+        if hasher.ref_hash.is_none() && hasher.ref_salt.is_some() {
+            let hash_func = hasher.new_hash_func();
+            if hash_func.salt_is_inline() {
+                if let Some(stored) = hasher.ref_salt.take() {
+                    let salt = hash_func
+                        .salt_from_hash(&stored)
+                        .ok_or(ErrorCode::InvalidPasswordFormat)?;
+                    hasher.ref_salt = Some(salt);
+                    hasher.ref_hash = Some(stored);
+                }
+            }
+        }
+        let hash_func = hasher.get_hash_func()?;
+        if self.auto_salt_len && !self.explicit_salt_len {
+            hasher.salt_len = hash_func.auto_salt_len();
+        }
+        if let Some(Ok(h)) = hasher.pbkdf2_hash().map(HashFunction::from_str) {
+            if self.forbidden_hashes.contains(&h) {
+                return Err(ErrorCode::IncompatibleOption);
+            }
+        }
+        hasher.check_work_factor()?;
+        Ok(hasher)
     }
 
     /// Set the way the password will be normalized.
@@ -256,10 +710,78 @@ impl HashBuilder {
         self
     }
 
+    /// Set the way the password will be normalized using its textual representation (`nfd`,
+    /// `nfkd`, `nfc`, `nfkc` or `none`). An unknown value is reported when finalizing.
+    pub fn normalization_str(&mut self, normalization: &str) -> &mut HashBuilder {
+        match Normalization::from_str(normalization) {
+            Ok(n) => {
+                self.normalization = n;
+            }
+            Err(e) => {
+                self.runtime_error = Some(e);
+            }
+        }
+        self
+    }
+
+    /// Set whether or not passwords containing unassigned Unicode code points are rejected when
+    /// hashed, using [PasswordContainsUnassigned](ErrorCode::PasswordContainsUnassigned). Such
+    /// code points may be assigned, and their normalization defined, by a later Unicode version.
+    /// Default is false.
+    ///
+    /// This may reject some unusual but legitimate passwords, for example ones typed using a
+    /// system supporting a more recent Unicode version than this library.
+    pub fn strict_unicode(&mut self, strict: bool) -> &mut HashBuilder {
+        self.strict_unicode = strict;
+        self
+    }
+
+    /// Set whether or not passwords which are empty once the leading and trailing Unicode
+    /// whitespace characters are removed are rejected when hashed, using
+    /// [PasswordWhitespaceOnly](ErrorCode::PasswordWhitespaceOnly). Such a password is weak and
+    /// often the result of an input error. Default is false, enabling it is recommended for new
+    /// deployments.
+    pub fn reject_whitespace_only(&mut self, reject: bool) -> &mut HashBuilder {
+        self.reject_whitespace_only = reject;
+        self
+    }
+
+    /// Set whether or not the password is converted to lowercase after being normalized, which
+    /// makes it case-insensitive. This is recorded in the stored hash so the password is checked
+    /// the same way. Default is false.
+    ///
+    /// **WARNING**: this considerably reduces the number of possible passwords and therefore the
+    /// resistance to brute-force attacks. It should only be used when case-insensitive passwords
+    /// are a requirement, for example for compatibility with a legacy system.
+    ///
+    /// The conversion uses the Unicode lowercase mapping, which is not a full case folding: for
+    /// example, `ß` and `SS` are not considered equal. Since the additional HMAC applied before
+    /// hashing uses the password as supplied, this option cannot be combined with
+    /// [xhmac_before](HashBuilder::xhmac_before).
+    pub fn case_fold(&mut self, case_fold: bool) -> &mut HashBuilder {
+        self.case_fold = case_fold;
+        self
+    }
+
+    /// Set whether or not the password has already been normalized by the caller, in which case
+    /// the normalization step is skipped. The normalization method is still recorded so the
+    /// password can be checked later. Default is false.
+    ///
+    /// **WARNING**: the caller must normalize the password using the exact same method as the
+    /// one configured. Otherwise, the password will not be recognized when checked by a Hasher
+    /// that normalizes it.
+    pub fn assume_normalized(&mut self, assume_normalized: bool) -> &mut HashBuilder {
+        self.assume_normalized = assume_normalized;
+        self
+    }
+
     /// Set the password hashing algorithm.
+    ///
+    /// The parameters which have already been added are kept: they are checked against the
+    /// algorithm when finalizing. Use [clear_parameters](HashBuilder::clear_parameters) to
+    /// remove them.
     pub fn algorithm(&mut self, algorithm: Algorithm) -> &mut HashBuilder {
         self.algorithm = algorithm;
-        self.parameters = HashMap::new();
         self
     }
 
@@ -269,11 +791,40 @@ impl HashBuilder {
         self
     }
 
-    /// Set the salt length.
+    /// Set the salt length. A length of zero is rejected when finalizing.
+    ///
+    /// An empty salt would give every password the same, effectively unsalted, hash. There is no
+    /// option to allow it, since both algorithms require a salt of at least a few bytes anyway.
     ///
     /// Unused if a salt is given.
     pub fn salt_len(&mut self, len: usize) -> &mut HashBuilder {
         self.salt_len = len;
+        self.explicit_salt_len = true;
+        self
+    }
+
+    /// Set the salt length in bits. It must be a multiple of 8 and at least 64, otherwise an
+    /// error is reported when finalizing.
+    ///
+    /// Unused if a salt is given.
+    pub fn salt_len_bits(&mut self, bits: usize) -> &mut HashBuilder {
+        if bits % 8 == 0 && bits >= MIN_SALT_LEN_BITS {
+            self.salt_len = bits / 8;
+            self.explicit_salt_len = true;
+        } else {
+            self.runtime_error = Some(ErrorCode::InvalidPasswordFormat);
+        }
+        self
+    }
+
+    /// Set whether or not the salt length is chosen according to the algorithm: for PBKDF2, it
+    /// matches the length of the derived key (e.g. 32 bytes using SHA-256) and, for Argon2, it
+    /// is 16 bytes. Default is false.
+    ///
+    /// Unused if the salt length is set using [salt_len](HashBuilder::salt_len) or
+    /// [salt_len_bits](HashBuilder::salt_len_bits), or if a salt is given.
+    pub fn auto_salt_len(&mut self, auto_salt_len: bool) -> &mut HashBuilder {
+        self.auto_salt_len = auto_salt_len;
         self
     }
 
@@ -289,15 +840,247 @@ impl HashBuilder {
         self
     }
 
-    /// Add a parameter that will be used by the password hashing algorithm.
+    /// Set the password soft maximal length. Longer passwords are still accepted but reported by
+    /// [hash_checked](Hasher::hash_checked). Unset by default.
+    pub fn soft_max_len(&mut self, len: usize) -> &mut HashBuilder {
+        self.soft_max_len = Some(len);
+        self
+    }
+
+    /// Add a parameter that will be used by the password hashing algorithm. Parameters unknown
+    /// to the algorithm or having an invalid value are reported when finalizing.
     pub fn add_param(&mut self, key: &str, value: &str) -> &mut HashBuilder {
         self.parameters.insert(key.to_string(), value.to_string());
         self
     }
 
+    /// Remove all the parameters added using [add_param](HashBuilder::add_param).
+    pub fn clear_parameters(&mut self) -> &mut HashBuilder {
+        self.parameters.clear();
+        self
+    }
+
+    /// Return the parameters that will be used by the password hashing algorithm.
+    pub fn parameters(&self) -> &HashMap<String, String> {
+        &self.parameters
+    }
+
     /// Set the hashing scheme version number.
     pub fn version(&mut self, version: usize) -> &mut HashBuilder {
-        self.version = version + INTERNAL_VERSION;
+        self.version = version + self.internal_version;
+        self
+    }
+
+    /// Override the internal version number, which is added to the hashing scheme version number
+    /// and is increased by this library when the hashing scheme changes, making
+    /// [needs_update](Hasher::needs_update) report hashes produced by a previous release.
+    /// Default is the value returned by [internal_version](crate::pass::internal_version).
+    ///
+    /// This is meant for testing the migration of stored hashes across such a change: the
+    /// resulting hashes cannot be correctly interpreted by a Hasher using another internal
+    /// version number.
+    pub fn internal_version(&mut self, internal_version: usize) -> &mut HashBuilder {
+        self.version = self.version.saturating_sub(self.internal_version) + internal_version;
+        self.internal_version = internal_version;
+        self
+    }
+
+    /// Set the range of hashing scheme version numbers which are considered acceptable, for
+    /// example while rolling out a new version.
+    ///
+    /// The version number never changes the way a password is derived, hence it has no effect
+    /// on the password verification: this range is only reflected by
+    /// [is_version_accepted](Hasher::is_version_accepted) and does not change the result of
+    /// [needs_update](Hasher::needs_update).
+    pub fn accept_versions(&mut self, versions: RangeInclusive<usize>) -> &mut HashBuilder {
+        self.accepted_versions = Some(versions);
+        self
+    }
+
+    /// Attach metadata, such as a small JSON document, to the hashes. It is stored base64 encoded
+    /// in the `meta` parameter and can be read back using [Hasher::metadata]. It does not change
+    /// the way the password is derived. Metadata longer than 256 bytes is reported when
+    /// finalizing.
+    ///
+    /// The metadata is not authenticated unless the hash is: use
+    /// [integrity_key](HashBuilder::integrity_key) or [param_mac](HashBuilder::param_mac) in
+    /// order to prevent it from being altered.
+    pub fn metadata(&mut self, metadata: &str) -> &mut HashBuilder {
+        if metadata.len() > MAX_METADATA_LEN {
+            self.runtime_error = Some(ErrorCode::InvalidPasswordFormat);
+        } else {
+            self.metadata = Some(metadata.to_string());
+        }
+        self
+    }
+
+    /// Use the specified salt instead of a random one, so the hashes are always the same. This is
+    /// only available with the `deterministic-docs` feature and is meant for documentation
+    /// examples and tests showing an exact hash.
+    ///
+    /// **WARNING**: never use this in production. A fixed salt allows to attack every hash at
+    /// once using precomputed tables and reveals which users share the same password. The
+    /// resulting hashes carry the `fixedsalt=1` parameter so that they can be detected using
+    /// [used_fixed_salt](Hasher::used_fixed_salt).
+    ///
+    /// ## Examples
+    /// ```
+    /// use libreauth::pass::{Algorithm, HashBuilder};
+    ///
+    /// let hasher = HashBuilder::new()
+    ///     .algorithm(Algorithm::Pbkdf2)
+    ///     .add_param("iter", "10000")
+    ///     .deterministic_for_docs(b"not random")
+    ///     .finalize()
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     hasher.hash("correct horse battery staple").unwrap(),
+    ///     "$pbkdf2$fixedsalt=1,hmac=sha512,iter=10000,len-calc=chars,norm=nfkc,pmax=128,pmin=8,ver=1,xhmac=none$bm90IHJhbmRvbQ$y88i6LwhgkFuw1B40I7GLjNs1H1EcsjgM8aNPJGw85zvhlVi3KOlpfT4ZZOl/F+MJTx5gucUsaXWsxRE1l3XaA"
+    /// );
+    /// ```
+    #[cfg(any(test, feature = "deterministic-docs"))]
+    pub fn deterministic_for_docs(&mut self, salt: &[u8]) -> &mut HashBuilder {
+        self.ref_salt = Some(salt.to_vec());
+        self.fixed_salt = true;
+        self
+    }
+
+    /// Authenticate the hashing parameters using an HMAC keyed with the specified secret. The
+    /// resulting tag is stored in the `pmac` parameter, and the hash can only be checked using
+    /// [from_phc_param_mac](HashBuilder::from_phc_param_mac), which rejects hashes whose
+    /// parameters have been altered, for example to lower their cost.
+    ///
+    /// This only prevents such a downgrade if the attacker does not know the secret, which should
+    /// therefore not be stored along with the password hashes.
+    pub fn param_mac(&mut self, secret: &[u8]) -> &mut HashBuilder {
+        self.param_mac = Some(secret.to_vec());
+        self
+    }
+
+    /// Authenticate the whole hash, including its salt and the hash itself, using an HMAC keyed
+    /// with the specified secret. The resulting tag is stored in the `mac` parameter, and the
+    /// hash can only be checked using [from_phc_integrity](HashBuilder::from_phc_integrity),
+    /// which rejects hashes that have been altered in any way.
+    ///
+    /// This covers the parameters as well, hence it cannot be combined with
+    /// [param_mac](HashBuilder::param_mac).
+    pub fn integrity_key(&mut self, secret: &[u8]) -> &mut HashBuilder {
+        self.integrity_key = Some(secret.to_vec());
+        self
+    }
+
+    /// Set whether or not the creation time of the hash is recorded, as a number of seconds since
+    /// the Unix epoch in the `ts` parameter. It does not affect the derived key and can be read
+    /// using [created_at](Hasher::created_at), for example in order to rehash passwords older
+    /// than a given age. Default is false.
+    pub fn timestamp_hashes(&mut self, timestamp_hashes: bool) -> &mut HashBuilder {
+        self.timestamp_hashes = timestamp_hashes;
+        self
+    }
+
+    /// Set the secret key fed to Argon2. Only the fact that a secret is used is recorded (as
+    /// `secret=1`), hence the hash can only be checked using
+    /// [from_phc_argon2_secret](HashBuilder::from_phc_argon2_secret).
+    ///
+    /// Finalizing returns [IncompatibleOption](ErrorCode::IncompatibleOption) if the algorithm
+    /// is not Argon2.
+    pub fn argon2_secret(&mut self, key: &[u8]) -> &mut HashBuilder {
+        self.argon2_secret = Some(key.to_vec());
+        self
+    }
+
+    /// Set a function called with the time spent by each call to [hash](Hasher::hash) and
+    /// [is_valid](Hasher::is_valid), for example in order to report slow operations. A panic
+    /// occurring in this function is caught and ignored. Unset by default.
+    pub fn on_timing(&mut self, f: impl Fn(Duration) + Send + Sync + 'static) -> &mut HashBuilder {
+        self.timing_hook = Some(Arc::new(f));
+        self
+    }
+
+    /// Set the secret used to compute the [fingerprint](Hasher::fingerprint) of the stored hash.
+    /// Unset by default.
+    pub fn fingerprint_key(&mut self, secret: &[u8]) -> &mut HashBuilder {
+        self.fingerprint_key = Some(secret.to_vec());
+        self
+    }
+
+    /// Set the minimal [work factor](crate::pass::CostSummary::work_factor) of the hashing
+    /// algorithm. Finalizing returns [IncompatibleOption](ErrorCode::IncompatibleOption) if the
+    /// parameters are below this floor. Unset by default.
+    ///
+    /// A Hasher created from a PHC formatted string can be given the same floor using
+    /// [Hasher::min_work_factor].
+    pub fn min_work_factor(&mut self, work_factor: u64) -> &mut HashBuilder {
+        self.min_work_factor = Some(work_factor);
+        self
+    }
+
+    /// Set whether or not the Hasher is restricted to verifying passwords. When set,
+    /// [hash](Hasher::hash) and every other method producing a new hash or derived key return
+    /// [IncompatibleOption](ErrorCode::IncompatibleOption), which helps enforcing that only a
+    /// dedicated service creates new hashes. Default is false.
+    ///
+    /// A Hasher created from a PHC formatted string can be restricted using
+    /// [Hasher::verify_only].
+    pub fn verify_only(&mut self, verify_only: bool) -> &mut HashBuilder {
+        self.verify_only = verify_only;
+        self
+    }
+
+    /// Set whether or not the password length bounds are ignored when verifying a password. They
+    /// still apply when hashing a new one. Default is false.
+    ///
+    /// The length bounds are a policy on new passwords and are not part of the derivation:
+    /// ignoring them when verifying prevents users whose password was accepted by a former policy,
+    /// or counted using another [length calculation method](LengthCalculationMethod), from being
+    /// locked out. Passwords longer than the highest accepted maximal length (65536) are still
+    /// rejected in order to bound the cost of a verification.
+    ///
+    /// A Hasher created from a PHC formatted string can be configured using
+    /// [Hasher::skip_length_check_on_verify].
+    pub fn skip_length_check_on_verify(&mut self, skip: bool) -> &mut HashBuilder {
+        self.skip_length_check_on_verify = skip;
+        self
+    }
+
+    /// Restrict the password hashing algorithms which may be used, for example in order to refuse
+    /// deprecated ones and force a password reset instead of verifying them. This applies both to
+    /// the hashes loaded using [load_phc](HashBuilder::load_phc) and to the algorithm of this
+    /// builder, an algorithm outside of the list being reported as
+    /// [AlgorithmNotAllowed](ErrorCode::AlgorithmNotAllowed). Default is to allow every
+    /// available algorithm.
+    ///
+    /// **WARNING**: [load_phc](HashBuilder::load_phc) is the only way to load a stored hash
+    /// which enforces this list. The other constructors, such as
+    /// [from_phc](HashBuilder::from_phc), are associated functions without access to this
+    /// builder: they accept every available algorithm.
+    pub fn allowed_algorithms(&mut self, algorithms: &[Algorithm]) -> &mut HashBuilder {
+        self.allowed_algorithms = Some(algorithms.to_vec());
+        self
+    }
+
+    /// Forbid the use of a hash function, such as `sha1`, by PBKDF2 for new hashes: finalizing
+    /// a builder using it returns [IncompatibleOption](ErrorCode::IncompatibleOption). An
+    /// unknown hash function is reported when finalizing. This may be called several times.
+    ///
+    /// This is a policy on new hashes only: a Hasher created from a PHC formatted string is not
+    /// affected, so legacy hashes can still be verified and then replaced.
+    pub fn forbid_hash(&mut self, hash_func: &str) -> &mut HashBuilder {
+        match HashFunction::from_str(hash_func) {
+            Ok(h) => {
+                self.forbidden_hashes.push(h);
+            }
+            Err(_) => {
+                self.runtime_error = Some(ErrorCode::InvalidPasswordFormat);
+            }
+        }
+        self
+    }
+
+    /// Set the hash function used by the HMAC which allows to compare the password hashes in
+    /// constant time. It does not change the stored hash. Default is Sha512.
+    pub fn comparison_hash(&mut self, hash_func: HashFunction) -> &mut HashBuilder {
+        self.comparison_hash = hash_func;
         self
     }
 
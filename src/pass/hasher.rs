@@ -1,26 +1,208 @@
 use super::{
-    argon2, pbkdf2, std_default, Algorithm, ErrorCode, HashedDuo, HashingFunction,
-    LengthCalculationMethod, Normalization, DEFAULT_USER_VERSION, INTERNAL_VERSION, XHMAC,
+    argon2, fill_random, pbkdf2, std_default, unassigned, Algorithm, CostSummary, ErrorCode,
+    HashingFunction, LengthCalculationMethod, Normalization, PasswordPolicyError, Warning,
+    DEFAULT_USER_VERSION, FINGERPRINT_LEN, FIXED_SALT, INTEGRITY_MAC, MAX_PASSWORD_LEN, METADATA,
+    PARAM_MAC, TIMESTAMP, XHMAC,
 };
 use crate::hash::HashFunction;
-use crate::key::KeyBuilder;
-use crate::pass::phc::PHCData;
-use hmac::{Hmac, Mac};
+use crate::pass::phc::{encode_metadata, PHCData};
+use blake2::Blake2b512;
+use hmac::{Hmac, Mac, SimpleHmac};
 use sha1::Sha1;
 use sha2::{Sha224, Sha256, Sha384, Sha512, Sha512_224, Sha512_256};
 use sha3::{Keccak224, Keccak256, Keccak384, Keccak512, Sha3_224, Sha3_256, Sha3_384, Sha3_512};
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
 
 macro_rules! get_hmac {
-    ($hash_func: ty, $salt: ident, $pass: ident) => {{
-        let mut hasher = Hmac::<$hash_func>::new_from_slice(&$salt)?;
+    ($hash_func: ty, $salt: ident, $pass: ident, $out: ident) => {{
+        get_hmac!(Hmac, $hash_func, $salt, $pass, $out)
+    }};
+    ($mac: ident, $hash_func: ty, $salt: ident, $pass: ident, $out: ident) => {{
+        let mut hasher = $mac::<$hash_func>::new_from_slice(&$salt)?;
         hasher.update($pass);
-        Ok(hasher.finalize().into_bytes().to_vec())
+        $out.clear();
+        $out.extend_from_slice(&hasher.finalize().into_bytes());
+        Ok(())
     }};
 }
 
+const HKDF_BLOCK_LEN: usize = 32;
+const HKDF_MAX_BLOCKS: usize = 255;
+const DERIVE_KEY_CONTEXT: &[u8] = b"libreauth derive_key";
+const MAX_TIMEOUT_WORKERS: usize = 16;
+
+static TIMEOUT_WORKERS: WorkerSlots = WorkerSlots::new(MAX_TIMEOUT_WORKERS);
+
+/// Counting semaphore bounding the number of threads running a verification.
+pub(super) struct WorkerSlots {
+    running: AtomicUsize,
+    max: usize,
+}
+
+impl WorkerSlots {
+    pub(super) const fn new(max: usize) -> WorkerSlots {
+        WorkerSlots {
+            running: AtomicUsize::new(0),
+            max,
+        }
+    }
+
+    /// Reserve a slot, which is released when the returned guard is dropped. Returns `None`
+    /// without waiting if all the slots are in use.
+    pub(super) fn try_acquire(&self) -> Option<WorkerSlot<'_>> {
+        self.running
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < self.max).then(|| n + 1)
+            })
+            .ok()?;
+        Some(WorkerSlot(self))
+    }
+}
+
+pub(super) struct WorkerSlot<'a>(&'a WorkerSlots);
+
+impl Drop for WorkerSlot<'_> {
+    fn drop(&mut self) {
+        self.0.running.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Compute the HMAC authenticating the hashing parameters. Those are sorted so the result does
+/// not depend on their order.
+pub(super) fn get_param_mac(
+    secret: &[u8],
+    id: &str,
+    parameters: &HashMap<String, String>,
+) -> Result<Hmac<Sha256>, ErrorCode> {
+    let mut params: Vec<String> = parameters
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect();
+    params.sort();
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret)?;
+    mac.update(id.as_bytes());
+    mac.update(b"$");
+    mac.update(params.join(",").as_bytes());
+    Ok(mac)
+}
+
+/// Compute the HMAC authenticating the whole hash: the identifier, the version, the parameters,
+/// the salt and the hash itself.
+pub(super) fn get_integrity_mac(secret: &[u8], phc: &PHCData) -> Result<Hmac<Sha256>, ErrorCode> {
+    let mut mac = get_param_mac(secret, &phc.id, &phc.parameters)?;
+    mac.update(&phc.version.unwrap_or(0).to_be_bytes());
+    for data in [&phc.salt, &phc.hash] {
+        let data = data.as_deref().unwrap_or_default();
+        mac.update(&(data.len() as u64).to_be_bytes());
+        mac.update(data);
+    }
+    Ok(mac)
+}
+
+/// HKDF using HMAC-SHA256, as defined in [RFC 5869](https://tools.ietf.org/html/rfc5869).
+pub(super) fn hkdf_sha256(
+    salt: &[u8],
+    ikm: &[u8],
+    info: &[u8],
+    out_len: usize,
+) -> Result<Vec<u8>, ErrorCode> {
+    if out_len > HKDF_MAX_BLOCKS * HKDF_BLOCK_LEN {
+        return Err(ErrorCode::InvalidKeyLen);
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(salt)?;
+    mac.update(ikm);
+    let prk = mac.finalize().into_bytes();
+    let mut okm = Vec::with_capacity(out_len);
+    let mut block: Vec<u8> = Vec::new();
+    let mut i: u8 = 1;
+    while okm.len() < out_len {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&prk)?;
+        mac.update(&block);
+        mac.update(info);
+        mac.update(&[i]);
+        block = mac.finalize().into_bytes().to_vec();
+        okm.extend_from_slice(&block);
+        i = i.wrapping_add(1);
+    }
+    okm.truncate(out_len);
+    Ok(okm)
+}
+
+/// Compute an HMAC into `out`, which does not allocate if its capacity is large enough.
+fn compute_hmac(
+    hash_function: HashFunction,
+    data: &[u8],
+    key: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), ErrorCode> {
+    match hash_function {
+        HashFunction::Sha1 => get_hmac!(Sha1, key, data, out),
+        HashFunction::Sha224 => get_hmac!(Sha224, key, data, out),
+        HashFunction::Sha256 => get_hmac!(Sha256, key, data, out),
+        HashFunction::Sha384 => get_hmac!(Sha384, key, data, out),
+        HashFunction::Sha512 => get_hmac!(Sha512, key, data, out),
+        HashFunction::Sha512Trunc224 => get_hmac!(Sha512_224, key, data, out),
+        HashFunction::Sha512Trunc256 => get_hmac!(Sha512_256, key, data, out),
+        HashFunction::Sha3_224 => get_hmac!(Sha3_224, key, data, out),
+        HashFunction::Sha3_256 => get_hmac!(Sha3_256, key, data, out),
+        HashFunction::Sha3_384 => get_hmac!(Sha3_384, key, data, out),
+        HashFunction::Sha3_512 => get_hmac!(Sha3_512, key, data, out),
+        HashFunction::Keccak224 => get_hmac!(Keccak224, key, data, out),
+        HashFunction::Keccak256 => get_hmac!(Keccak256, key, data, out),
+        HashFunction::Keccak384 => get_hmac!(Keccak384, key, data, out),
+        HashFunction::Keccak512 => get_hmac!(Keccak512, key, data, out),
+        HashFunction::Blake2b => get_hmac!(SimpleHmac, Blake2b512, key, data, out),
+    }
+}
+
+/// Reusable state for [verify_into](Hasher::verify_into).
+///
+/// It keeps the hashing function built from the stored hash, the key and the reference used to
+/// compare the hashes, as well as the buffers receiving the normalized password and the derived
+/// hash, so they are not rebuilt on each verification. It may be used with any Hasher: it is
+/// rebuilt whenever the stored hash or anything used to build the hashing function, such as the
+/// algorithm, its parameters, the salt or the Argon2 secret, changes.
+#[derive(Default)]
+pub struct VerifyScratch {
+    ref_hash: Vec<u8>,
+    algorithm: Option<Algorithm>,
+    normalization: Option<Normalization>,
+    parameters: HashMap<String, String>,
+    ref_salt: Option<Vec<u8>>,
+    salt_len: usize,
+    argon2_secret: Option<Vec<u8>>,
+    hash_func: Option<Box<dyn HashingFunction>>,
+    work_factor: u64,
+    comparison_hash: Option<HashFunction>,
+    comparison_key: Vec<u8>,
+    ref_hmac: Vec<u8>,
+    password: String,
+    xhmac: Vec<u8>,
+    hash: Vec<u8>,
+    pass_hmac: Vec<u8>,
+}
+
+impl VerifyScratch {
+    /// Create a new, empty, VerifyScratch object.
+    pub fn new() -> VerifyScratch {
+        VerifyScratch::default()
+    }
+}
+
+/// Build the hashing function instead of the one of the configured algorithm, which allows the
+/// tests to use a function whose behavior differs from the available algorithms.
+pub(super) type HashFuncHook = fn() -> Box<dyn HashingFunction>;
+
 /// Hash a password and check a password against a previously hashed one.
+#[derive(Clone)]
 pub struct Hasher {
     pub(crate) normalization: Normalization,
     pub(crate) min_len: usize,
@@ -34,11 +216,33 @@ pub struct Hasher {
     pub(crate) version: usize,
     pub(crate) xhmac: XHMAC,
     pub(crate) xhmax_alg: HashFunction,
+    pub(crate) assume_normalized: bool,
+    pub(crate) accepted_versions: Option<RangeInclusive<usize>>,
+    pub(crate) param_mac: Option<Vec<u8>>,
+    pub(crate) soft_max_len: Option<usize>,
+    pub(crate) prefix: Option<String>,
+    pub(crate) comparison_hash: HashFunction,
+    pub(crate) argon2_secret: Option<Vec<u8>>,
+    pub(crate) timing_hook: Option<Arc<dyn Fn(Duration) + Send + Sync>>,
+    pub(crate) fingerprint_key: Option<Vec<u8>>,
+    pub(crate) strict_unicode: bool,
+    pub(crate) min_work_factor: Option<u64>,
+    pub(crate) verify_only: bool,
+    pub(crate) case_fold: bool,
+    pub(crate) integrity_key: Option<Vec<u8>>,
+    pub(crate) timestamp_hashes: bool,
+    pub(crate) created_at: Option<u64>,
+    pub(crate) skip_length_check_on_verify: bool,
+    pub(crate) reject_whitespace_only: bool,
+    pub(crate) internal_version: usize,
+    pub(crate) metadata: Option<String>,
+    pub(crate) fixed_salt: bool,
+    pub(super) hash_func_hook: Option<HashFuncHook>,
 }
 
 impl Hasher {
-    fn check_password(&self, password: &str) -> Result<(), ErrorCode> {
-        let pass_len = match self.length_calculation {
+    fn password_len(&self, password: &str) -> usize {
+        match self.length_calculation {
             LengthCalculationMethod::Bytes => password.len(),
             LengthCalculationMethod::Characters => {
                 let mut len = 0;
@@ -47,32 +251,74 @@ impl Hasher {
                 }
                 len
             }
+            LengthCalculationMethod::Graphemes => password.graphemes(true).count(),
+        }
+    }
+
+    fn check_password(&self, password: &str, verifying: bool) -> Result<(), PasswordPolicyError> {
+        let (min_len, max_len) = if verifying && self.skip_length_check_on_verify {
+            (0, MAX_PASSWORD_LEN)
+        } else {
+            (self.min_len, self.max_len)
         };
-        if pass_len < self.min_len {
-            return Err(ErrorCode::PasswordTooShort);
+        let pass_len = self.password_len(password);
+        if pass_len < min_len {
+            return Err(PasswordPolicyError::TooShort { min: min_len });
+        }
+        if pass_len > max_len {
+            return Err(PasswordPolicyError::TooLong { max: max_len });
         }
-        if pass_len > self.max_len {
-            return Err(ErrorCode::PasswordTooLong);
+        if self.strict_unicode && password.chars().any(unassigned::is_unassigned) {
+            return Err(PasswordPolicyError::ContainsUnassigned);
+        }
+        if self.reject_whitespace_only && password.trim().is_empty() {
+            return Err(PasswordPolicyError::WhitespaceOnly);
         }
         Ok(())
     }
 
     fn normalize_password(&self, password: &str) -> String {
-        match self.normalization {
-            Normalization::Nfd => password.nfd().collect::<String>(),
-            Normalization::Nfkd => password.nfkd().collect::<String>(),
-            Normalization::Nfc => password.nfc().collect::<String>(),
-            Normalization::Nfkc => password.nfkc().collect::<String>(),
-            Normalization::None => password.to_string(),
+        let mut out = String::new();
+        self.normalize_password_into(password, &mut out);
+        out
+    }
+
+    fn normalize_password_into(&self, password: &str, out: &mut String) {
+        out.clear();
+        if self.assume_normalized {
+            out.push_str(password);
+        } else {
+            match self.normalization {
+                Normalization::Nfd => out.extend(password.nfd()),
+                Normalization::Nfkd => out.extend(password.nfkd()),
+                Normalization::Nfc => out.extend(password.nfc()),
+                Normalization::Nfkc => out.extend(password.nfkc()),
+                Normalization::None => out.push_str(password),
+            }
+        }
+        if self.case_fold {
+            let folded = out.to_lowercase();
+            out.clear();
+            out.push_str(&folded);
         }
     }
 
-    fn get_hash_func(&self) -> Result<Box<dyn HashingFunction>, ErrorCode> {
-        let mut hash_func: Box<dyn HashingFunction> = match self.algorithm {
+    pub(super) fn new_hash_func(&self) -> Box<dyn HashingFunction> {
+        if let Some(hook) = self.hash_func_hook {
+            return hook();
+        }
+        match self.algorithm {
             Algorithm::Argon2 => Box::new(argon2::Argon2Hash::new()),
             Algorithm::Pbkdf2 => Box::new(pbkdf2::Pbkdf2Hash::new()),
-        };
+        }
+    }
+
+    pub(super) fn get_hash_func(&self) -> Result<Box<dyn HashingFunction>, ErrorCode> {
+        let mut hash_func = self.new_hash_func();
         hash_func.set_normalization(self.normalization)?;
+        if let Some(ref secret) = self.argon2_secret {
+            hash_func.set_secret(secret)?;
+        }
         for (k, v) in &self.parameters {
             hash_func.set_parameter(k, v)?;
         }
@@ -87,115 +333,651 @@ impl Hasher {
         Ok(hash_func)
     }
 
-    fn apply_xhmac(&self, password: &[u8], salt: &[u8]) -> Result<Vec<u8>, ErrorCode> {
-        match self.xhmax_alg {
-            HashFunction::Sha1 => get_hmac!(Sha1, salt, password),
-            HashFunction::Sha224 => get_hmac!(Sha224, salt, password),
-            HashFunction::Sha256 => get_hmac!(Sha256, salt, password),
-            HashFunction::Sha384 => get_hmac!(Sha384, salt, password),
-            HashFunction::Sha512 => get_hmac!(Sha512, salt, password),
-            HashFunction::Sha512Trunc224 => get_hmac!(Sha512_224, salt, password),
-            HashFunction::Sha512Trunc256 => get_hmac!(Sha512_256, salt, password),
-            HashFunction::Sha3_224 => get_hmac!(Sha3_224, salt, password),
-            HashFunction::Sha3_256 => get_hmac!(Sha3_256, salt, password),
-            HashFunction::Sha3_384 => get_hmac!(Sha3_384, salt, password),
-            HashFunction::Sha3_512 => get_hmac!(Sha3_512, salt, password),
-            HashFunction::Keccak224 => get_hmac!(Keccak224, salt, password),
-            HashFunction::Keccak256 => get_hmac!(Keccak256, salt, password),
-            HashFunction::Keccak384 => get_hmac!(Keccak384, salt, password),
-            HashFunction::Keccak512 => get_hmac!(Keccak512, salt, password),
-        }
-    }
-
-    fn do_hash(&self, password: &str) -> Result<HashedDuo, ErrorCode> {
-        let norm_pass = self.normalize_password(password);
-        match self.check_password(&norm_pass) {
-            Ok(_) => {}
-            Err(e) => {
-                return Err(e);
+    fn derive(
+        &self,
+        password: &str,
+        hash_func: &dyn HashingFunction,
+        verifying: bool,
+    ) -> Result<Vec<u8>, ErrorCode> {
+        let mut hash = Vec::new();
+        self.derive_into(
+            password,
+            hash_func,
+            verifying,
+            &mut String::new(),
+            &mut Vec::new(),
+            &mut hash,
+        )?;
+        Ok(hash)
+    }
+
+    /// Same as `derive` but uses the supplied buffers for the normalized password and the
+    /// additional HMAC, and writes the result into `hash`.
+    fn derive_into(
+        &self,
+        password: &str,
+        hash_func: &dyn HashingFunction,
+        verifying: bool,
+        norm_pass: &mut String,
+        xhmac: &mut Vec<u8>,
+        hash: &mut Vec<u8>,
+    ) -> Result<(), ErrorCode> {
+        self.normalize_password_into(password, norm_pass);
+        self.check_password(norm_pass, verifying)?;
+        let input = match &self.xhmac {
+            XHMAC::Before(salt) => {
+                compute_hmac(self.xhmax_alg, password.as_bytes(), salt, xhmac)?;
+                xhmac.as_slice()
             }
+            _ => norm_pass.as_bytes(),
         };
-        let norm_pass = match &self.xhmac {
-            XHMAC::Before(salt) => self.apply_xhmac(password.as_bytes(), salt)?,
-            _ => norm_pass.into_bytes(),
-        };
+        hash_func.hash_into(input, hash);
+        if let XHMAC::After(salt) = &self.xhmac {
+            compute_hmac(self.xhmax_alg, hash, salt, xhmac)?;
+            std::mem::swap(hash, xhmac);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_work_factor(&self) -> Result<(), ErrorCode> {
+        if let Some(min) = self.min_work_factor {
+            if self.cost_summary()?.work_factor < min {
+                return Err(ErrorCode::IncompatibleOption);
+            }
+        }
+        Ok(())
+    }
+
+    fn check_not_verify_only(&self) -> Result<(), ErrorCode> {
+        if self.verify_only {
+            return Err(ErrorCode::IncompatibleOption);
+        }
+        Ok(())
+    }
+
+    fn do_hash(&self, password: &str) -> Result<String, ErrorCode> {
+        self.check_not_verify_only()?;
+        self.check_work_factor()?;
         let hash_func = self.get_hash_func()?;
-        let hash = hash_func.hash(&norm_pass);
-        let hash = match &self.xhmac {
-            XHMAC::After(salt) => self.apply_xhmac(&hash, salt)?,
-            _ => hash,
-        };
+        let hash = self.derive(password, hash_func.as_ref(), false)?;
+        self.format_hash(hash_func.as_ref(), &hash, self.version, None)
+    }
+
+    /// Build the stored hash. If timestamps are enabled, `created_at` is recorded instead of the
+    /// current time when it is set.
+    fn format_hash(
+        &self,
+        hash_func: &dyn HashingFunction,
+        hash: &[u8],
+        version: usize,
+        created_at: Option<u64>,
+    ) -> Result<String, ErrorCode> {
         let lc = match self.length_calculation {
             LengthCalculationMethod::Bytes => "bytes",
             LengthCalculationMethod::Characters => "chars",
+            LengthCalculationMethod::Graphemes => "graphemes",
         };
         let mut params = hash_func.get_parameters();
         params.insert("len-calc".to_string(), lc.to_string());
         params.insert("pmin".to_string(), format!("{}", self.min_len));
         params.insert("pmax".to_string(), format!("{}", self.max_len));
-        params.insert("ver".to_string(), format!("{}", self.version));
+        params.insert("ver".to_string(), format!("{}", version));
         params.insert("xhmac".to_string(), self.xhmac.to_string());
+        if self.case_fold {
+            params.insert("fold".to_string(), "1".to_string());
+        }
+        if self.timestamp_hashes {
+            let ts = match created_at {
+                Some(ts) => ts,
+                None => SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_err(|_| ErrorCode::InvalidPasswordFormat)?
+                    .as_secs(),
+            };
+            params.insert(TIMESTAMP.to_string(), ts.to_string());
+        }
+        if let Some(metadata) = &self.metadata {
+            params.insert(METADATA.to_string(), encode_metadata(metadata));
+        }
+        if self.fixed_salt {
+            params.insert(FIXED_SALT.to_string(), "1".to_string());
+        }
         if self.xhmac.is_some() {
             params.insert(
                 "xhmac-alg".to_string(),
                 self.xhmax_alg.to_string().to_lowercase(),
             );
         }
-        let phc = PHCData {
+        if let Some(secret) = &self.param_mac {
+            let mac = get_param_mac(secret, &hash_func.get_id(), &params)?;
+            params.insert(
+                PARAM_MAC.to_string(),
+                hex::encode(mac.finalize().into_bytes()),
+            );
+        }
+        let mut phc = PHCData {
             id: hash_func.get_id(),
+            version: None,
             parameters: params,
-            salt: hash_func.get_salt(),
-            hash: Some(hash.clone()),
+            salt: match hash_func.salt_is_inline() {
+                true => None,
+                false => hash_func.get_salt(),
+            },
+            hash: Some(hash.to_vec()),
+            prefix: self.prefix.clone(),
         };
-        match phc.to_string() {
-            Ok(fmtd) => Ok(HashedDuo {
-                raw: hash,
-                formated: fmtd,
-            }),
-            Err(_) => Err(ErrorCode::InvalidPasswordFormat),
+        if let Some(secret) = &self.integrity_key {
+            let mac = get_integrity_mac(secret, &phc)?;
+            phc.parameters.insert(
+                INTEGRITY_MAC.to_string(),
+                hex::encode(mac.finalize().into_bytes()),
+            );
+        }
+        phc.to_string()
+            .map_err(|_| ErrorCode::InvalidPasswordFormat)
+    }
+
+    fn report_timing(&self, start: Instant) {
+        if let Some(hook) = &self.timing_hook {
+            let elapsed = start.elapsed();
+            let _ = catch_unwind(AssertUnwindSafe(|| hook(elapsed)));
         }
     }
 
+    /// Return the creation time of the reference hash, as a number of seconds since the Unix
+    /// epoch, if it has been recorded. See [timestamp_hashes](crate::pass::HashBuilder::timestamp_hashes).
+    pub fn created_at(&self) -> Option<u64> {
+        self.created_at
+    }
+
+    /// Check whether or not the password satisfies the requirements on its length and content,
+    /// without hashing it. The password is normalized beforehand, as it would be when hashed.
+    ///
+    /// ## Examples
+    /// ```
+    /// use libreauth::pass::{HashBuilder, PasswordPolicyError};
+    ///
+    /// let hasher = HashBuilder::new().min_len(12).finalize().unwrap();
+    /// let err = hasher.check_policy("short").unwrap_err();
+    /// assert_eq!(err, PasswordPolicyError::TooShort { min: 12 });
+    /// assert_eq!(err.to_string(), "too short: minimum 12");
+    /// ```
+    pub fn check_policy(&self, password: &str) -> Result<(), PasswordPolicyError> {
+        self.check_password(&self.normalize_password(password), false)
+    }
+
     pub fn hash(&self, password: &str) -> Result<String, ErrorCode> {
-        Ok(self.do_hash(password)?.formated)
+        let start = Instant::now();
+        let res = self.do_hash(password);
+        self.report_timing(start);
+        res
     }
 
+    /// Hash a password using [hash](Hasher::hash) and copy the resulting string into the supplied
+    /// buffer, returning the number of bytes written. Unlike the C interface, no NUL terminator is
+    /// added. If the buffer is too small, [NotEnoughSpace](ErrorCode::NotEnoughSpace) is returned
+    /// and the buffer is left untouched.
+    ///
+    /// This does not avoid memory allocations: the string is built on the heap before being
+    /// copied. [PASSWORD_STORAGE_LEN](crate::pass::PASSWORD_STORAGE_LEN) is large enough for any
+    /// hash.
+    pub fn hash_and_copy(&self, password: &str, out: &mut [u8]) -> Result<usize, ErrorCode> {
+        let hash = self.hash(password)?;
+        let len = hash.len();
+        if len > out.len() {
+            return Err(ErrorCode::NotEnoughSpace);
+        }
+        out[..len].copy_from_slice(hash.as_bytes());
+        Ok(len)
+    }
+
+    /// Format the stored hash again using a new hashing scheme version number, keeping the same
+    /// salt, hash and parameters, including the [creation time](Hasher::created_at). The
+    /// password is not required since nothing is recomputed.
+    ///
+    /// **WARNING**: the version number is only a stamp. This must only be used when the way
+    /// the password is derived did not change between both versions, otherwise the new hash
+    /// should be computed from the password.
+    pub fn rebless(&self, new_version: usize) -> Result<String, ErrorCode> {
+        self.check_not_verify_only()?;
+        let hash = self
+            .ref_hash
+            .as_ref()
+            .ok_or(ErrorCode::InvalidPasswordFormat)?;
+        let hash_func = self.get_hash_func()?;
+        self.format_hash(
+            hash_func.as_ref(),
+            hash,
+            new_version + self.internal_version,
+            self.created_at,
+        )
+    }
+
+    /// Compute the raw derived key using the configured algorithm and parameters, without
+    /// formatting it as a PHC string.
+    ///
+    /// The salt is the one supplied, not the one the Hasher may hold: the caller is in charge of
+    /// generating and storing it.
+    pub fn derive_raw(&self, password: &str, salt: &[u8]) -> Result<Vec<u8>, ErrorCode> {
+        self.check_not_verify_only()?;
+        let mut hash_func = self.get_hash_func()?;
+        hash_func.set_salt(salt.to_vec())?;
+        self.derive(password, hash_func.as_ref(), false)
+    }
+
+    /// Derive a key of `out_len` bytes from a password, for example an encryption key, using the
+    /// configured algorithm and parameters and the salt of the reference hash. The result of the
+    /// password hashing function is expanded using HKDF-SHA256 with `info` as context, so that
+    /// distinct `info` values (e.g. `b"authentication"` and `b"file-encryption"`) yield
+    /// independent keys. At most 8160 bytes can be derived.
+    ///
+    /// The password hashing function is run with a salt derived from the stored one, hence its
+    /// result differs from the stored hash: reading the stored hash does not reveal the keys.
+    ///
+    /// Unlike [is_valid](Hasher::is_valid), this does not check the password: a wrong password
+    /// simply yields a different key. The Hasher must have been created from a PHC formatted
+    /// string, whose salt makes the derivation reproducible.
+    pub fn derive_key(
+        &self,
+        password: &str,
+        info: &[u8],
+        out_len: usize,
+    ) -> Result<Vec<u8>, ErrorCode> {
+        let salt = self
+            .ref_salt
+            .as_ref()
+            .ok_or(ErrorCode::InvalidPasswordFormat)?;
+        // Using the stored salt as is would make the stored hash the input key material.
+        let mut mac = Hmac::<Sha256>::new_from_slice(salt)?;
+        mac.update(DERIVE_KEY_CONTEXT);
+        let kdf_salt = mac.finalize().into_bytes();
+        let ikm = self.derive_raw(password, &kdf_salt)?;
+        hkdf_sha256(salt, &ikm, info, out_len)
+    }
+
+    /// Hash a password and report the advisory [warnings](Warning) raised by the password, such
+    /// as exceeding the soft maximal length.
+    pub fn hash_checked(&self, password: &str) -> (Result<String, ErrorCode>, Vec<Warning>) {
+        let mut warnings = Vec::new();
+        if let Some(soft_max) = self.soft_max_len {
+            if self.password_len(&self.normalize_password(password)) > soft_max {
+                warnings.push(Warning::ExceedsSoftMax);
+            }
+        }
+        (self.hash(password), warnings)
+    }
+
+    /// Check whether or not the supplied password is valid.
+    ///
+    /// Verifying requires both the salt and the hash of the reference, which are only available
+    /// if this Hasher has been created from a complete PHC formatted string. Otherwise, false is
+    /// returned: use [verify_into](Hasher::verify_into) in order to tell this case apart.
     pub fn is_valid(&self, password: &str) -> bool {
-        match self.ref_hash {
-            Some(ref rh) => match self.do_hash(password) {
-                Ok(hash_duo) => {
-                    let salt = KeyBuilder::new()
-                        .size(std_default::DEFAULT_SALT_LEN)
-                        .as_vec();
-
-                    let mut ref_hmac = match Hmac::<Sha512>::new_from_slice(&salt) {
-                        Ok(h) => h,
-                        Err(_) => {
-                            return false;
-                        }
-                    };
-                    ref_hmac.update(rh.as_slice());
-
-                    let mut pass_hmac = match Hmac::<Sha512>::new_from_slice(&salt) {
-                        Ok(h) => h,
-                        Err(_) => {
-                            return false;
-                        }
-                    };
-                    pass_hmac.update(hash_duo.raw.as_slice());
-
-                    ref_hmac.finalize().into_bytes() == pass_hmac.finalize().into_bytes()
-                }
-                Err(_) => false,
+        let start = Instant::now();
+        let res = self.check_hash(password, &mut VerifyScratch::new());
+        self.report_timing(start);
+        res.unwrap_or(false)
+    }
+
+    /// Check whether or not the supplied password is valid, reusing the state kept in `scratch`
+    /// across calls in order to avoid rebuilding the hashing function and the comparison buffers.
+    /// This is meant for high-throughput verification; [is_valid](Hasher::is_valid) is the simple
+    /// equivalent.
+    ///
+    /// Once `scratch` has been used with the same stored hash, checking a password using PBKDF2
+    /// does not allocate, unless the password is
+    /// [case folded](crate::pass::HashBuilder::case_fold). Argon2 still allocates its working
+    /// memory and its output.
+    ///
+    /// Unlike [is_valid](Hasher::is_valid), the reason why a password could not be checked, such
+    /// as being too long, is reported as an error. In particular, a Hasher lacking the reference
+    /// salt or hash returns [InvalidPasswordFormat](ErrorCode::InvalidPasswordFormat).
+    pub fn verify_into(
+        &self,
+        password: &str,
+        scratch: &mut VerifyScratch,
+    ) -> Result<bool, ErrorCode> {
+        let start = Instant::now();
+        let res = self.check_hash(password, scratch);
+        self.report_timing(start);
+        res
+    }
+
+    /// Check whether or not the supplied password is valid, giving up if the verification takes
+    /// longer than `budget`. This bounds the time a request may spend on a stored hash whose
+    /// cost is unexpectedly high.
+    ///
+    /// The verification runs on a dedicated thread, which therefore requires a platform
+    /// supporting threads. Once the budget is exceeded, [Timeout](ErrorCode::Timeout) is
+    /// returned but the thread cannot be interrupted: it keeps running in the background until
+    /// the hash has been computed and its result is discarded. `Timeout` is also returned if
+    /// the thread cannot be spawned or if the verification panics.
+    ///
+    /// At most 16 of those threads, including the ones whose result has been discarded, run at
+    /// the same time in the whole process. When they are all busy, `Timeout` is returned
+    /// immediately instead of starting a new one, so that an attacker submitting expensive
+    /// hashes cannot exhaust the memory or the threads of the system.
+    ///
+    /// Like [verify_into](Hasher::verify_into), the reason why a password could not be checked
+    /// is reported as an error.
+    pub fn verify_with_timeout(&self, password: &str, budget: Duration) -> Result<bool, ErrorCode> {
+        let hasher = self.clone();
+        let password = password.to_string();
+        let (sender, receiver) = mpsc::channel();
+        let slot = TIMEOUT_WORKERS.try_acquire().ok_or(ErrorCode::Timeout)?;
+        thread::Builder::new()
+            .spawn(move || {
+                let _slot = slot;
+                let start = Instant::now();
+                let res = hasher.check_hash(&password, &mut VerifyScratch::new());
+                hasher.report_timing(start);
+                let _ = sender.send(res);
+            })
+            .map_err(|_| ErrorCode::Timeout)?;
+        receiver
+            .recv_timeout(budget)
+            .unwrap_or(Err(ErrorCode::Timeout))
+    }
+
+    /// Check whether or not the state kept in `scratch` has been built from the same stored hash
+    /// and the same settings as this Hasher.
+    fn is_scratch_current(&self, scratch: &VerifyScratch, ref_hash: &[u8]) -> bool {
+        scratch.hash_func.is_some()
+            && scratch.ref_hash == ref_hash
+            && scratch.algorithm == Some(self.algorithm)
+            && scratch.normalization == Some(self.normalization)
+            && scratch.parameters == self.parameters
+            && scratch.ref_salt == self.ref_salt
+            && scratch.salt_len == self.salt_len
+            && scratch.argon2_secret == self.argon2_secret
+            && scratch.comparison_hash == Some(self.comparison_hash)
+    }
+
+    fn check_hash(&self, password: &str, scratch: &mut VerifyScratch) -> Result<bool, ErrorCode> {
+        let ref_hash = match self.ref_hash {
+            Some(ref h) => h,
+            None => {
+                self.check_work_factor()?;
+                return Err(ErrorCode::InvalidPasswordFormat);
+            }
+        };
+        if !self.is_scratch_current(scratch, ref_hash) {
+            // The reference is only authenticated once, using a random key kept for the next
+            // verifications of the same stored hash.
+            scratch.hash_func = None;
+            let hash_func = self.get_hash_func()?;
+            let cost = CostSummary::from_parameters(self.algorithm, &hash_func.get_parameters())?;
+            scratch.work_factor = cost.work_factor;
+            scratch
+                .comparison_key
+                .resize(std_default::DEFAULT_SALT_LEN, 0);
+            fill_random(&mut scratch.comparison_key)?;
+            compute_hmac(
+                self.comparison_hash,
+                ref_hash,
+                &scratch.comparison_key,
+                &mut scratch.ref_hmac,
+            )?;
+            scratch.ref_hash.clear();
+            scratch.ref_hash.extend_from_slice(ref_hash);
+            scratch.algorithm = Some(self.algorithm);
+            scratch.normalization = Some(self.normalization);
+            scratch.parameters.clone_from(&self.parameters);
+            scratch.ref_salt.clone_from(&self.ref_salt);
+            scratch.salt_len = self.salt_len;
+            scratch.argon2_secret.clone_from(&self.argon2_secret);
+            scratch.comparison_hash = Some(self.comparison_hash);
+            scratch.hash_func = Some(hash_func);
+        }
+        if let Some(min) = self.min_work_factor {
+            if scratch.work_factor < min {
+                return Err(ErrorCode::IncompatibleOption);
+            }
+        }
+        let hash_func = match scratch.hash_func {
+            Some(ref h) => h.as_ref(),
+            None => return Err(ErrorCode::InvalidPasswordFormat),
+        };
+        self.derive_into(
+            password,
+            hash_func,
+            true,
+            &mut scratch.password,
+            &mut scratch.xhmac,
+            &mut scratch.hash,
+        )?;
+        compute_hmac(
+            self.comparison_hash,
+            &scratch.hash,
+            &scratch.comparison_key,
+            &mut scratch.pass_hmac,
+        )?;
+        Ok(scratch.ref_hmac == scratch.pass_hmac)
+    }
+
+    /// Check whether or not the supplied password is valid and report the algorithm used to
+    /// verify it.
+    pub fn verify_reporting(&self, password: &str) -> (bool, Algorithm) {
+        (self.is_valid(password), self.algorithm)
+    }
+
+    /// Set the secret used to compute the [fingerprint](Hasher::fingerprint) of the stored hash.
+    /// This is useful for a Hasher created from a PHC formatted string.
+    pub fn fingerprint_key(&mut self, secret: &[u8]) -> &mut Hasher {
+        self.fingerprint_key = Some(secret.to_vec());
+        self
+    }
+
+    /// Set whether or not this Hasher is restricted to verifying passwords, which is useful for a
+    /// Hasher created from a PHC formatted string. See
+    /// [HashBuilder::verify_only](crate::pass::HashBuilder::verify_only).
+    pub fn verify_only(&mut self, verify_only: bool) -> &mut Hasher {
+        self.verify_only = verify_only;
+        self
+    }
+
+    /// Set whether or not the length bounds are ignored when verifying a password, which is
+    /// useful for a Hasher created from a PHC formatted string. See
+    /// [HashBuilder::skip_length_check_on_verify](crate::pass::HashBuilder::skip_length_check_on_verify).
+    pub fn skip_length_check_on_verify(&mut self, skip: bool) -> &mut Hasher {
+        self.skip_length_check_on_verify = skip;
+        self
+    }
+
+    /// Override the internal version number, which is useful for a Hasher created from a PHC
+    /// formatted string in order to simulate a change of the hashing scheme by this library. See
+    /// [HashBuilder::internal_version](crate::pass::HashBuilder::internal_version).
+    pub fn internal_version(&mut self, internal_version: usize) -> &mut Hasher {
+        self.internal_version = internal_version;
+        self
+    }
+
+    /// Set the minimal [work factor](crate::pass::CostSummary::work_factor) of the hashing
+    /// algorithm, which is useful for a Hasher created from a PHC formatted string. Below this
+    /// floor, the password is not even checked: [is_valid](Hasher::is_valid) returns false and
+    /// [verify_into](Hasher::verify_into) returns
+    /// [IncompatibleOption](ErrorCode::IncompatibleOption).
+    ///
+    /// This is a hardening measure against stored hashes whose cost is implausibly low, for
+    /// example because they have been tampered with. Unlike [satisfies](Hasher::satisfies),
+    /// which is meant to decide whether or not a password should be hashed again after a
+    /// successful verification, a hash below the floor can never be verified.
+    pub fn min_work_factor(&mut self, work_factor: u64) -> &mut Hasher {
+        self.min_work_factor = Some(work_factor);
+        self
+    }
+
+    /// Return a short identifier of the stored hash, suitable for correlating logs. It is a
+    /// truncated HMAC of the hash keyed using the secret set with
+    /// [fingerprint_key](Hasher::fingerprint_key), hence it reveals neither the hash nor the
+    /// salt.
+    ///
+    /// Returns `None` if there is no stored hash or if no secret has been set.
+    pub fn fingerprint(&self) -> Option<String> {
+        let hash = self.ref_hash.as_ref()?;
+        let key = self.fingerprint_key.as_ref()?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).ok()?;
+        mac.update(hash);
+        let tag = mac.finalize().into_bytes();
+        Some(hex::encode(&tag[..FINGERPRINT_LEN]))
+    }
+
+    /// Suggest new parameters for the password hashing algorithm so that hashing a password
+    /// takes about the target duration on this machine.
+    ///
+    /// The current parameters are timed once and scaled proportionally: for Argon2, the memory
+    /// cost is increased first, then the number of passes; for PBKDF2, the number of
+    /// iterations. The suggestion is never lower than the current parameters and is bounded by
+    /// the maximal values accepted by the algorithm.
+    pub fn suggest_parameters(
+        &self,
+        target: Duration,
+    ) -> Result<HashMap<String, String>, ErrorCode> {
+        let mut hash_func = self.get_hash_func()?;
+        let start = Instant::now();
+        hash_func.hash(b"correct horse battery staple");
+        let elapsed = start.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            hash_func.increase_cost(target.as_secs_f64() / elapsed);
+        }
+        Ok(hash_func.get_parameters())
+    }
+
+    /// Return the password hashing algorithm.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Return the parameters of the password hashing algorithm. Parameters which have not been
+    /// explicitly set, either using the builder or in the PHC string, are not included.
+    pub fn parameters(&self) -> &HashMap<String, String> {
+        &self.parameters
+    }
+
+    /// Return the minimal length of the password.
+    pub fn min_len(&self) -> usize {
+        self.min_len
+    }
+
+    /// Return the maximal length of the password.
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Return the hashing scheme version number, as set using
+    /// [version](crate::pass::HashBuilder::version).
+    pub fn version(&self) -> usize {
+        self.version.saturating_sub(self.internal_version)
+    }
+
+    /// Return the Unicode normalization applied to the password.
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// Return the method used to calculate the password length.
+    pub fn length_calculation(&self) -> LengthCalculationMethod {
+        self.length_calculation
+    }
+
+    /// Return the salt of the reference hash, for example in order to detect salts reused across
+    /// several stored hashes, which would reveal a broken source of randomness. Salts are not
+    /// secret, hence exposing them is safe. A Hasher which has not been created from a PHC
+    /// formatted string does not have a salt: a new one is generated for each password.
+    pub fn salt(&self) -> Option<&[u8]> {
+        self.ref_salt.as_deref()
+    }
+
+    /// Return the metadata attached to the stored hash, as set using
+    /// [metadata](crate::pass::HashBuilder::metadata).
+    pub fn metadata(&self) -> Option<String> {
+        self.metadata.clone()
+    }
+
+    /// Check whether or not the stored hash has been created using a fixed salt instead of a
+    /// random one, which happens with
+    /// [deterministic_for_docs](crate::pass::HashBuilder::deterministic_for_docs). Such a hash
+    /// should never be found in production and must be replaced.
+    pub fn used_fixed_salt(&self) -> bool {
+        self.fixed_salt
+    }
+
+    /// Summarize the effective cost of hashing a password without actually hashing it.
+    pub fn cost_summary(&self) -> Result<CostSummary, ErrorCode> {
+        CostSummary::from_hasher(self)
+    }
+
+    /// Return the number of iterations used by PBKDF2 or `None` if the algorithm is not PBKDF2.
+    pub fn pbkdf2_iterations(&self) -> Option<u32> {
+        if self.algorithm != Algorithm::Pbkdf2 {
+            return None;
+        }
+        match self.parameters.get("iter") {
+            Some(i) => i.parse::<u32>().ok(),
+            None => Some(pbkdf2::DEFAULT_ITER),
+        }
+    }
+
+    /// Return the name of the hash function used by PBKDF2 or `None` if the algorithm is not
+    /// PBKDF2.
+    pub fn pbkdf2_hash(&self) -> Option<&str> {
+        if self.algorithm != Algorithm::Pbkdf2 {
+            return None;
+        }
+        match self.parameters.get("hmac").or(self.parameters.get("hash")) {
+            Some(h) => Some(h.as_str()),
+            None => Some(pbkdf2::DEFAULT_HASH_NAME),
+        }
+    }
+
+    /// Check whether or not the hashing scheme version number is within the range set using
+    /// [accept_versions](crate::pass::HashBuilder::accept_versions). Always true if no range has
+    /// been set.
+    pub fn is_version_accepted(&self) -> bool {
+        match self.accepted_versions {
+            Some(ref range) => match self.version.checked_sub(self.internal_version) {
+                Some(v) => range.contains(&v),
+                None => false,
             },
-            None => false,
+            None => true,
         }
     }
 
+    /// Check whether or not the password should be hashed again using the current version of
+    /// the hashing scheme. The version number has no effect on the password verification.
     pub fn needs_update(&self, current_version: Option<usize>) -> bool {
         match current_version {
-            Some(nb) => self.version < nb + INTERNAL_VERSION,
-            None => self.version < DEFAULT_USER_VERSION + INTERNAL_VERSION,
+            Some(nb) => self.version < nb + self.internal_version,
+            None => self.version < DEFAULT_USER_VERSION + self.internal_version,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pass::inline_salt::InlineSaltHash;
+    use crate::pass::HashBuilder;
+
+    #[test]
+    fn test_inline_salt_format() {
+        let hasher = HashBuilder::new().finalize().unwrap();
+        let hash_func = InlineSaltHash {
+            salt: b"somesalt".to_vec(),
+        };
+        let hash = hash_func.hash(b"password");
+        let stored = hasher.format_hash(&hash_func, &hash, 1, None).unwrap();
+        assert!(stored.ends_with("$c29tZXNhbHRwYXNzd29yZA"));
+        assert!(!stored.contains("$c29tZXNhbHQ"));
+
+        // The single trailing segment is parsed as the salt and then used as the hash.
+        let phc = PHCData::from_str(&stored).unwrap();
+        assert_eq!(phc.id, "inline");
+        assert_eq!(phc.salt, Some(hash));
+        assert!(phc.hash.is_none());
+        assert!(phc
+            .to_string()
+            .unwrap()
+            .ends_with("$c29tZXNhbHRwYXNzd29yZA"));
+    }
+}
@@ -0,0 +1,85 @@
+use super::{ErrorCode, HashingFunction, Normalization};
+use std::collections::HashMap;
+
+const SALT_LEN: usize = 8; // in bytes
+
+/// A test-only hashing function whose output starts with its salt, like bcrypt.
+pub struct InlineSaltHash {
+    pub salt: Vec<u8>,
+}
+
+impl InlineSaltHash {
+    pub fn new() -> InlineSaltHash {
+        InlineSaltHash {
+            salt: b"somesalt".to_vec(),
+        }
+    }
+
+    /// Hook making a Hasher use this function.
+    pub fn new_boxed() -> Box<dyn HashingFunction> {
+        Box::new(InlineSaltHash::new())
+    }
+}
+
+impl HashingFunction for InlineSaltHash {
+    fn get_id(&self) -> String {
+        "inline".to_string()
+    }
+
+    fn get_parameters(&self) -> HashMap<String, String> {
+        // Lets the cost be summarized as for PBKDF2, the algorithm set by the tests.
+        [("iter".to_string(), "10000".to_string())]
+            .into_iter()
+            .collect()
+    }
+
+    fn set_parameter(&mut self, _name: &str, _value: &str) -> Result<(), ErrorCode> {
+        Err(ErrorCode::InvalidPasswordFormat)
+    }
+
+    fn get_salt(&self) -> Option<Vec<u8>> {
+        Some(self.salt.clone())
+    }
+
+    fn set_salt(&mut self, salt: Vec<u8>) -> Result<(), ErrorCode> {
+        match salt.len() {
+            SALT_LEN => {
+                self.salt = salt;
+                Ok(())
+            }
+            _ => Err(ErrorCode::InvalidPasswordFormat),
+        }
+    }
+
+    fn set_salt_len(&mut self, _salt_len: usize) -> Result<(), ErrorCode> {
+        Ok(())
+    }
+
+    fn auto_salt_len(&self) -> usize {
+        SALT_LEN
+    }
+
+    fn salt_is_inline(&self) -> bool {
+        true
+    }
+
+    fn salt_from_hash(&self, hash: &[u8]) -> Option<Vec<u8>> {
+        hash.get(..SALT_LEN).map(|s| s.to_vec())
+    }
+
+    fn set_normalization(&mut self, _norm: Normalization) -> Result<(), ErrorCode> {
+        Ok(())
+    }
+
+    fn set_secret(&mut self, _secret: &[u8]) -> Result<(), ErrorCode> {
+        Err(ErrorCode::IncompatibleOption)
+    }
+
+    fn increase_cost(&mut self, _ratio: f64) {}
+
+    fn hash(&self, input: &[u8]) -> Vec<u8> {
+        let mut hash = self.salt.clone();
+        hash.extend_from_slice(input);
+        hash
+    }
+}
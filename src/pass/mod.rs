@@ -18,6 +18,8 @@
 //!
 //! The password fingerprint is stored in the [PHC] format which is very close to the modular crypt format (cf. [[1]] and [[2]]).
 //!
+//! Hashes produced by the reference Argon2 implementation (`$argon2i$`, `$argon2d$` and `$argon2id$` identifiers with a `$v=19` version segment and the `m`, `t` and `p` parameters) are also accepted, which includes those produced by PHP's `password_hash` function. Since the reference implementation neither normalizes the password nor restricts its length, those hashes default to no normalization and no minimal length.
+//!
 //! ## Supported identifiers and parameters
 //!
 //! <table>
@@ -32,19 +34,49 @@
 //!     </thead>
 //!     <tbody>
 //!         <tr>
-//!             <td rowspan="7">Global parameters</td>
+//!             <td rowspan="13">Global parameters</td>
+//!             <td>fixedsalt</td>
+//!             <td>integer: 1</td>
+//!             <td>The salt has not been randomly generated, absent for normal hashes.</td>
+//!             <td>none</td>
+//!         </tr>
+//!         <tr>
+//!             <td>fold</td>
+//!             <td>integer: 1</td>
+//!             <td>The password is converted to lowercase, absent if not requested.</td>
+//!             <td>none</td>
+//!         </tr>
+//!         <tr>
 //!             <td>len-calc</td>
-//!             <td>string: bytes | chars</td>
+//!             <td>string: bytes | chars | graphemes</td>
 //!             <td>Unicode string length calculation method.</td>
 //!             <td>chars</td>
 //!         </tr>
 //!         <tr>
+//!             <td>mac</td>
+//!             <td>string: hexadecimal</td>
+//!             <td>HMAC-SHA256 of the whole hash using a server-side secret, absent if not requested.</td>
+//!             <td>none</td>
+//!         </tr>
+//!         <tr>
+//!             <td>meta</td>
+//!             <td>string: base64</td>
+//!             <td>Application metadata, absent if not requested.</td>
+//!             <td>none</td>
+//!         </tr>
+//!         <tr>
 //!             <td>norm</td>
 //!             <td>string: nfd | nfkd | nfc | nfkc | none</td>
 //!             <td>Unicode normalization.</td>
 //!             <td>nfkc</td>
 //!         </tr>
 //!         <tr>
+//!             <td>pmac</td>
+//!             <td>string: hexadecimal</td>
+//!             <td>HMAC-SHA256 of the parameters using a server-side secret, absent if not requested.</td>
+//!             <td>none</td>
+//!         </tr>
+//!         <tr>
 //!             <td>pmax</td>
 //!             <td>integer</td>
 //!             <td>Password maximal length.</td>
@@ -57,6 +89,12 @@
 //!             <td>8</td>
 //!         </tr>
 //!         <tr>
+//!             <td>ts</td>
+//!             <td>integer</td>
+//!             <td>Creation time, in seconds since the Unix epoch, absent if not requested.</td>
+//!             <td>none</td>
+//!         </tr>
+//!         <tr>
 //!             <td>ver</td>
 //!             <td>integer</td>
 //!             <td>The password hashing version.</td>
@@ -70,12 +108,12 @@
 //!         </tr>
 //!         <tr>
 //!             <td>xhmac-alg</td>
-//!             <td>string: sha1 | sha224 | sha256 | sha384 | sha512 | sha512t224 | sha512t256 | keccak224 | keccak256 | keccak384 | keccak512 | sha3-224 | sha3-256 | sha3-384 | sha3-512</td>
+//!             <td>string: sha1 | sha224 | sha256 | sha384 | sha512 | sha512t224 | sha512t256 | keccak224 | keccak256 | keccak384 | keccak512 | sha3-224 | sha3-256 | sha3-384 | sha3-512 | blake2b</td>
 //!             <td>The underlying hash function to use for the HMAC.</td>
 //!             <td>sha512</td>
 //!         </tr>
 //!         <tr>
-//!             <td rowspan="4">argon2</td>
+//!             <td rowspan="7">argon2</td>
 //!             <td>lanes</td>
 //!             <td>integer</td>
 //!             <td>The degree of parallelism by which memory is filled during hash computation.</td>
@@ -94,15 +132,33 @@
 //!             <td>12 (4096 KiB)</td>
 //!         </tr>
 //!         <tr>
+//!             <td>m</td>
+//!             <td>integer</td>
+//!             <td>Memory cost in kibibytes, used instead of mem when it is not a power of two.</td>
+//!             <td>none</td>
+//!         </tr>
+//!         <tr>
 //!             <td>passes</td>
 //!             <td>integer</td>
 //!             <td>The number of block matrix iterations to perform.</td>
 //!             <td>3</td>
 //!         </tr>
 //!         <tr>
+//!             <td>secret</td>
+//!             <td>integer: 1</td>
+//!             <td>Present if a secret key, which is not stored, has been used.</td>
+//!             <td></td>
+//!         </tr>
+//!         <tr>
+//!             <td>variant</td>
+//!             <td>string: i | d | id</td>
+//!             <td>The Argon2 variant.</td>
+//!             <td>i</td>
+//!         </tr>
+//!         <tr>
 //!             <td rowspan="2">pbkdf2</td>
 //!             <td>hmac</td>
-//!             <td>string: sha1 | sha224 | sha256 | sha384 | sha512 | sha512t224 | sha512t256 | keccak224 | keccak256 | keccak384 | keccak512 | sha3-224 | sha3-256 | sha3-384 | sha3-512</td>
+//!             <td>string: sha1 | sha224 | sha256 | sha384 | sha512 | sha512t224 | sha512t256 | keccak224 | keccak256 | keccak384 | keccak512 | sha3-224 | sha3-256 | sha3-384 | sha3-512 | blake2b</td>
 //!             <td>The underlying hash function to use for the HMAC.</td>
 //!             <td>sha512</td>
 //!         </tr>
@@ -156,18 +212,31 @@ macro_rules! set_normalization {
     };
 }
 
+mod algorithms;
 pub(crate) mod argon2;
 #[cfg(feature = "cbindings")]
 mod cbindings;
+mod compare;
+mod context;
+mod cost;
+mod diagnose;
 mod error;
 mod hash_builder;
 mod hasher;
+#[cfg(test)]
+mod inline_salt;
+mod password_hash;
 pub(crate) mod pbkdf2;
 mod phc;
+mod policy;
+mod recovery;
+mod redact;
 pub(crate) mod std_default;
 pub(crate) mod std_nist;
 #[cfg(test)]
 mod tests;
+mod tuning;
+mod unassigned;
 
 #[cfg(feature = "cbindings")]
 pub use self::cbindings::libreauth_pass_hash;
@@ -180,17 +249,48 @@ pub use self::cbindings::libreauth_pass_init_std;
 #[cfg(feature = "cbindings")]
 pub use self::cbindings::libreauth_pass_is_valid;
 #[cfg(feature = "cbindings")]
+pub use self::cbindings::libreauth_pass_verify_and_upgrade;
+#[cfg(feature = "cbindings")]
 pub use self::cbindings::PassCfg;
 #[cfg(feature = "cbindings")]
 pub use self::cbindings::XHMACType;
-pub use error::ErrorCode;
+pub use algorithms::{available_algorithms, AlgorithmInfo};
+pub use compare::{matches_any, same_derivation};
+pub use context::check_not_in_context;
+pub use cost::{estimate_crack_cost, CostSummary};
+pub use diagnose::{diagnose, is_valid_phc, validate_phc, Diagnostic};
+pub use error::{ErrorCode, PasswordPolicyError};
 pub use hash_builder::HashBuilder;
-pub use hasher::Hasher;
+pub use hasher::{Hasher, VerifyScratch};
+pub use password_hash::PasswordHash;
+pub use policy::{Policy, UpdateReasons};
+pub use recovery::generate_recovery_codes;
+pub use redact::redact;
 use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
+pub use tuning::calibrate_pbkdf2;
 
 const INTERNAL_VERSION: usize = 1;
+const PARAM_MAC: &str = "pmac";
+const INTEGRITY_MAC: &str = "mac";
+const TIMESTAMP: &str = "ts";
+const METADATA: &str = "meta";
+const FIXED_SALT: &str = "fixedsalt";
+const MAX_METADATA_LEN: usize = 256; // in bytes
 const DEFAULT_USER_VERSION: usize = 0;
+const MAX_PASSWORD_LEN: usize = 65_536;
+const MAX_VERSION: usize = u32::MAX as usize;
+const FINGERPRINT_LEN: usize = 8; // in bytes
+const MIN_SALT_LEN_BITS: usize = 64;
+
+/// Return the internal version number of the hashing scheme. It is added to the hashing scheme
+/// [version](HashBuilder::version) number stored in the hashes and is increased when this library
+/// changes the way passwords are hashed, so that [needs_update](Hasher::needs_update) reports
+/// hashes produced by a previous release.
+pub fn internal_version() -> usize {
+    INTERNAL_VERSION
+}
 
 /// The recommended length to reserve for password hash storage.
 ///
@@ -226,7 +326,9 @@ pub const PASSWORD_STORAGE_LEN: usize = 512;
 ///     </tbody>
 /// </table>
 #[repr(C)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Algorithm {
     Argon2 = 0,
     Pbkdf2 = 1,
@@ -253,13 +355,20 @@ pub enum Algorithm {
 ///             <td>Characters</td>
 ///             <td>LIBREAUTH_PASS_CHARACTERS</td>
 ///         </tr>
+///         <tr>
+///             <td>Graphemes</td>
+///             <td>LIBREAUTH_PASS_GRAPHEMES</td>
+///         </tr>
 ///     </tbody>
 /// </table>
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LengthCalculationMethod {
     Bytes = 0,
+    /// Counts the Unicode scalar values.
     Characters = 1,
+    /// Counts the extended grapheme clusters, which are the user-perceived characters.
+    Graphemes = 2,
 }
 
 /// Available string normalization methods.
@@ -298,7 +407,9 @@ pub enum LengthCalculationMethod {
 ///     </tbody>
 /// </table>
 #[repr(C)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Normalization {
     Nfd = 1,
     Nfkd = 2,
@@ -307,6 +418,52 @@ pub enum Normalization {
     None = 0,
 }
 
+impl Normalization {
+    /// Return the normalization currently recommended by this library, which is the default
+    /// one. It is NFKC, but may change in a future version.
+    pub fn recommended() -> Normalization {
+        std_default::DEFAULT_NORMALIZATION
+    }
+}
+
+impl FromStr for Normalization {
+    type Err = ErrorCode;
+
+    fn from_str(data: &str) -> Result<Self, Self::Err> {
+        Ok(match data {
+            "nfd" => Normalization::Nfd,
+            "nfkd" => Normalization::Nfkd,
+            "nfc" => Normalization::Nfc,
+            "nfkc" => Normalization::Nfkc,
+            "none" => Normalization::None,
+            _ => return Err(ErrorCode::InvalidPasswordFormat),
+        })
+    }
+}
+
+/// Advisory warnings which do not prevent a password from being hashed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Warning {
+    /// The password is longer than the soft maximal length.
+    ExceedsSoftMax,
+}
+
+/// Order of the salt and hash segments in a stored hash, used by
+/// [from_phc_with_layout](crate::pass::HashBuilder::from_phc_with_layout).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SegmentLayout {
+    /// The salt followed by the hash, as specified by the PHC format.
+    SaltHash,
+    /// The hash followed by the salt.
+    HashSalt,
+}
+
+impl Default for SegmentLayout {
+    fn default() -> Self {
+        SegmentLayout::SaltHash
+    }
+}
+
 /// Defines whether or not LibreAuth should comply with recommendations from a specific standard.
 ///
 /// ## C interface
@@ -339,6 +496,16 @@ pub enum PasswordStorageStandard {
     Nist80063b = 1,
 }
 
+impl PasswordStorageStandard {
+    /// Return the normalization used by default when complying with this standard.
+    pub fn default_normalization(&self) -> Normalization {
+        match self {
+            PasswordStorageStandard::NoStandard => std_default::DEFAULT_NORMALIZATION,
+            PasswordStorageStandard::Nist80063b => std_nist::DEFAULT_NORMALIZATION,
+        }
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 #[allow(clippy::upper_case_acronyms)]
 pub(crate) enum XHMAC {
@@ -368,6 +535,22 @@ impl XHMAC {
     }
 }
 
+/// Generate a random salt, reporting a failure of the system's random number generator
+/// instead of panicking.
+fn generate_salt(len: usize) -> Result<Vec<u8>, ErrorCode> {
+    let mut salt = vec![0; len];
+    fill_random(&mut salt)?;
+    Ok(salt)
+}
+
+fn fill_random(buf: &mut [u8]) -> Result<(), ErrorCode> {
+    map_rng_result(getrandom::getrandom(buf))
+}
+
+fn map_rng_result(res: Result<(), getrandom::Error>) -> Result<(), ErrorCode> {
+    res.map_err(|_| ErrorCode::RandomnessFailure)
+}
+
 trait HashingFunction {
     fn get_id(&self) -> String;
     fn get_parameters(&self) -> HashMap<String, String>;
@@ -375,11 +558,23 @@ trait HashingFunction {
     fn get_salt(&self) -> Option<Vec<u8>>;
     fn set_salt(&mut self, salt: Vec<u8>) -> Result<(), ErrorCode>;
     fn set_salt_len(&mut self, salt_len: usize) -> Result<(), ErrorCode>;
+    fn auto_salt_len(&self) -> usize;
+    /// Whether or not the salt is embedded in the hash, in which case the stored hash only has a
+    /// single trailing segment instead of a salt followed by the hash.
+    fn salt_is_inline(&self) -> bool {
+        false
+    }
+    /// For the functions whose salt is inline, extract the salt from a stored hash.
+    fn salt_from_hash(&self, _hash: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
     fn set_normalization(&mut self, norm: Normalization) -> Result<(), ErrorCode>;
+    fn set_secret(&mut self, secret: &[u8]) -> Result<(), ErrorCode>;
+    fn increase_cost(&mut self, ratio: f64);
     fn hash(&self, input: &[u8]) -> Vec<u8>;
-}
-
-struct HashedDuo {
-    raw: Vec<u8>,
-    formated: String,
+    /// Same as `hash` but writes the result into `out`, which avoids an allocation for the
+    /// functions able to reuse its capacity.
+    fn hash_into(&self, input: &[u8], out: &mut Vec<u8>) {
+        *out = self.hash(input);
+    }
 }
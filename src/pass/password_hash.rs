@@ -0,0 +1,169 @@
+use super::{validate_phc, ErrorCode, HashBuilder, Hasher};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// A stored password hash, as returned by [hash_typed](Hasher::hash_typed).
+///
+/// Unlike a plain `String`, it cannot be confused with any other string: it can only be built by
+/// hashing a password or by parsing a string whose structure is valid according to
+/// [validate_phc]. Since this check does not require any secret, hashes using a pepper or an
+/// Argon2 secret are accepted. When the `serde` feature is enabled, it is serialized as a string
+/// and deserialization applies the same check.
+///
+/// ## Examples
+/// ```
+/// use libreauth::pass::{HashBuilder, PasswordHash};
+///
+/// let hasher = HashBuilder::new().finalize().unwrap();
+/// let stored_hash = hasher.hash_typed("correct horse battery staple").unwrap();
+/// let parsed: PasswordHash = stored_hash.to_string().parse().unwrap();
+/// assert!(parsed.checker().unwrap().is_valid("correct horse battery staple"));
+/// assert!("not a hash".parse::<PasswordHash>().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PasswordHash(String);
+
+impl PasswordHash {
+    /// Create a new Hasher object able to check a password against this hash. Hashes requiring
+    /// a secret must be loaded using the corresponding constructor of [HashBuilder] instead,
+    /// such as [from_phc_xhmac](HashBuilder::from_phc_xhmac).
+    pub fn checker(&self) -> Result<Hasher, ErrorCode> {
+        HashBuilder::from_phc(&self.0)
+    }
+
+    /// Return the hash as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for PasswordHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for PasswordHash {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for PasswordHash {
+    type Err = ErrorCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        validate_phc(s)?;
+        Ok(PasswordHash(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for PasswordHash {
+    type Error = ErrorCode;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        validate_phc(&s)?;
+        Ok(PasswordHash(s))
+    }
+}
+
+impl From<PasswordHash> for String {
+    fn from(hash: PasswordHash) -> Self {
+        hash.0
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PasswordHash {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PasswordHash {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        PasswordHash::try_from(s).map_err(|_| serde::de::Error::custom("invalid password hash"))
+    }
+}
+
+impl Hasher {
+    /// Hash a password, as [hash](Hasher::hash) does, and return the result as a
+    /// [PasswordHash].
+    pub fn hash_typed(&self, password: &str) -> Result<PasswordHash, ErrorCode> {
+        self.hash(password).map(PasswordHash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let password = "correct horse battery staple";
+        let hasher = HashBuilder::new().finalize().unwrap();
+        let stored_hash = hasher.hash_typed(password).unwrap();
+        let parsed = PasswordHash::from_str(stored_hash.as_ref()).unwrap();
+        assert_eq!(parsed, stored_hash);
+        assert_eq!(parsed.to_string(), stored_hash.as_str());
+        assert!(parsed.checker().unwrap().is_valid(password));
+        let s: String = parsed.into();
+        assert_eq!(PasswordHash::try_from(s).unwrap(), stored_hash);
+    }
+
+    #[test]
+    fn test_round_trip_pepper() {
+        let password = "correct horse battery staple";
+        let pepper = b"server-side pepper";
+        let hasher = HashBuilder::new().xhmac_before(pepper).finalize().unwrap();
+        let stored_hash = hasher.hash_typed(password).unwrap();
+        let parsed = PasswordHash::from_str(stored_hash.as_str()).unwrap();
+        assert_eq!(parsed, stored_hash);
+        let s: String = parsed.into();
+        assert_eq!(PasswordHash::try_from(s).unwrap(), stored_hash);
+        assert!(stored_hash.checker().is_err());
+        let checker = HashBuilder::from_phc_xhmac(stored_hash.as_str(), pepper).unwrap();
+        assert!(checker.is_valid(password));
+    }
+
+    #[test]
+    fn test_invalid() {
+        for s in [
+            "",
+            "not a hash",
+            "$pbkdf2$iter=1$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+        ] {
+            assert!(matches!(
+                PasswordHash::from_str(s),
+                Err(ErrorCode::InvalidPasswordFormat)
+            ));
+            assert!(PasswordHash::try_from(s.to_string()).is_err());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let hasher = HashBuilder::new().finalize().unwrap();
+        let stored_hash = hasher.hash_typed("correct horse battery staple").unwrap();
+        let json = serde_json::to_string(&stored_hash).unwrap();
+        assert_eq!(json, format!("\"{}\"", stored_hash));
+        let parsed: PasswordHash = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, stored_hash);
+        assert!(serde_json::from_str::<PasswordHash>("\"not a hash\"").is_err());
+
+        let hasher = HashBuilder::new()
+            .argon2_secret(b"argon2 secret key")
+            .finalize()
+            .unwrap();
+        let stored_hash = hasher.hash_typed("correct horse battery staple").unwrap();
+        let json = serde_json::to_string(&stored_hash).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PasswordHash>(&json).unwrap(),
+            stored_hash
+        );
+    }
+}
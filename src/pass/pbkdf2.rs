@@ -1,7 +1,7 @@
-use super::{std_default, ErrorCode, HashingFunction, Normalization};
+use super::{generate_salt, std_default, ErrorCode, HashingFunction, Normalization};
 use crate::hash::HashFunction;
-use crate::key::KeyBuilder;
-use hmac::Hmac;
+use blake2::Blake2b512;
+use hmac::{Hmac, SimpleHmac};
 use pbkdf2::pbkdf2;
 use sha1::Sha1;
 use sha2::{Sha224, Sha256, Sha384, Sha512, Sha512_224, Sha512_256};
@@ -10,18 +10,20 @@ use std::collections::HashMap;
 use std::str::FromStr;
 
 pub const DEFAULT_HASH_FUNCTION: HashFunction = HashFunction::Sha512;
+pub const DEFAULT_HASH_NAME: &str = "sha512";
 const MIN_SALT_LENGTH: usize = 4; // in bytes
 const MAX_SALT_LENGTH: usize = 256; // in bytes
-const MIN_ITER: u32 = 10_000;
-const MAX_ITER: u32 = 200_000;
+pub const MIN_ITER: u32 = 10_000;
+pub const MAX_ITER: u32 = 200_000;
 pub const DEFAULT_ITER: u32 = 45_000;
 
 macro_rules! process_pbkdf2 {
-    ($obj: ident, $input: ident, $hash: ty, $len: expr) => {{
-        let mut out = [0u8; $len];
-        pbkdf2::<Hmac<$hash>>($input, $obj.salt.as_slice(), $obj.nb_iter, &mut out[..$len])
-            .unwrap();
-        out.to_vec()
+    ($obj: ident, $input: ident, $out: ident, $hash: ty, $len: expr) => {{
+        process_pbkdf2!($obj, $input, $out, Hmac, $hash, $len)
+    }};
+    ($obj: ident, $input: ident, $out: ident, $mac: ident, $hash: ty, $len: expr) => {{
+        $out.resize($len, 0);
+        pbkdf2::<$mac<$hash>>($input, $obj.salt.as_slice(), $obj.nb_iter, &mut $out[..]).unwrap();
     }};
 }
 
@@ -34,12 +36,19 @@ pub struct Pbkdf2Hash {
 
 impl Pbkdf2Hash {
     pub fn new() -> Pbkdf2Hash {
+        Pbkdf2Hash {
+            salt: generate_salt(std_default::DEFAULT_SALT_LEN).unwrap_or_default(),
+            ..Pbkdf2Hash::without_salt()
+        }
+    }
+
+    /// Create the hashing function with an empty salt, which must be set before hashing. Unlike
+    /// [new](Pbkdf2Hash::new), this does not use the random number generator.
+    pub fn without_salt() -> Pbkdf2Hash {
         Pbkdf2Hash {
             hash_function: DEFAULT_HASH_FUNCTION,
             nb_iter: DEFAULT_ITER,
-            salt: KeyBuilder::new()
-                .size(std_default::DEFAULT_SALT_LEN)
-                .as_vec(),
+            salt: Vec::new(),
             norm: Normalization::Nfkc,
         }
     }
@@ -99,32 +108,61 @@ impl HashingFunction for Pbkdf2Hash {
     }
 
     fn set_salt_len(&mut self, salt_len: usize) -> Result<(), ErrorCode> {
-        let salt = KeyBuilder::new().size(salt_len).as_vec();
+        let salt = generate_salt(salt_len)?;
         self.set_salt(salt)
     }
 
+    /// The salt has the same length as the derived key.
+    fn auto_salt_len(&self) -> usize {
+        match self.hash_function {
+            HashFunction::Sha1 => 20,
+            HashFunction::Sha224 | HashFunction::Sha512Trunc224 | HashFunction::Sha3_224 => 28,
+            HashFunction::Sha384 | HashFunction::Sha3_384 => 48,
+            HashFunction::Sha512 | HashFunction::Sha3_512 | HashFunction::Blake2b => 64,
+            _ => 32,
+        }
+    }
+
     fn set_normalization(&mut self, norm: Normalization) -> Result<(), ErrorCode> {
         self.norm = norm;
         Ok(())
     }
 
+    fn set_secret(&mut self, _secret: &[u8]) -> Result<(), ErrorCode> {
+        Err(ErrorCode::IncompatibleOption)
+    }
+
+    fn increase_cost(&mut self, ratio: f64) {
+        if ratio > 1.0 {
+            let nb_iter = (f64::from(self.nb_iter) * ratio).min(f64::from(MAX_ITER));
+            self.nb_iter = self.nb_iter.max(nb_iter as u32);
+        }
+    }
+
     fn hash(&self, input: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.hash_into(input, &mut out);
+        out
+    }
+
+    fn hash_into(&self, input: &[u8], out: &mut Vec<u8>) {
         match self.hash_function {
-            HashFunction::Sha1 => process_pbkdf2!(self, input, Sha1, 20),
-            HashFunction::Sha224 => process_pbkdf2!(self, input, Sha224, 28),
-            HashFunction::Sha256 => process_pbkdf2!(self, input, Sha256, 32),
-            HashFunction::Sha384 => process_pbkdf2!(self, input, Sha384, 48),
-            HashFunction::Sha512 => process_pbkdf2!(self, input, Sha512, 64),
-            HashFunction::Sha512Trunc224 => process_pbkdf2!(self, input, Sha512_224, 28),
-            HashFunction::Sha512Trunc256 => process_pbkdf2!(self, input, Sha512_256, 32),
-            HashFunction::Keccak224 => process_pbkdf2!(self, input, Keccak224, 32),
-            HashFunction::Keccak256 => process_pbkdf2!(self, input, Keccak256, 32),
-            HashFunction::Keccak384 => process_pbkdf2!(self, input, Keccak384, 32),
-            HashFunction::Keccak512 => process_pbkdf2!(self, input, Keccak512, 32),
-            HashFunction::Sha3_224 => process_pbkdf2!(self, input, Sha3_224, 28),
-            HashFunction::Sha3_256 => process_pbkdf2!(self, input, Sha3_256, 32),
-            HashFunction::Sha3_384 => process_pbkdf2!(self, input, Sha3_384, 48),
-            HashFunction::Sha3_512 => process_pbkdf2!(self, input, Sha3_512, 64),
+            HashFunction::Sha1 => process_pbkdf2!(self, input, out, Sha1, 20),
+            HashFunction::Sha224 => process_pbkdf2!(self, input, out, Sha224, 28),
+            HashFunction::Sha256 => process_pbkdf2!(self, input, out, Sha256, 32),
+            HashFunction::Sha384 => process_pbkdf2!(self, input, out, Sha384, 48),
+            HashFunction::Sha512 => process_pbkdf2!(self, input, out, Sha512, 64),
+            HashFunction::Sha512Trunc224 => process_pbkdf2!(self, input, out, Sha512_224, 28),
+            HashFunction::Sha512Trunc256 => process_pbkdf2!(self, input, out, Sha512_256, 32),
+            HashFunction::Keccak224 => process_pbkdf2!(self, input, out, Keccak224, 32),
+            HashFunction::Keccak256 => process_pbkdf2!(self, input, out, Keccak256, 32),
+            HashFunction::Keccak384 => process_pbkdf2!(self, input, out, Keccak384, 32),
+            HashFunction::Keccak512 => process_pbkdf2!(self, input, out, Keccak512, 32),
+            HashFunction::Sha3_224 => process_pbkdf2!(self, input, out, Sha3_224, 28),
+            HashFunction::Sha3_256 => process_pbkdf2!(self, input, out, Sha3_256, 32),
+            HashFunction::Sha3_384 => process_pbkdf2!(self, input, out, Sha3_384, 48),
+            HashFunction::Sha3_512 => process_pbkdf2!(self, input, out, Sha3_512, 64),
+            HashFunction::Blake2b => process_pbkdf2!(self, input, out, SimpleHmac, Blake2b512, 64),
         }
     }
 }
@@ -180,6 +218,13 @@ mod tests {
         assert!(h.get_salt().unwrap().len() >= 4);
     }
 
+    #[test]
+    fn test_without_salt() {
+        let h = Pbkdf2Hash::without_salt();
+        assert!(h.salt.is_empty());
+        assert_eq!(h.get_parameters(), Pbkdf2Hash::new().get_parameters());
+    }
+
     /// NIST SP 800-63B: the salt shall be chosen arbitrarily
     #[test]
     fn test_salt_randomness() {
@@ -205,7 +250,7 @@ mod tests {
                 "salt",
                 "password",
                 vec![
-                    0x0c as u8, 0x60, 0xc8, 0x0f, 0x96, 0x1f, 0x0e, 0x71, 0xf3, 0xa9, 0xb5, 0x24,
+                    0x0c_u8, 0x60, 0xc8, 0x0f, 0x96, 0x1f, 0x0e, 0x71, 0xf3, 0xa9, 0xb5, 0x24,
                     0xaf, 0x60, 0x12, 0x06, 0x2f, 0xe0, 0x37, 0xa6,
                 ],
             ),
@@ -355,6 +400,19 @@ mod tests {
                     0x78, 0xc8, 0x7e, 0x67, 0x48, 0xf6, 0x65, 0x24, 0x48, 0xb5, 0xce, 0x2f,
                 ],
             ),
+            (
+                "blake2b",
+                1605,
+                "Ejj2M0Mo",
+                "LdUEx0sZfn7X",
+                vec![
+                    0xd2, 0xb8, 0x6f, 0x1b, 0xaf, 0x8b, 0x31, 0xfb, 0x87, 0x37, 0x76, 0xa2, 0x63,
+                    0x6, 0x62, 0x84, 0xbb, 0xfc, 0x45, 0x4a, 0x71, 0x28, 0x68, 0x2a, 0xdc, 0x5d,
+                    0x91, 0x6c, 0x3c, 0xe6, 0x42, 0xd6, 0x2d, 0xd9, 0xb9, 0x71, 0xba, 0x6e, 0xfb,
+                    0x56, 0xbd, 0x32, 0xe6, 0x8c, 0xd9, 0x54, 0xfe, 0xf6, 0x7b, 0x55, 0xcb, 0xbe,
+                    0xe4, 0x55, 0x3a, 0xd1, 0x94, 0x10, 0xec, 0x6, 0x41, 0xdb, 0x3d, 0x63,
+                ],
+            ),
         ];
         for &(func, nbi, salt, key, ref result) in lst.iter() {
             let h = Pbkdf2Hash {
@@ -374,6 +432,7 @@ mod tests {
                     "sha3-256" => HashFunction::Sha3_256,
                     "sha3-384" => HashFunction::Sha3_384,
                     "sha3-512" => HashFunction::Sha3_512,
+                    "blake2b" => HashFunction::Blake2b,
                     _ => {
                         panic!();
                     }
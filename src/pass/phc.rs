@@ -1,26 +1,34 @@
 use base64::engine::general_purpose::STANDARD_NO_PAD;
 use base64::Engine;
 use nom::bytes::complete::{tag, take_while, take_while1};
-use nom::combinator::{map_res, opt};
+use nom::character::complete::digit1;
+use nom::combinator::opt;
 use nom::multi::fold_many0;
 use nom::sequence::{preceded, separated_pair, terminated};
 use nom::IResult;
 use std::collections::HashMap;
 
-fn from_b64(data: &str) -> Result<Option<Vec<u8>>, ()> {
-    Ok(match data.len() {
-        0 => None,
-        _ => match STANDARD_NO_PAD.decode(data.as_bytes()) {
-            Ok(r) => Some(r),
-            Err(_) => None,
-        },
-    })
+pub const DEFAULT_MAX_LEN: usize = 4096; // in bytes
+pub const MAX_PREFIX_SEGMENTS: usize = 4;
+
+fn from_b64(data: &str) -> Option<Vec<u8>> {
+    STANDARD_NO_PAD.decode(data.as_bytes()).ok()
 }
 
 fn to_b64(data: &[u8]) -> String {
     STANDARD_NO_PAD.encode(data)
 }
 
+/// Encode the metadata stored in a parameter, whose value cannot contain every character.
+pub fn encode_metadata(metadata: &str) -> String {
+    STANDARD_NO_PAD.encode(metadata.as_bytes())
+}
+
+pub fn decode_metadata(value: &str) -> Option<String> {
+    let raw = STANDARD_NO_PAD.decode(value.as_bytes()).ok()?;
+    String::from_utf8(raw).ok()
+}
+
 fn is_b64(chr: char) -> bool {
     chr.is_ascii_alphanumeric() || chr == '+' || chr == '/'
 }
@@ -41,15 +49,31 @@ fn get_id(input: &str) -> IResult<&str, &str> {
     preceded(tag("$"), take_while1(is_id_char))(input)
 }
 
-fn get_phc_part(input: &str) -> IResult<&str, Option<Vec<u8>>> {
+/// The optional version segment (`$v=<version>`) used by the reference Argon2 implementation.
+fn get_version(input: &str) -> IResult<&str, Option<u32>> {
+    let (rest, version) = match preceded(tag("$v="), digit1::<&str, ()>)(input) {
+        Ok(v) => v,
+        Err(_) => return Ok((input, None)),
+    };
+    if !(rest.is_empty() || rest.starts_with('$')) {
+        return Ok((input, None));
+    }
+    match version.parse::<u32>() {
+        Ok(v) => Ok((rest, Some(v))),
+        Err(_) => Ok((input, None)),
+    }
+}
+
+fn get_phc_part(input: &str) -> IResult<&str, Option<&str>> {
     if input.is_empty() {
         return Ok((input, None));
     }
-    map_res(preceded(tag("$"), take_while(is_b64)), from_b64)(input)
+    let (input, part) = preceded(tag("$"), take_while(is_b64))(input)?;
+    Ok((input, Some(part).filter(|p| !p.is_empty())))
 }
 
 // TODO: replace by the not-yet implemented nom::opt()
-fn get_phc_part_if(input: &str, cond: bool) -> IResult<&str, Option<Vec<u8>>> {
+fn get_phc_part_if(input: &str, cond: bool) -> IResult<&str, Option<&str>> {
     if cond {
         get_phc_part(input)
     } else {
@@ -83,39 +107,104 @@ fn parse_params(input: &str) -> IResult<&str, HashMap<String, String>> {
     preceded(tag("$"), get_params)(input)
 }
 
-fn get_phc(input: &str) -> IResult<&str, PHCData> {
+type RawPHCData<'a> = (
+    &'a str,
+    Option<u32>,
+    Option<HashMap<String, String>>,
+    Option<&'a str>,
+    Option<&'a str>,
+);
+
+fn get_phc(input: &str) -> IResult<&str, RawPHCData<'_>> {
     let (input, id) = get_id(input)?;
+    let (input, version) = get_version(input)?;
     let (input, parameters) = opt(parse_params)(input)?;
     let (input, salt) = get_phc_part_if(input, parameters.is_some())?;
     let (input, hash) = get_phc_part_if(input, salt.is_some())?;
-    let parameters = match parameters {
-        Some(p) => p,
-        None => HashMap::new(),
-    };
-    let data = PHCData {
-        id: id.to_string(),
-        parameters,
-        salt,
-        hash,
-    };
-    Ok((input, data))
+    Ok((input, (id, version, parameters, salt, hash)))
 }
 
 pub struct PHCData {
     pub id: String,
+    pub version: Option<u32>,
     pub parameters: HashMap<String, String>,
     pub salt: Option<Vec<u8>>,
     pub hash: Option<Vec<u8>>,
+    pub prefix: Option<String>,
 }
 
 impl PHCData {
     pub fn from_str(s: &str) -> Result<PHCData, ()> {
-        match get_phc(s) {
+        PHCData::from_str_max_len(s, DEFAULT_MAX_LEN)
+    }
+
+    /// Same as `from_str` but rejects, before any parsing, strings longer than `max_len` bytes.
+    pub fn from_str_max_len(s: &str, max_len: usize) -> Result<PHCData, ()> {
+        if s.len() > max_len {
+            return Err(());
+        }
+        let (id, version, parameters, salt, hash) = match get_phc(s) {
             Ok((r, v)) => match r.len() {
-                0 => Ok(v),
-                _ => Err(()),
+                0 => v,
+                _ => return Err(()),
             },
-            Err(_) => Err(()),
+            Err(_) => return Err(()),
+        };
+        let parameters = parameters.unwrap_or_default();
+        let salt = match salt {
+            Some(s) => Some(from_b64(s).ok_or(())?),
+            None => None,
+        };
+        let hash = match hash {
+            Some(h) => Some(from_b64(h).ok_or(())?),
+            None => None,
+        };
+        Ok(PHCData {
+            id: id.to_string(),
+            version,
+            parameters,
+            salt,
+            hash,
+            prefix: None,
+        })
+    }
+
+    /// Same as `from_str` but tolerates up to `MAX_PREFIX_SEGMENTS` unrecognized leading
+    /// segments, such as a function-family prefix, which are kept in `prefix` and emitted
+    /// unchanged by `to_string`.
+    pub fn from_str_prefixed(s: &str) -> Result<PHCData, ()> {
+        if s.len() > DEFAULT_MAX_LEN {
+            return Err(());
+        }
+        if let Ok(phc) = PHCData::from_str(s) {
+            return Ok(phc);
+        }
+        if !s.starts_with('$') {
+            return Err(());
+        }
+        let mut start = 0;
+        for _ in 0..MAX_PREFIX_SEGMENTS {
+            let end = start + 1 + s[start + 1..].find('$').ok_or(())?;
+            let segment = &s[start + 1..end];
+            if segment.is_empty() || !segment.chars().all(is_id_char) {
+                return Err(());
+            }
+            start = end;
+            if let Ok(mut phc) = PHCData::from_str(&s[start..]) {
+                phc.prefix = Some(s[..start].to_string());
+                return Ok(phc);
+            }
+        }
+        Err(())
+    }
+
+    /// Same as `from_str` but only accepts strings that are in their canonical form, hence
+    /// having the same length once serialized again.
+    pub fn from_str_strict(s: &str) -> Result<PHCData, ()> {
+        let phc = PHCData::from_str(s)?;
+        match phc.prefix.is_none() && phc.to_string()?.len() == s.len() {
+            true => Ok(phc),
+            false => Err(()),
         }
     }
 
@@ -123,14 +212,26 @@ impl PHCData {
         if self.id.is_empty() {
             return Err(());
         }
-        let mut res = String::from("$");
+        let mut res = self.prefix.clone().unwrap_or_default();
+        res += "$";
         res += self.id.as_str();
+        if let Some(v) = self.version {
+            res += &format!("$v={}", v);
+        }
+
+        let mut parameters: Vec<(&str, &str)> = self
+            .parameters
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        // Sorted so that the same data is always serialized the same way.
+        parameters.sort_unstable();
 
-        if self.parameters.is_empty() && self.salt.is_none() {
+        if parameters.is_empty() && self.salt.is_none() && self.hash.is_none() {
             return Ok(res);
         }
         res += "$";
-        for (i, (k, v)) in self.parameters.iter().enumerate() {
+        for (i, (k, v)) in parameters.iter().enumerate() {
             res += &match i {
                 0 => format!("{}={}", k, v),
                 _ => format!(",{}={}", k, v),
@@ -150,7 +251,14 @@ impl PHCData {
                     None => Ok(res),
                 }
             }
-            None => Ok(res),
+            None => {
+                // The salt may be embedded in the hash, which is then the only trailing segment.
+                if let Some(ref h) = self.hash {
+                    res += "$";
+                    res += to_b64(h).as_str();
+                }
+                Ok(res)
+            }
         }
     }
 }
@@ -172,6 +280,8 @@ mod tests {
             "$pbkdf2-sha256$t-y=./42+a-1$RSF4Aw$xvdfA4H7QJQ1w/4jGcjBEIjCvsc",
             "$pbkdf2$$RSF4Aw",
             "$pbkdf2$i=21000$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+            "$test$v=19",
+            "$test$v=19$i=42$YXN1cmUu$YW55IGNhcm5hbCBwbGVhc3Vy",
         ];
         for ref_str in data.iter() {
             let phc = PHCData::from_str(ref_str);
@@ -182,6 +292,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_version() {
+        let phc = PHCData::from_str("$test$v=19$i=42$YXN1cmUu").unwrap();
+        assert_eq!(phc.version, Some(19));
+        assert_eq!(phc.parameters.len(), 1);
+        let phc = PHCData::from_str("$test$v=19,i=42$YXN1cmUu").unwrap();
+        assert_eq!(phc.version, None);
+        assert_eq!(phc.parameters.get("v").unwrap(), "19");
+    }
+
+    #[test]
+    fn test_missing_segments() {
+        let phc = PHCData::from_str("$test$i=42$YXN1cmUu").unwrap();
+        assert!(phc.salt.is_some());
+        assert!(phc.hash.is_none());
+        for s in ["$test$i=42", "$test$i=42$"] {
+            let phc = PHCData::from_str(s).unwrap();
+            assert!(phc.salt.is_none());
+            assert!(phc.hash.is_none());
+        }
+        // A hash can only follow a salt.
+        for s in [
+            "$test$i=42$$",
+            "$test$i=42$$YW55IGNhcm5hbCBwbGVhc3Vy",
+            "$test$$$YW55IGNhcm5hbCBwbGVhc3Vy",
+        ] {
+            assert!(PHCData::from_str(s).is_err());
+        }
+    }
+
     #[test]
     fn test_to_string_diff() {
         let data = [
@@ -199,6 +339,50 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_inline_salt() {
+        let phc = PHCData {
+            id: "test".to_string(),
+            version: None,
+            parameters: [("i".to_string(), "42".to_string())].into_iter().collect(),
+            salt: None,
+            hash: Some(b"any carnal pleasur".to_vec()),
+            prefix: None,
+        };
+        let s = phc.to_string().unwrap();
+        assert_eq!(s, "$test$i=42$YW55IGNhcm5hbCBwbGVhc3Vy");
+        let phc = PHCData::from_str(&s).unwrap();
+        assert_eq!(phc.salt.as_deref(), Some(&b"any carnal pleasur"[..]));
+        assert!(phc.hash.is_none());
+        assert_eq!(phc.to_string().unwrap(), s);
+    }
+
+    #[test]
+    fn test_strict() {
+        let data = [
+            "$test$",
+            "$test$$",
+            "$test$$YXN1cmUu$",
+            "$test$i=42$YXN1cmUu$",
+            "$test$i=42,$YXN1cmUu",
+            "$test$i=42,i=21$YXN1cmUu",
+        ];
+        for s in data.iter() {
+            assert!(PHCData::from_str(s).is_ok());
+            assert!(PHCData::from_str_strict(s).is_err());
+        }
+        let data = [
+            "$test",
+            "$test$i=42",
+            "$test$$YXN1cmUu",
+            "$test$i=42$YXN1cmUu$YW55IGNhcm5hbCBwbGVhc3Vy",
+            "$pbkdf2$i=21000$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+        ];
+        for s in data.iter() {
+            assert!(PHCData::from_str_strict(s).is_ok());
+        }
+    }
+
     #[test]
     fn test_valid_data_id() {
         let phc = PHCData::from_str("$dummy");
@@ -219,7 +403,7 @@ mod tests {
         assert_eq!(phc.parameters.len(), 1);
         match phc.parameters.get("i") {
             Some(v) => assert_eq!(v, "42"),
-            None => assert!(false),
+            None => panic!(),
         }
         assert_eq!(phc.salt, None);
         assert_eq!(phc.hash, None);
@@ -234,11 +418,11 @@ mod tests {
         assert_eq!(phc.parameters.len(), 1);
         match phc.parameters.get("i") {
             Some(v) => assert_eq!(v, "42"),
-            None => assert!(false),
+            None => panic!(),
         }
         match phc.salt {
             Some(p) => assert_eq!(p, vec![0x61, 0x73, 0x75, 0x72, 0x65, 0x2e]),
-            None => assert!(false),
+            None => panic!(),
         };
         assert_eq!(phc.hash, None);
     }
@@ -252,11 +436,11 @@ mod tests {
         assert_eq!(phc.parameters.len(), 1);
         match phc.parameters.get("i") {
             Some(v) => assert_eq!(v, "42"),
-            None => assert!(false),
+            None => panic!(),
         }
         match phc.salt {
             Some(p) => assert_eq!(p, vec![0x61, 0x73, 0x75, 0x72, 0x65, 0x2e]),
-            None => assert!(false),
+            None => panic!(),
         };
         match phc.hash {
             Some(p) => assert_eq!(
@@ -266,7 +450,7 @@ mod tests {
                     0x65, 0x61, 0x73, 0x75, 0x72,
                 ]
             ),
-            None => assert!(false),
+            None => panic!(),
         };
     }
 
@@ -278,15 +462,15 @@ mod tests {
         assert_eq!(phc.parameters.len(), 3);
         match phc.parameters.get("i") {
             Some(v) => assert_eq!(v, "42"),
-            None => assert!(false),
+            None => panic!(),
         }
         match phc.parameters.get("plop") {
             Some(v) => assert_eq!(v, "asdfg"),
-            None => assert!(false),
+            None => panic!(),
         }
         match phc.parameters.get("21") {
             Some(v) => assert_eq!(v, "abcd12efg"),
-            None => assert!(false),
+            None => panic!(),
         }
     }
 
@@ -313,4 +497,54 @@ mod tests {
             assert!(phc.is_err());
         }
     }
+
+    #[test]
+    fn test_max_len() {
+        let data = "$pbkdf2$i=21000$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+        assert!(PHCData::from_str_max_len(data, data.len()).is_ok());
+        assert!(PHCData::from_str_max_len(data, data.len() - 1).is_err());
+
+        let data = format!("$pbkdf2$i=21000$RSF4Aw${}", "A".repeat(8 * 1024 * 1024));
+        assert!(PHCData::from_str(&data).is_err());
+        assert!(PHCData::from_str_max_len(&data, data.len()).is_ok());
+    }
+
+    #[test]
+    fn test_prefix() {
+        let data = [
+            "$custom$pbkdf2$i=21000$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+            "$a$b-2$pbkdf2$i=21000$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+        ];
+        for s in data.iter() {
+            assert!(PHCData::from_str(s).is_err());
+            assert!(PHCData::from_str_strict(s).is_err());
+            let phc = PHCData::from_str_prefixed(s).unwrap();
+            assert_eq!(phc.id, "pbkdf2");
+            assert!(phc.prefix.is_some());
+            assert_eq!(phc.to_string().unwrap(), s.to_string());
+        }
+
+        let phc = PHCData::from_str_prefixed("$pbkdf2$i=21000$RSF4Aw").unwrap();
+        assert!(phc.prefix.is_none());
+
+        let data = [
+            "pbkdf2$i=21000$RSF4Aw",
+            "$$pbkdf2$i=21000$RSF4Aw",
+            "$a_b$pbkdf2$i=21000",
+        ];
+        for s in data.iter() {
+            assert!(PHCData::from_str_prefixed(s).is_err());
+        }
+    }
+
+    #[test]
+    fn test_prefix_limits() {
+        let suffix = "$pbkdf2$i=21000$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+        let prefix = "$a".repeat(super::MAX_PREFIX_SEGMENTS);
+        assert!(PHCData::from_str_prefixed(&format!("{}{}", prefix, suffix)).is_ok());
+        assert!(PHCData::from_str_prefixed(&format!("$a{}{}", prefix, suffix)).is_err());
+
+        let padding = "$a".repeat(super::DEFAULT_MAX_LEN / 2);
+        assert!(PHCData::from_str_prefixed(&format!("{}{}", padding, suffix)).is_err());
+    }
 }
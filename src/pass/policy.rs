@@ -0,0 +1,130 @@
+use super::{Algorithm, Hasher, Normalization};
+
+/// Minimal requirements a password hash should satisfy, for example in order to decide whether
+/// or not a stored hash should be replaced by a new one.
+///
+/// Each requirement is optional and only applies to the algorithm it is relevant to: a
+/// requirement on the memory does not apply to PBKDF2. Use `algorithm` in order to require a
+/// specific algorithm.
+///
+/// When the `serde` feature is enabled, a policy can be deserialized, which allows to share it
+/// between several services. Unknown fields are rejected.
+///
+/// ## Examples
+/// ```
+/// use libreauth::pass::{Algorithm, HashBuilder, Policy};
+///
+/// let policy = Policy {
+///     algorithm: Some(Algorithm::Pbkdf2),
+///     min_iterations: Some(50_000),
+///     ..Policy::default()
+/// };
+/// let hasher = HashBuilder::new()
+///     .algorithm(Algorithm::Pbkdf2)
+///     .add_param("iter", "45000")
+///     .finalize()
+///     .unwrap();
+/// assert!(!hasher.satisfies(&policy));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct Policy {
+    /// Required algorithm.
+    pub algorithm: Option<Algorithm>,
+    /// Minimal memory, in KiB. Only relevant for Argon2.
+    pub min_memory_kib: Option<u64>,
+    /// Minimal number of passes over the memory. Only relevant for Argon2.
+    pub min_passes: Option<u32>,
+    /// Minimal number of iterations. Only relevant for PBKDF2.
+    pub min_iterations: Option<u32>,
+    /// Current version of the hashing scheme, as in [needs_update](Hasher::needs_update).
+    pub version: Option<usize>,
+    /// Required normalization.
+    pub normalization: Option<Normalization>,
+}
+
+/// Reasons why a password should be hashed again, as reported by
+/// [needs_update_full](Hasher::needs_update_full).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UpdateReasons {
+    /// The version of the hashing scheme is older than the current one.
+    pub version_stale: bool,
+    /// The algorithm is not the required one.
+    pub algorithm_outdated: bool,
+    /// The cost is below the policy's minimal requirements, or cannot be determined.
+    pub cost_below_policy: bool,
+    /// The normalization is not the required one.
+    pub normalization_outdated: bool,
+}
+
+impl UpdateReasons {
+    /// Check whether or not at least one reason has been reported.
+    pub fn any(&self) -> bool {
+        self.version_stale
+            || self.algorithm_outdated
+            || self.cost_below_policy
+            || self.normalization_outdated
+    }
+}
+
+fn at_least<T: PartialOrd>(value: Option<T>, min: Option<T>) -> bool {
+    match (value, min) {
+        (Some(v), Some(m)) => v >= m,
+        _ => true,
+    }
+}
+
+fn differs<T: PartialEq>(value: T, required: Option<T>) -> bool {
+    match required {
+        Some(r) => value != r,
+        None => false,
+    }
+}
+
+impl Hasher {
+    /// Check whether or not the hashing scheme satisfies the specified policy.
+    pub fn satisfies(&self, policy: &Policy) -> bool {
+        !self.needs_update_full(policy).any()
+    }
+
+    /// Check whether or not the password should be hashed again according to the specified
+    /// policy and report every reason why. This combines [needs_update](Hasher::needs_update),
+    /// which is only checked if the policy sets a version, with the policy's requirements on the
+    /// algorithm, the cost and the normalization.
+    ///
+    /// ## Examples
+    /// ```
+    /// use libreauth::pass::{Algorithm, HashBuilder, Normalization, Policy};
+    ///
+    /// let policy = Policy {
+    ///     algorithm: Some(Algorithm::Argon2),
+    ///     normalization: Some(Normalization::Nfkc),
+    ///     ..Policy::default()
+    /// };
+    /// let hasher = HashBuilder::new()
+    ///     .algorithm(Algorithm::Pbkdf2)
+    ///     .normalization(Normalization::Nfkc)
+    ///     .finalize()
+    ///     .unwrap();
+    /// let reasons = hasher.needs_update_full(&policy);
+    /// assert!(reasons.algorithm_outdated);
+    /// assert!(!reasons.normalization_outdated);
+    /// ```
+    pub fn needs_update_full(&self, policy: &Policy) -> UpdateReasons {
+        let cost_satisfied = match self.cost_summary() {
+            Ok(cost) => {
+                at_least(cost.memory_kib, policy.min_memory_kib)
+                    && at_least(cost.passes, policy.min_passes)
+                    && at_least(cost.iterations, policy.min_iterations)
+            }
+            Err(_) => false,
+        };
+        UpdateReasons {
+            version_stale: policy.version.map_or(false, |v| self.needs_update(Some(v))),
+            algorithm_outdated: differs(self.algorithm, policy.algorithm),
+            cost_below_policy: !cost_satisfied,
+            normalization_outdated: differs(self.normalization, policy.normalization),
+        }
+    }
+}
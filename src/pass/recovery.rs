@@ -0,0 +1,92 @@
+use super::{Algorithm, ErrorCode, HashBuilder};
+use crate::key::KeyBuilder;
+use std::collections::HashSet;
+
+const MIN_RECOVERY_CODE_ENTROPY: usize = 64; // in bits
+const MAX_RECOVERY_CODE_ENTROPY: usize = 256; // in bits
+const RECOVERY_CODE_ITER: &str = "10000";
+
+/// Generate distinct one-time recovery codes, also known as backup codes, and hash them.
+///
+/// Each item is a `(plaintext, hash)` pair: the plaintext code is meant to be shown once to the
+/// user and the hash to be stored. The codes are base32 encoded (uppercase letters and digits
+/// from 2 to 7) and contain at least `entropy_bits` bits of entropy, which must be between 64
+/// and 256. A code can be checked using [HashBuilder::from_phc](crate::pass::HashBuilder::from_phc);
+/// you may want to convert the user input to uppercase beforehand.
+///
+/// Because those codes are random and have a high entropy, they do not require a costly key
+/// derivation in order to resist a brute-force attack: they are therefore hashed using PBKDF2
+/// with a low iteration count instead of the default Argon2 configuration. This is not suitable
+/// for passwords chosen by humans.
+///
+/// ## Examples
+/// ```
+/// use libreauth::pass::{generate_recovery_codes, HashBuilder};
+///
+/// let codes = generate_recovery_codes(10, 80).unwrap();
+/// let (code, stored_hash) = &codes[0];
+/// let checker = HashBuilder::from_phc(stored_hash).unwrap();
+/// assert!(checker.is_valid(code));
+/// ```
+pub fn generate_recovery_codes(
+    count: usize,
+    entropy_bits: usize,
+) -> Result<Vec<(String, String)>, ErrorCode> {
+    if entropy_bits < MIN_RECOVERY_CODE_ENTROPY {
+        return Err(ErrorCode::PasswordTooShort);
+    }
+    if entropy_bits > MAX_RECOVERY_CODE_ENTROPY {
+        return Err(ErrorCode::PasswordTooLong);
+    }
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .add_param("iter", RECOVERY_CODE_ITER)
+        .finalize()?;
+    let size = (entropy_bits + 7) / 8;
+    let mut seen = HashSet::with_capacity(count);
+    let mut codes = Vec::with_capacity(count);
+    while codes.len() < count {
+        let code = KeyBuilder::new().size(size).as_base32();
+        if seen.insert(code.clone()) {
+            let hash = hasher.hash(&code)?;
+            codes.push((code, hash));
+        }
+    }
+    Ok(codes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = generate_recovery_codes(8, 64).unwrap();
+        assert_eq!(codes.len(), 8);
+        let plaintexts: HashSet<&String> = codes.iter().map(|(c, _)| c).collect();
+        assert_eq!(plaintexts.len(), 8);
+        for (code, hash) in codes.iter() {
+            assert_eq!(code.len(), 13);
+            assert!(hash.starts_with("$pbkdf2$"));
+            let checker = HashBuilder::from_phc(hash).unwrap();
+            assert!(checker.is_valid(code));
+            assert!(!checker.is_valid(&code.to_lowercase()));
+        }
+        let checker = HashBuilder::from_phc(&codes[0].1).unwrap();
+        assert!(!checker.is_valid(&codes[1].0));
+    }
+
+    #[test]
+    fn test_recovery_codes_entropy() {
+        assert!(generate_recovery_codes(0, 64).unwrap().is_empty());
+        assert_eq!(generate_recovery_codes(1, 256).unwrap()[0].0.len(), 52);
+        assert!(matches!(
+            generate_recovery_codes(1, 63),
+            Err(ErrorCode::PasswordTooShort)
+        ));
+        assert!(matches!(
+            generate_recovery_codes(1, 257),
+            Err(ErrorCode::PasswordTooLong)
+        ));
+    }
+}
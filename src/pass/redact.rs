@@ -0,0 +1,73 @@
+use super::phc::PHCData;
+
+const REDACTED: &str = "****";
+
+/// Return a form of a stored password hash which can be shared, for example in a bug report,
+/// without exposing the salt nor the hash.
+///
+/// The identifier, the version and the parameters are kept, the parameters being sorted by name.
+/// The salt is replaced by `****` followed by its length in bytes and the hash by `****`. A
+/// string which cannot be parsed is entirely replaced by `****`.
+///
+/// ## Examples
+/// ```
+/// use libreauth::pass::redact;
+///
+/// let stored_hash = "$pbkdf2$iter=21000,hmac=sha256$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+/// assert_eq!(redact(stored_hash), "$pbkdf2$hmac=sha256,iter=21000$****(4 bytes)$****");
+/// ```
+pub fn redact(phc: &str) -> String {
+    let data = match PHCData::from_str(phc) {
+        Ok(data) => data,
+        Err(_) => return REDACTED.to_string(),
+    };
+    let mut res = format!("${}", data.id);
+    if let Some(v) = data.version {
+        res += &format!("$v={}", v);
+    }
+    let mut parameters: Vec<(&String, &String)> = data.parameters.iter().collect();
+    parameters.sort();
+    let parameters: Vec<String> = parameters
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect();
+    if !parameters.is_empty() || data.salt.is_some() {
+        res += "$";
+        res += &parameters.join(",");
+    }
+    if let Some(salt) = data.salt {
+        res += &format!("${}({} bytes)", REDACTED, salt.len());
+        if data.hash.is_some() {
+            res += "$";
+            res += REDACTED;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let salt = "c29tZXNhbHQ";
+        let hash = "CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc";
+        let phc = format!("$argon2id$v=19$m=65536,t=2,p=1${}${}", salt, hash);
+        let redacted = redact(&phc);
+        assert_eq!(
+            redacted,
+            "$argon2id$v=19$m=65536,p=1,t=2$****(8 bytes)$****"
+        );
+        assert!(!redacted.contains(salt));
+        assert!(!redacted.contains(hash));
+    }
+
+    #[test]
+    fn test_redact_partial() {
+        assert_eq!(redact("$pbkdf2"), "$pbkdf2");
+        assert_eq!(redact("$pbkdf2$iter=21000"), "$pbkdf2$iter=21000");
+        assert_eq!(redact("$pbkdf2$$RSF4Aw"), "$pbkdf2$$****(4 bytes)");
+        assert_eq!(redact("not a hash"), "****");
+    }
+}
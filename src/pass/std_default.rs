@@ -8,3 +8,4 @@ pub const DEFAULT_ALGORITHM: Algorithm = Algorithm::Argon2;
 pub const DEFAULT_LENGTH_CALCULATION: LengthCalculationMethod = LengthCalculationMethod::Characters;
 pub const DEFAULT_SALT_LEN: usize = 16; // In bytes
 pub const DEFAULT_XHMAC_ALGORITHM: HashFunction = HashFunction::Sha512;
+pub const DEFAULT_COMPARISON_HASH: HashFunction = HashFunction::Sha512;
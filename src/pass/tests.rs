@@ -1,8 +1,11 @@
 use super::{
-    std_default, std_nist, Algorithm, HashBuilder, LengthCalculationMethod, Normalization,
-    PasswordStorageStandard, DEFAULT_USER_VERSION, INTERNAL_VERSION, XHMAC,
+    std_default, std_nist, Algorithm, ErrorCode, HashBuilder, LengthCalculationMethod,
+    Normalization, PasswordPolicyError, PasswordStorageStandard, Policy, SegmentLayout,
+    UpdateReasons, Warning, DEFAULT_USER_VERSION, INTERNAL_VERSION, PASSWORD_STORAGE_LEN, XHMAC,
 };
 use crate::hash::HashFunction;
+use crate::pass::phc::PHCData;
+use unicode_normalization::UnicodeNormalization;
 
 #[test]
 fn test_default_hashbuilder() {
@@ -13,18 +16,9 @@ fn test_default_hashbuilder() {
     assert_eq!(hb.ref_salt, None);
     assert_eq!(hb.ref_hash, None);
     assert_eq!(hb.xhmac, XHMAC::None);
-    match hb.standard {
-        PasswordStorageStandard::NoStandard => assert!(true),
-        _ => assert!(false),
-    }
-    match hb.normalization {
-        Normalization::Nfkc => assert!(true),
-        _ => assert!(false),
-    }
-    match hb.algorithm {
-        Algorithm::Argon2 => assert!(true),
-        _ => assert!(false),
-    }
+    assert!(matches!(hb.standard, PasswordStorageStandard::NoStandard));
+    assert!(matches!(hb.normalization, Normalization::Nfkc));
+    assert!(matches!(hb.algorithm, Algorithm::Argon2));
 }
 
 #[test]
@@ -36,22 +30,13 @@ fn test_nist_hashbuilder() {
     assert_eq!(hb.ref_salt, None);
     assert_eq!(hb.ref_hash, None);
     assert_eq!(hb.xhmac, XHMAC::None);
-    match hb.length_calculation {
-        std_nist::DEFAULT_LENGTH_CALCULATION => assert!(true),
-        _ => assert!(false),
-    };
-    match hb.standard {
-        PasswordStorageStandard::Nist80063b => assert!(true),
-        _ => assert!(false),
-    }
-    match hb.normalization {
-        Normalization::Nfkc => assert!(true),
-        _ => assert!(false),
-    }
-    match hb.algorithm {
-        Algorithm::Pbkdf2 => assert!(true),
-        _ => assert!(false),
-    }
+    assert!(matches!(
+        hb.length_calculation,
+        std_nist::DEFAULT_LENGTH_CALCULATION
+    ));
+    assert!(matches!(hb.standard, PasswordStorageStandard::Nist80063b));
+    assert!(matches!(hb.normalization, Normalization::Nfkc));
+    assert!(matches!(hb.algorithm, Algorithm::Pbkdf2));
 }
 
 #[test]
@@ -72,35 +57,26 @@ fn test_params() {
     assert_eq!(hb.ref_hash, None);
     assert_eq!(hb.version, 5 + INTERNAL_VERSION);
     assert_eq!(hb.xhmac, XHMAC::None);
-    match hb.length_calculation {
-        LengthCalculationMethod::Characters => assert!(true),
-        _ => assert!(false),
-    };
-    match hb.standard {
-        PasswordStorageStandard::Nist80063b => assert!(true),
-        _ => assert!(false),
-    }
-    match hb.normalization {
-        Normalization::Nfkd => assert!(true),
-        _ => assert!(false),
-    }
-    match hb.algorithm {
-        Algorithm::Pbkdf2 => assert!(true),
-        _ => assert!(false),
-    }
+    assert!(matches!(
+        hb.length_calculation,
+        LengthCalculationMethod::Characters
+    ));
+    assert!(matches!(hb.standard, PasswordStorageStandard::Nist80063b));
+    assert!(matches!(hb.normalization, Normalization::Nfkd));
+    assert!(matches!(hb.algorithm, Algorithm::Pbkdf2));
     match hb.parameters.get("hmac") {
         Some(h) => match h.as_str() {
-            "sha512t256" => assert!(true),
-            v => assert!(false, "{} invalid hmac parameter value", v),
+            "sha512t256" => {}
+            v => panic!("{} invalid hmac parameter value", v),
         },
-        None => assert!(false, "hmac: parameter not found"),
+        None => panic!("hmac: parameter not found"),
     }
     match hb.parameters.get("iter") {
         Some(i) => match i.as_str() {
-            "80000" => assert!(true),
-            v => assert!(false, "{} invalid iter parameter value", v),
+            "80000" => {}
+            v => panic!("{} invalid iter parameter value", v),
         },
-        None => assert!(false, "iter: parameter not found"),
+        None => panic!("iter: parameter not found"),
     }
 }
 
@@ -367,3 +343,2073 @@ fn test_xhmac_no_salt_create() {
     let checker = HashBuilder::from_phc_xhmac(hpass.as_str(), extra_salt).unwrap();
     assert!(!checker.is_valid(password));
 }
+
+#[test]
+fn test_inline_salt_from_phc() {
+    use super::inline_salt::InlineSaltHash;
+
+    let password = "correct horse battery staple";
+    let mut builder = HashBuilder::new();
+    builder.algorithm(Algorithm::Pbkdf2);
+    builder.hash_func_hook = Some(InlineSaltHash::new_boxed);
+    let stored = builder.finalize().unwrap().hash(password).unwrap();
+    assert!(stored.starts_with("$inline$"));
+
+    // As from_phc does, the single trailing segment is loaded as the salt.
+    let phc = PHCData::from_str(&stored).unwrap();
+    assert!(phc.hash.is_none());
+    builder.ref_salt = phc.salt;
+    let checker = builder.finalize().unwrap();
+    assert_eq!(checker.salt(), Some(&b"somesalt"[..]));
+    assert!(checker.is_valid(password));
+    assert!(!checker.is_valid("bad password"));
+    assert_eq!(checker.hash(password).unwrap(), stored);
+}
+
+#[test]
+fn test_verify_reporting() {
+    let password = "correct horse battery staple";
+    for (algorithm, id) in [
+        (Algorithm::Argon2, "$argon2$"),
+        (Algorithm::Pbkdf2, "$pbkdf2$"),
+    ] {
+        let hasher = HashBuilder::new().algorithm(algorithm).finalize().unwrap();
+        let hpass = hasher.hash(password).unwrap();
+        assert!(hpass.starts_with(id));
+        let checker = HashBuilder::from_phc(hpass.as_str()).unwrap();
+        assert_eq!(checker.verify_reporting(password), (true, algorithm));
+        assert_eq!(checker.verify_reporting("bad password"), (false, algorithm));
+    }
+}
+
+#[test]
+fn test_assume_normalized() {
+    let password = String::from_utf8(vec![
+        116, 101, 115, 116, 32, 110, 102, 107, 100, 32, 97, 204, 136, 32, 80, 32, 32, 204, 136, 97,
+    ])
+    .unwrap(); // "test nfkd ä P  ̈a"
+    let normalized = password.nfkc().collect::<String>();
+    let hasher = HashBuilder::new()
+        .normalization(Normalization::Nfkc)
+        .assume_normalized(true)
+        .finalize()
+        .unwrap();
+    let stored_password = hasher.hash(&normalized).unwrap();
+    assert!(stored_password.contains("norm=nfkc"));
+    let checker = HashBuilder::from_phc(stored_password.as_str()).unwrap();
+    assert!(checker.is_valid(&normalized));
+    assert!(checker.is_valid(&password));
+
+    let stored_password = hasher.hash(&password).unwrap();
+    let checker = HashBuilder::from_phc(stored_password.as_str()).unwrap();
+    assert!(!checker.is_valid(&password));
+}
+
+#[test]
+fn test_cost_summary_argon2() {
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Argon2)
+        .add_param("mem", "14")
+        .add_param("passes", "4")
+        .finalize()
+        .unwrap();
+    let summary = hasher.cost_summary().unwrap();
+    assert_eq!(summary.algorithm, Algorithm::Argon2);
+    assert_eq!(summary.memory_kib, Some(16384));
+    assert_eq!(summary.passes, Some(4));
+    assert_eq!(summary.iterations, None);
+    assert_eq!(summary.work_factor, 65536);
+
+    let summary = HashBuilder::new()
+        .finalize()
+        .unwrap()
+        .cost_summary()
+        .unwrap();
+    assert_eq!(summary.memory_kib, Some(4096));
+    assert_eq!(summary.passes, Some(3));
+}
+
+#[test]
+fn test_cost_summary_pbkdf2() {
+    let data = "$pbkdf2$iter=80000,hmac=sha256,len-calc=chars,pmax=128,pmin=8,norm=nfkc$RSF4Aw$xvdfA4H7QJQ1w/4jGcjBEIjCvsc";
+    let summary = HashBuilder::from_phc(data).unwrap().cost_summary().unwrap();
+    assert_eq!(summary.algorithm, Algorithm::Pbkdf2);
+    assert_eq!(summary.memory_kib, None);
+    assert_eq!(summary.passes, None);
+    assert_eq!(summary.iterations, Some(80000));
+    assert_eq!(summary.work_factor, 160000);
+}
+
+#[test]
+fn test_from_phc_strict() {
+    let password = "correct horse battery staple";
+    let hpass = HashBuilder::new()
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(HashBuilder::from_phc_strict(&hpass)
+        .unwrap()
+        .is_valid(password));
+    assert!(HashBuilder::from_phc_strict(&format!("{}$", hpass)).is_err());
+    let mut parts: Vec<&str> = hpass.split('$').collect();
+    let params = format!("{},", parts[2]);
+    parts[2] = &params;
+    let non_canonical = parts.join("$");
+    assert!(HashBuilder::from_phc(&non_canonical).is_ok());
+    assert!(HashBuilder::from_phc_strict(&non_canonical).is_err());
+}
+
+#[test]
+fn test_pbkdf2_accessors() {
+    let data = "$pbkdf2$iter=80000,hmac=sha256,len-calc=chars,pmax=128,pmin=8,norm=nfkc$RSF4Aw$xvdfA4H7QJQ1w/4jGcjBEIjCvsc";
+    let hasher = HashBuilder::from_phc(data).unwrap();
+    assert_eq!(hasher.pbkdf2_iterations(), Some(80_000));
+    assert_eq!(hasher.pbkdf2_hash(), Some("sha256"));
+
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .finalize()
+        .unwrap();
+    assert_eq!(hasher.pbkdf2_iterations(), Some(45_000));
+    assert_eq!(hasher.pbkdf2_hash(), Some("sha512"));
+
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Argon2)
+        .finalize()
+        .unwrap();
+    assert_eq!(hasher.pbkdf2_iterations(), None);
+    assert_eq!(hasher.pbkdf2_hash(), None);
+}
+
+#[test]
+fn test_hasher_getters() {
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .add_param("iter", "12000")
+        .min_len(10)
+        .max_len(64)
+        .version(5)
+        .normalization(Normalization::Nfd)
+        .length_calculation(LengthCalculationMethod::Bytes)
+        .finalize()
+        .unwrap();
+    let stored = hasher.hash("correct horse battery staple").unwrap();
+    let checker = HashBuilder::from_phc(&stored).unwrap();
+    for h in [&hasher, &checker] {
+        assert_eq!(h.algorithm(), Algorithm::Pbkdf2);
+        assert_eq!(
+            h.parameters().get("iter").map(String::as_str),
+            Some("12000")
+        );
+        assert_eq!(h.min_len(), 10);
+        assert_eq!(h.max_len(), 64);
+        assert_eq!(h.version(), 5);
+        assert_eq!(h.normalization(), Normalization::Nfd);
+        assert_eq!(h.length_calculation(), LengthCalculationMethod::Bytes);
+    }
+
+    let hasher = HashBuilder::new().finalize().unwrap();
+    assert_eq!(hasher.algorithm(), Algorithm::Argon2);
+    assert!(hasher.parameters().is_empty());
+    assert_eq!(hasher.version(), 0);
+    assert_eq!(hasher.normalization(), Normalization::Nfkc);
+}
+
+#[test]
+fn test_accept_versions() {
+    let password = "correct horse battery staple";
+    let hasher = HashBuilder::new()
+        .version(1)
+        .accept_versions(1..=2)
+        .finalize()
+        .unwrap();
+    assert!(hasher.is_version_accepted());
+    let stored = hasher.hash(password).unwrap();
+
+    // The version does not change the derivation: an old version still verifies but is flagged.
+    let checker = HashBuilder::from_phc(&stored).unwrap();
+    assert!(checker.is_valid(password));
+    assert!(checker.is_version_accepted());
+    assert!(checker.needs_update(Some(2)));
+    assert!(checker.needs_update(Some(5)));
+    assert!(!checker.needs_update(Some(1)));
+
+    let hasher = HashBuilder::new()
+        .version(5)
+        .accept_versions(1..=2)
+        .finalize()
+        .unwrap();
+    assert!(!hasher.is_version_accepted());
+}
+
+#[test]
+fn test_derive_raw() {
+    let password = "correct horse battery staple";
+    for algo in [Algorithm::Argon2, Algorithm::Pbkdf2] {
+        let hasher = HashBuilder::new().algorithm(algo).finalize().unwrap();
+        let stored = hasher.hash(password).unwrap();
+        let checker = HashBuilder::from_phc(&stored).unwrap();
+        let salt = checker.ref_salt.clone().unwrap();
+        let raw = hasher.derive_raw(password, &salt).unwrap();
+        assert_eq!(Some(raw), checker.ref_hash);
+        assert!(matches!(
+            hasher.derive_raw("short", &salt),
+            Err(ErrorCode::PasswordTooShort)
+        ));
+        assert!(hasher.derive_raw(password, &[]).is_err());
+    }
+}
+
+#[test]
+fn test_normalization_str() {
+    let hasher = HashBuilder::new()
+        .normalization_str("nfkd")
+        .finalize()
+        .unwrap();
+    assert!(matches!(hasher.normalization, Normalization::Nfkd));
+    let stored = hasher.hash("correct horse battery staple").unwrap();
+    assert!(stored.contains("norm=nfkd"));
+
+    assert!(matches!(
+        HashBuilder::new().normalization_str("nfkx").finalize(),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+}
+
+#[test]
+fn test_length_graphemes() {
+    let precomposed = "\u{e9}";
+    let decomposed = "e\u{301}";
+    for (norm, password) in [
+        (Normalization::None, precomposed),
+        (Normalization::None, decomposed),
+        (Normalization::Nfc, decomposed),
+        (Normalization::Nfd, precomposed),
+    ] {
+        let hasher = HashBuilder::new()
+            .normalization(norm)
+            .length_calculation(LengthCalculationMethod::Graphemes)
+            .min_len(1)
+            .max_len(1)
+            .finalize()
+            .unwrap();
+        let stored = hasher.hash(password).unwrap();
+        assert!(stored.contains("len-calc=graphemes"));
+        let checker = HashBuilder::from_phc(&stored).unwrap();
+        assert_eq!(
+            checker.length_calculation,
+            LengthCalculationMethod::Graphemes
+        );
+        assert!(checker.is_valid(password));
+    }
+
+    let hasher = HashBuilder::new()
+        .normalization(Normalization::None)
+        .length_calculation(LengthCalculationMethod::Characters)
+        .min_len(1)
+        .max_len(1)
+        .finalize()
+        .unwrap();
+    assert!(hasher.hash(precomposed).is_ok());
+    assert!(matches!(
+        hasher.hash(decomposed),
+        Err(ErrorCode::PasswordTooLong)
+    ));
+}
+
+#[test]
+fn test_algorithm_not_available() {
+    let data = [
+        "$scrypt$ln=16,r=8,p=1$aM15713r3Xsvxbi31lqr1Q$nFNh2CVHVjNldFVKDHDlm4CbdRSCdEBsjjJxD+iCs5E",
+        "$2b$12$GhvMmNVjRW29ulnudl.LbuAnUtN/LRfe1JsBm1Xu6LE3059z5Tr8m",
+    ];
+    for phc in data.iter() {
+        assert!(matches!(
+            HashBuilder::from_phc(phc),
+            Err(ErrorCode::AlgorithmNotAvailable)
+        ));
+    }
+    assert!(matches!(
+        HashBuilder::from_phc("$unknown$i=42$YXN1cmUu$YW55IGNhcm5hbCBwbGVhc3Vy"),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+}
+
+#[test]
+fn test_param_mac() {
+    let password = "correct horse battery staple";
+    let secret = b"server-side secret";
+    let stored = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .add_param("iter", "80000")
+        .param_mac(secret)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(stored.contains("pmac="));
+    let checker = HashBuilder::from_phc_param_mac(&stored, secret).unwrap();
+    assert!(checker.is_valid(password));
+
+    // The secret is required.
+    assert!(HashBuilder::from_phc(&stored).is_err());
+    assert!(HashBuilder::from_phc_param_mac(&stored, b"wrong secret").is_err());
+
+    // Weakened parameters.
+    let tampered = stored.replace("iter=80000", "iter=10000");
+    assert_ne!(tampered, stored);
+    assert!(matches!(
+        HashBuilder::from_phc_param_mac(&tampered, secret),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+
+    // Removed tag.
+    let unauthenticated = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(HashBuilder::from_phc_param_mac(&unauthenticated, secret).is_err());
+}
+
+#[test]
+fn test_soft_max_len() {
+    let hasher = HashBuilder::new()
+        .soft_max_len(16)
+        .max_len(32)
+        .finalize()
+        .unwrap();
+
+    let (res, warnings) = hasher.hash_checked("correct horse");
+    assert!(res.is_ok());
+    assert!(warnings.is_empty());
+
+    let password = "correct horse battery staple";
+    let (res, warnings) = hasher.hash_checked(password);
+    assert!(HashBuilder::from_phc(&res.unwrap())
+        .unwrap()
+        .is_valid(password));
+    assert_eq!(warnings, vec![Warning::ExceedsSoftMax]);
+
+    let (res, warnings) = hasher.hash_checked("correct horse battery staple and more");
+    assert!(matches!(res, Err(ErrorCode::PasswordTooLong)));
+    assert_eq!(warnings, vec![Warning::ExceedsSoftMax]);
+}
+
+#[test]
+fn test_from_phc_prefixed() {
+    let password = "correct horse battery staple";
+    let stored = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    let prefixed = format!("$family{}", stored);
+    assert!(HashBuilder::from_phc(&prefixed).is_err());
+    let checker = HashBuilder::from_phc_prefixed(&prefixed).unwrap();
+    assert!(checker.is_valid(password));
+    assert!(checker
+        .hash(password)
+        .unwrap()
+        .starts_with("$family$pbkdf2$"));
+
+    let oversized = format!("{}{}", "$family".repeat(600), stored);
+    assert!(HashBuilder::from_phc_prefixed(&oversized).is_err());
+    let nested = format!("{}{}", "$family".repeat(5), stored);
+    assert!(HashBuilder::from_phc_prefixed(&nested).is_err());
+}
+
+#[test]
+fn test_comparison_hash() {
+    let password = "correct horse battery staple";
+    let hasher = HashBuilder::new()
+        .comparison_hash(HashFunction::Sha256)
+        .finalize()
+        .unwrap();
+    assert!(matches!(hasher.comparison_hash, HashFunction::Sha256));
+    let stored = hasher.hash(password).unwrap();
+    let mut checker = HashBuilder::from_phc(&stored).unwrap();
+    assert!(matches!(checker.comparison_hash, HashFunction::Sha512));
+    for func in [
+        HashFunction::Sha512,
+        HashFunction::Sha256,
+        HashFunction::Sha3_256,
+        HashFunction::Blake2b,
+    ] {
+        checker.comparison_hash = func;
+        assert!(checker.is_valid(password));
+        assert!(!checker.is_valid("bad password"));
+    }
+}
+
+#[test]
+fn test_argon2_secret() {
+    let password = "correct horse battery staple";
+    let key = b"argon2 secret key";
+    let stored = HashBuilder::new()
+        .argon2_secret(key)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(stored.contains("secret=1"));
+    assert!(!stored.contains("argon2 secret key"));
+    assert!(matches!(
+        HashBuilder::from_phc(&stored),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+    let checker = HashBuilder::from_phc_argon2_secret(&stored, key).unwrap();
+    assert!(checker.is_valid(password));
+    assert!(!checker.is_valid("bad password"));
+    let checker = HashBuilder::from_phc_argon2_secret(&stored, b"other secret key").unwrap();
+    assert!(!checker.is_valid(password));
+
+    let stored = HashBuilder::new()
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(HashBuilder::from_phc_argon2_secret(&stored, key).is_err());
+}
+
+#[test]
+fn test_argon2_secret_pbkdf2() {
+    assert!(matches!(
+        HashBuilder::new()
+            .algorithm(Algorithm::Pbkdf2)
+            .argon2_secret(b"argon2 secret key")
+            .finalize(),
+        Err(ErrorCode::IncompatibleOption)
+    ));
+}
+
+#[test]
+fn test_from_phc_param_range() {
+    let base = "$pbkdf2$iter=80000,hmac=sha256,len-calc=chars,norm=nfkc";
+    let end = "$RSF4Aw$xvdfA4H7QJQ1w/4jGcjBEIjCvsc";
+    assert!(HashBuilder::from_phc(&format!("{},pmin=8,pmax=128,ver=1{}", base, end)).is_ok());
+    for params in [
+        "pmin=8,pmax=18446744073709551615",
+        "pmin=8,pmax=1000000",
+        "pmin=42,pmax=12",
+        "pmin=8,pmax=128,ver=0",
+        "pmin=8,pmax=128,ver=18446744073709551615",
+    ] {
+        assert!(matches!(
+            HashBuilder::from_phc(&format!("{},{}{}", base, params, end)),
+            Err(ErrorCode::InvalidPasswordFormat)
+        ));
+    }
+}
+
+#[test]
+fn test_rebless() {
+    let password = "correct horse battery staple";
+    let stored = HashBuilder::new()
+        .version(3)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    let checker = HashBuilder::from_phc(&stored).unwrap();
+    let reblessed = checker.rebless(4).unwrap();
+    let checker = HashBuilder::from_phc(&reblessed).unwrap();
+    assert!(checker.is_valid(password));
+    assert!(!checker.needs_update(Some(4)));
+
+    let mut original = PHCData::from_str(&stored).unwrap();
+    let mut new = PHCData::from_str(&reblessed).unwrap();
+    assert_eq!(original.parameters.remove("ver").unwrap(), "4");
+    assert_eq!(new.parameters.remove("ver").unwrap(), "5");
+    assert_eq!(original.id, new.id);
+    assert_eq!(original.parameters, new.parameters);
+    assert_eq!(original.salt, new.salt);
+    assert_eq!(original.hash, new.hash);
+
+    // The creation time is kept.
+    let stored = HashBuilder::new()
+        .version(3)
+        .timestamp_hashes(true)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    let ts = PHCData::from_str(&stored).unwrap().parameters["ts"].clone();
+    let stored = stored.replace(&format!("ts={}", ts), "ts=946684800");
+    let reblessed = HashBuilder::from_phc(&stored).unwrap().rebless(4).unwrap();
+    let checker = HashBuilder::from_phc(&reblessed).unwrap();
+    assert!(checker.is_valid(password));
+    assert_eq!(checker.created_at(), Some(946_684_800));
+    let mut original = PHCData::from_str(&stored).unwrap();
+    let mut new = PHCData::from_str(&reblessed).unwrap();
+    assert_eq!(original.parameters.remove("ver").unwrap(), "4");
+    assert_eq!(new.parameters.remove("ver").unwrap(), "5");
+    assert_eq!(original.parameters, new.parameters);
+
+    let hasher = HashBuilder::new().finalize().unwrap();
+    assert!(matches!(
+        hasher.rebless(4),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+}
+
+#[test]
+fn test_on_timing() {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    let timings = Arc::new(Mutex::new(Vec::new()));
+    let t = timings.clone();
+    let hasher = HashBuilder::new()
+        .on_timing(move |d| t.lock().unwrap().push(d))
+        .finalize()
+        .unwrap();
+    assert!(hasher.hash("correct horse battery staple").is_ok());
+    assert!(hasher.hash("short").is_err());
+    let timings = timings.lock().unwrap();
+    assert_eq!(timings.len(), 2);
+    assert!(timings[0] > Duration::ZERO);
+    assert!(timings[0] < Duration::from_secs(60));
+}
+
+#[test]
+fn test_on_timing_panic() {
+    let hasher = HashBuilder::new()
+        .on_timing(|_| panic!("timing hook"))
+        .finalize()
+        .unwrap();
+    assert!(hasher.hash("correct horse battery staple").is_ok());
+    assert!(!hasher.is_valid("correct horse battery staple"));
+}
+
+#[test]
+fn test_randomness_failure() {
+    assert!(super::map_rng_result(Ok(())).is_ok());
+    assert!(matches!(
+        super::map_rng_result(Err(getrandom::Error::UNSUPPORTED)),
+        Err(ErrorCode::RandomnessFailure)
+    ));
+}
+
+#[test]
+fn test_fingerprint() {
+    let key = b"fingerprint key";
+    let hasher = HashBuilder::new().fingerprint_key(key).finalize().unwrap();
+    assert_eq!(hasher.fingerprint(), None);
+    let stored_a = hasher.hash("correct horse battery staple").unwrap();
+    let stored_b = hasher.hash("correct horse battery staple").unwrap();
+
+    let mut checker = HashBuilder::from_phc(&stored_a).unwrap();
+    assert_eq!(checker.fingerprint(), None);
+    let fp_a = checker.fingerprint_key(key).fingerprint().unwrap();
+    assert_eq!(fp_a.len(), 16);
+    assert!(!stored_a.contains(&fp_a));
+    let fp_a2 = HashBuilder::from_phc(&stored_a)
+        .unwrap()
+        .fingerprint_key(key)
+        .fingerprint()
+        .unwrap();
+    assert_eq!(fp_a, fp_a2);
+    let fp_b = HashBuilder::from_phc(&stored_b)
+        .unwrap()
+        .fingerprint_key(key)
+        .fingerprint()
+        .unwrap();
+    assert_ne!(fp_a, fp_b);
+    let fp_other_key = HashBuilder::from_phc(&stored_a)
+        .unwrap()
+        .fingerprint_key(b"other key")
+        .fingerprint()
+        .unwrap();
+    assert_ne!(fp_a, fp_other_key);
+}
+
+#[test]
+fn test_salt_len_bits() {
+    let hasher = HashBuilder::new().salt_len_bits(128).finalize().unwrap();
+    assert_eq!(hasher.salt_len, 16);
+    let stored = hasher.hash("correct horse battery staple").unwrap();
+    let checker = HashBuilder::from_phc(&stored).unwrap();
+    assert_eq!(checker.ref_salt.unwrap().len(), 16);
+
+    for bits in [0, 56, 100, 129] {
+        assert!(matches!(
+            HashBuilder::new().salt_len_bits(bits).finalize(),
+            Err(ErrorCode::InvalidPasswordFormat)
+        ));
+    }
+}
+
+#[test]
+fn test_estimate_crack_cost() {
+    use super::{estimate_crack_cost, CostSummary};
+    use std::time::Duration;
+
+    let summary = CostSummary {
+        algorithm: Algorithm::Pbkdf2,
+        memory_kib: None,
+        passes: None,
+        iterations: Some(500_000),
+        work_factor: 1_000_000,
+    };
+    assert_eq!(
+        estimate_crack_cost(&summary, 1_048_576.0, 41.0),
+        Duration::from_secs(1_048_576_000_000)
+    );
+    assert_eq!(
+        estimate_crack_cost(&summary, 1e6, 1.0),
+        Duration::from_secs(1)
+    );
+    assert_eq!(estimate_crack_cost(&summary, 1e9, 200.0), Duration::MAX);
+    assert_eq!(estimate_crack_cost(&summary, 0.0, 40.0), Duration::MAX);
+
+    let summary = HashBuilder::new()
+        .finalize()
+        .unwrap()
+        .cost_summary()
+        .unwrap();
+    assert_eq!(
+        estimate_crack_cost(&summary, 4096.0 * 3.0, 11.0),
+        Duration::from_secs(1024)
+    );
+}
+
+#[test]
+fn test_zero_salt_len() {
+    for algorithm in [Algorithm::Argon2, Algorithm::Pbkdf2] {
+        assert!(matches!(
+            HashBuilder::new()
+                .algorithm(algorithm)
+                .salt_len(0)
+                .finalize(),
+            Err(ErrorCode::IncompatibleOption)
+        ));
+    }
+}
+
+#[test]
+fn test_suggest_parameters() {
+    use std::time::Duration;
+
+    let hasher = HashBuilder::new().add_param("mem", "7").finalize().unwrap();
+    let params = hasher.suggest_parameters(Duration::from_secs(10)).unwrap();
+    assert!(params["mem"].parse::<u32>().unwrap() > 7);
+    assert!(params["passes"].parse::<u32>().unwrap() >= 3);
+
+    let params = hasher.suggest_parameters(Duration::ZERO).unwrap();
+    assert_eq!(params["mem"], "7");
+    assert_eq!(params["passes"], "3");
+
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .add_param("iter", "10000")
+        .finalize()
+        .unwrap();
+    let params = hasher.suggest_parameters(Duration::from_secs(10)).unwrap();
+    assert!(params["iter"].parse::<u32>().unwrap() > 10_000);
+}
+
+#[test]
+fn test_parameters_ordering() {
+    let mut builder = HashBuilder::new();
+    builder
+        .add_param("iter", "50000")
+        .algorithm(Algorithm::Pbkdf2);
+    assert_eq!(builder.parameters().get("iter").unwrap(), "50000");
+    let hasher = builder.finalize().unwrap();
+    assert_eq!(hasher.pbkdf2_iterations(), Some(50_000));
+
+    builder.algorithm(Algorithm::Argon2);
+    assert!(matches!(
+        builder.finalize(),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+    builder.clear_parameters();
+    assert!(builder.parameters().is_empty());
+    assert!(builder.finalize().is_ok());
+
+    assert!(matches!(
+        HashBuilder::new()
+            .add_param("iter", "1")
+            .algorithm(Algorithm::Pbkdf2)
+            .finalize(),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+}
+
+#[test]
+fn test_finalize_invalid_param() {
+    for (name, value) in [
+        ("mem", "notanumber"),
+        ("mem", "64"),
+        ("passes", "0"),
+        ("foo", "1"),
+    ] {
+        let hasher = HashBuilder::new().add_param(name, value).finalize();
+        assert!(matches!(hasher, Err(ErrorCode::InvalidPasswordFormat)));
+    }
+}
+
+#[test]
+fn test_verify_into() {
+    use super::VerifyScratch;
+
+    let password = "correct horse battery staple";
+    let hasher = HashBuilder::new().finalize().unwrap();
+    let stored_a = hasher.hash(password).unwrap();
+    let stored_b = hasher.hash("another password").unwrap();
+    let checker_a = HashBuilder::from_phc(&stored_a).unwrap();
+    let checker_b = HashBuilder::from_phc(&stored_b).unwrap();
+
+    let mut scratch = VerifyScratch::new();
+    for _ in 0..3 {
+        assert!(checker_a.verify_into(password, &mut scratch).unwrap());
+        assert!(!checker_a.verify_into("bad password", &mut scratch).unwrap());
+    }
+    assert!(!checker_b.verify_into(password, &mut scratch).unwrap());
+    assert!(checker_b
+        .verify_into("another password", &mut scratch)
+        .unwrap());
+    assert!(checker_a.verify_into(password, &mut scratch).unwrap());
+    assert!(matches!(
+        checker_a.verify_into("short", &mut scratch),
+        Err(ErrorCode::PasswordTooShort)
+    ));
+    assert!(matches!(
+        hasher.verify_into(password, &mut scratch),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+}
+
+#[test]
+fn test_verify_into_argon2_secret() {
+    use super::VerifyScratch;
+
+    let password = "correct horse battery staple";
+    let key = b"argon2 secret key";
+    let stored = HashBuilder::new()
+        .argon2_secret(key)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    let good = HashBuilder::from_phc_argon2_secret(&stored, key).unwrap();
+    let bad = HashBuilder::from_phc_argon2_secret(&stored, b"revoked secret key").unwrap();
+    assert!(!bad.is_valid(password));
+
+    let mut scratch = VerifyScratch::new();
+    assert!(good.verify_into(password, &mut scratch).unwrap());
+    assert!(!bad.verify_into(password, &mut scratch).unwrap());
+    assert!(good.verify_into(password, &mut scratch).unwrap());
+}
+
+/// Test vectors from the reference implementation.
+/// https://github.com/P-H-C/phc-winner-argon2/blob/master/src/test.c
+#[test]
+fn test_argon2_reference_format() {
+    let data = [
+        "$argon2i$v=19$m=65536,t=2,p=1$c29tZXNhbHQ$wWKIMhR9lyDFvRz9YTZweHKfbftvj+qf+YFY4NeBbtA",
+        "$argon2i$v=19$m=256,t=2,p=1$c29tZXNhbHQ$iekCn0Y3spW+sCcFanM2xBT63UP2sghkUoHLIUpWRS8",
+        "$argon2id$v=19$m=65536,t=2,p=1$c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc",
+    ];
+    for phc in data.iter() {
+        let checker = HashBuilder::from_phc(phc).unwrap();
+        assert!(checker.is_valid("password"));
+        assert!(!checker.is_valid("bad password"));
+        assert!(HashBuilder::from_phc_strict(phc).is_ok());
+    }
+
+    let checker = HashBuilder::from_phc(data[2]).unwrap();
+    let stored = checker.hash("pass").unwrap();
+    assert!(stored.starts_with("$argon2$"));
+    assert!(stored.contains("variant=id"));
+    assert!(HashBuilder::from_phc(&stored).unwrap().is_valid("pass"));
+
+    for phc in [
+        "$argon2i$m=256,t=2,p=1$c29tZXNhbHQ$iekCn0Y3spW+sCcFanM2xBT63UP2sghkUoHLIUpWRS8",
+        "$argon2i$v=16$m=256,t=2,p=1$c29tZXNhbHQ$iekCn0Y3spW+sCcFanM2xBT63UP2sghkUoHLIUpWRS8",
+        "$argon2i$v=19$m=64,t=2,p=1$c29tZXNhbHQ$iekCn0Y3spW+sCcFanM2xBT63UP2sghkUoHLIUpWRS8",
+        "$argon2$v=19$m=256,t=2,p=1$c29tZXNhbHQ$iekCn0Y3spW+sCcFanM2xBT63UP2sghkUoHLIUpWRS8",
+    ] {
+        assert!(matches!(
+            HashBuilder::from_phc(phc),
+            Err(ErrorCode::InvalidPasswordFormat)
+        ));
+    }
+}
+
+#[test]
+fn test_argon2_memory_not_power_of_two() {
+    // 19 MiB, the minimum memory cost recommended by OWASP.
+    let phc =
+        "$argon2id$v=19$m=19456,t=2,p=1$c29tZXNhbHQ$PL01amPyeUuxG7H0vIr5X+qHkZvWnHmGBGXFYvh8z2E";
+    let checker = HashBuilder::from_phc(phc).unwrap();
+    assert!(checker.is_valid("password"));
+    assert!(!checker.is_valid("bad password"));
+    assert_eq!(checker.cost_summary().unwrap().memory_kib, Some(19456));
+
+    let stored = checker.hash("password").unwrap();
+    assert!(stored.contains("m=19456"));
+    assert!(!stored.contains("mem="));
+    assert!(HashBuilder::from_phc(&stored).unwrap().is_valid("password"));
+}
+
+#[test]
+fn test_strict_unicode() {
+    let password = "pass\u{0378}word";
+    let hasher = HashBuilder::new().finalize().unwrap();
+    let stored = hasher.hash(password).unwrap();
+    assert!(HashBuilder::from_phc(&stored).unwrap().is_valid(password));
+
+    let hasher = HashBuilder::new().strict_unicode(true).finalize().unwrap();
+    assert!(matches!(
+        hasher.hash(password),
+        Err(ErrorCode::PasswordContainsUnassigned)
+    ));
+    assert!(hasher.hash("pass\u{00e9}word \u{1f511}").is_ok());
+}
+
+#[test]
+fn test_php_password_hash() {
+    // password_hash("rasmuslerdorf", PASSWORD_ARGON2I), from the PHP manual
+    let checker = HashBuilder::from_phc("$argon2i$v=19$m=1024,t=2,p=2$YzJBSzV4TUhkMzc3d3laeg$zqU/1IN0/AogfP4cmSJI1vc8lpXRW9/S0sYY2i2jHT0").unwrap();
+    assert!(checker.is_valid("rasmuslerdorf"));
+    assert!(!checker.is_valid("rasmuslerdorF"));
+
+    // password_hash($password, PASSWORD_ARGON2ID) with the default cost (m=65536, t=4, p=1)
+    let data = [
+        ("rasmuslerdorf", "$argon2id$v=19$m=65536,t=4,p=1$WmsxaE4yWmpTVTVHUzJSaQ$Z+O8Rp1fmyfHHX/tTsDVCQJVA+gdS787x8SOCocLh0M"),
+        ("correct horse battery staple", "$argon2id$v=19$m=65536,t=4,p=1$ihPwXJEuR9O2CHxa4TnEYg$yNPmsT9MYWrwKTdoCZdq5Q3goAJkZn0G4O2CDB2b4nc"),
+        ("\u{fb01}sh & chips", "$argon2id$v=19$m=65536,t=4,p=1$AZ8i51vAPYhxqkYO1ZMsvw$V8AWuS4Ay+3uEUc5OkqQJG6IbHH5xRW/YmNCBqHPmag"),
+    ];
+    for (password, phc) in data.iter() {
+        let checker = HashBuilder::from_phc(phc).unwrap();
+        assert!(checker.is_valid(password));
+        assert!(!checker.is_valid("wrong password"));
+    }
+
+    // PHP does not normalize the password.
+    let checker = HashBuilder::from_phc(data[2].1).unwrap();
+    assert!(!checker.is_valid("fish & chips"));
+}
+
+#[test]
+fn test_policy() {
+    let hasher = HashBuilder::new()
+        .add_param("mem", "12")
+        .add_param("passes", "3")
+        .finalize()
+        .unwrap();
+    let stored = hasher.hash("correct horse battery staple").unwrap();
+    let checker = HashBuilder::from_phc(&stored).unwrap();
+    assert!(checker.satisfies(&Policy::default()));
+
+    let mut policy = Policy {
+        algorithm: Some(Algorithm::Argon2),
+        min_memory_kib: Some(4096),
+        min_passes: Some(3),
+        min_iterations: Some(200_000),
+        ..Policy::default()
+    };
+    assert!(checker.satisfies(&policy));
+    policy.min_memory_kib = Some(8192);
+    assert!(!checker.satisfies(&policy));
+    policy.min_memory_kib = None;
+    policy.min_passes = Some(4);
+    assert!(!checker.satisfies(&policy));
+    policy.min_passes = None;
+    policy.algorithm = Some(Algorithm::Pbkdf2);
+    assert!(!checker.satisfies(&policy));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_policy_deserialize() {
+    let policy: Policy =
+        serde_json::from_str(r#"{"algorithm": "argon2", "min_memory_kib": 8192}"#).unwrap();
+    assert_eq!(policy.algorithm, Some(Algorithm::Argon2));
+    assert_eq!(policy.min_memory_kib, Some(8192));
+    assert_eq!(policy.min_passes, None);
+    let checker = HashBuilder::new()
+        .add_param("mem", "12")
+        .finalize()
+        .unwrap();
+    assert!(!checker.satisfies(&policy));
+
+    assert!(serde_json::from_str::<Policy>(r#"{"min_memory": 8192}"#).is_err());
+
+    let policy: Policy =
+        serde_json::from_str(r#"{"version": 2, "normalization": "nfkc"}"#).unwrap();
+    assert_eq!(policy.version, Some(2));
+    assert_eq!(policy.normalization, Some(Normalization::Nfkc));
+}
+
+#[test]
+fn test_min_work_factor() {
+    use super::VerifyScratch;
+
+    let password = "correct horse battery staple";
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .add_param("iter", "10000")
+        .finalize()
+        .unwrap();
+    let stored = hasher.hash(password).unwrap();
+
+    let mut checker = HashBuilder::from_phc(&stored).unwrap();
+    checker.min_work_factor(20_000);
+    assert!(checker.is_valid(password));
+    checker.min_work_factor(20_001);
+    assert!(!checker.is_valid(password));
+    assert!(matches!(
+        checker.verify_into(password, &mut VerifyScratch::new()),
+        Err(ErrorCode::IncompatibleOption)
+    ));
+    assert!(matches!(
+        checker.hash(password),
+        Err(ErrorCode::IncompatibleOption)
+    ));
+
+    assert!(matches!(
+        HashBuilder::new()
+            .algorithm(Algorithm::Pbkdf2)
+            .add_param("iter", "10000")
+            .min_work_factor(90_000)
+            .finalize(),
+        Err(ErrorCode::IncompatibleOption)
+    ));
+    assert!(HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .min_work_factor(90_000)
+        .finalize()
+        .is_ok());
+}
+
+#[test]
+fn test_default_normalization() {
+    assert_eq!(
+        PasswordStorageStandard::Nist80063b.default_normalization(),
+        Normalization::Nfkc
+    );
+    assert_eq!(
+        PasswordStorageStandard::NoStandard.default_normalization(),
+        std_default::DEFAULT_NORMALIZATION
+    );
+    assert_eq!(
+        Normalization::recommended(),
+        std_default::DEFAULT_NORMALIZATION
+    );
+    for std in [
+        PasswordStorageStandard::NoStandard,
+        PasswordStorageStandard::Nist80063b,
+    ] {
+        let hasher = HashBuilder::new_std(std).finalize().unwrap();
+        assert_eq!(hasher.normalization(), std.default_normalization());
+    }
+}
+
+#[test]
+fn test_hash_and_copy() {
+    let password = "correct horse battery staple";
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .finalize()
+        .unwrap();
+    let len = hasher.hash(password).unwrap().len();
+
+    let mut out = vec![0u8; len];
+    assert_eq!(hasher.hash_and_copy(password, &mut out).unwrap(), len);
+    let stored = std::str::from_utf8(&out).unwrap();
+    assert!(HashBuilder::from_phc(stored).unwrap().is_valid(password));
+
+    let mut out = [0u8; PASSWORD_STORAGE_LEN];
+    let written = hasher.hash_and_copy(password, &mut out).unwrap();
+    assert_eq!(written, len);
+    assert!(out[written..].iter().all(|&b| b == 0));
+
+    let mut out = vec![0u8; len - 1];
+    assert!(matches!(
+        hasher.hash_and_copy(password, &mut out),
+        Err(ErrorCode::NotEnoughSpace)
+    ));
+    assert!(out.iter().all(|&b| b == 0));
+}
+
+#[test]
+fn test_crafted_ids() {
+    let suffix = "$iter=21000,hmac=sha256$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+    assert!(HashBuilder::from_phc(&format!("$pbkdf2{}", suffix)).is_ok());
+    for id in [
+        "pbkdf2-sha256",
+        "pbkdf2-sha256-extra",
+        "pbkdf2-",
+        "-pbkdf2",
+        "pbkdf22",
+        "argon2id-pbkdf2",
+        "argon2-id",
+        "scrypt-pbkdf2",
+    ] {
+        let phc = format!("${}{}", id, suffix);
+        assert!(matches!(
+            HashBuilder::from_phc(&phc),
+            Err(ErrorCode::InvalidPasswordFormat)
+        ));
+        assert!(matches!(
+            HashBuilder::from_phc_prefixed(&phc),
+            Err(ErrorCode::InvalidPasswordFormat)
+        ));
+    }
+
+    assert!(HashBuilder::from_phc_prefixed(&format!("$family$pbkdf2{}", suffix)).is_ok());
+    for prefix in ["$argon2", "$family$argon2id", "$pbkdf2"] {
+        let phc = format!("{}$pbkdf2{}", prefix, suffix);
+        assert!(matches!(
+            HashBuilder::from_phc_prefixed(&phc),
+            Err(ErrorCode::InvalidPasswordFormat)
+        ));
+    }
+}
+
+#[test]
+fn test_verify_with_timeout() {
+    use std::time::Duration;
+
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .add_param("iter", "10000")
+        .finalize()
+        .unwrap();
+    let stored_hash = hasher.hash("correct horse").unwrap();
+    let checker = HashBuilder::from_phc(&stored_hash).unwrap();
+    let budget = Duration::from_secs(60);
+    assert!(checker
+        .verify_with_timeout("correct horse", budget)
+        .unwrap());
+    assert!(!checker
+        .verify_with_timeout("battery staple", budget)
+        .unwrap());
+    assert!(matches!(
+        checker.verify_with_timeout("short", budget),
+        Err(ErrorCode::PasswordTooShort)
+    ));
+
+    // Only the verification has to be slow: raise the cost of a cheap hash.
+    let hasher = HashBuilder::new()
+        .add_param("mem", "14")
+        .finalize()
+        .unwrap();
+    let stored_hash = hasher.hash("correct horse").unwrap();
+    assert!(stored_hash.contains("passes=3"));
+    let stored_hash = stored_hash.replace("passes=3", "passes=100");
+    let checker = HashBuilder::from_phc(&stored_hash).unwrap();
+    assert!(matches!(
+        checker.verify_with_timeout("correct horse", Duration::from_millis(1)),
+        Err(ErrorCode::Timeout)
+    ));
+}
+
+#[test]
+fn test_worker_slots() {
+    use super::hasher::WorkerSlots;
+
+    let slots = WorkerSlots::new(2);
+    let first = slots.try_acquire().unwrap();
+    let second = slots.try_acquire().unwrap();
+    assert!(slots.try_acquire().is_none());
+    drop(first);
+    let third = slots.try_acquire().unwrap();
+    assert!(slots.try_acquire().is_none());
+    drop(second);
+    drop(third);
+    assert!(slots.try_acquire().is_some());
+}
+
+#[test]
+fn test_needs_update_full() {
+    let policy = Policy {
+        algorithm: Some(Algorithm::Argon2),
+        min_memory_kib: Some(4096),
+        version: Some(2),
+        normalization: Some(Normalization::Nfkc),
+        ..Policy::default()
+    };
+    let checker = HashBuilder::new()
+        .version(2)
+        .add_param("mem", "12")
+        .finalize()
+        .unwrap();
+    let reasons = checker.needs_update_full(&policy);
+    assert_eq!(reasons, UpdateReasons::default());
+    assert!(!reasons.any());
+    assert!(checker.satisfies(&policy));
+
+    let checker = HashBuilder::new()
+        .version(1)
+        .add_param("mem", "12")
+        .finalize()
+        .unwrap();
+    let reasons = checker.needs_update_full(&policy);
+    assert!(reasons.version_stale);
+    assert!(!reasons.algorithm_outdated && !reasons.cost_below_policy);
+    assert!(!reasons.normalization_outdated);
+
+    let checker = HashBuilder::new()
+        .version(2)
+        .add_param("mem", "11")
+        .finalize()
+        .unwrap();
+    assert_eq!(
+        checker.needs_update_full(&policy),
+        UpdateReasons {
+            cost_below_policy: true,
+            ..UpdateReasons::default()
+        }
+    );
+
+    let checker = HashBuilder::new()
+        .version(2)
+        .normalization(Normalization::Nfd)
+        .add_param("mem", "12")
+        .finalize()
+        .unwrap();
+    assert_eq!(
+        checker.needs_update_full(&policy),
+        UpdateReasons {
+            normalization_outdated: true,
+            ..UpdateReasons::default()
+        }
+    );
+    assert!(!checker.satisfies(&policy));
+
+    let checker = HashBuilder::new()
+        .version(1)
+        .algorithm(Algorithm::Pbkdf2)
+        .normalization(Normalization::None)
+        .finalize()
+        .unwrap();
+    let reasons = checker.needs_update_full(&policy);
+    assert_eq!(
+        reasons,
+        UpdateReasons {
+            version_stale: true,
+            algorithm_outdated: true,
+            cost_below_policy: false,
+            normalization_outdated: true,
+        }
+    );
+    assert!(reasons.any());
+    assert!(!checker.needs_update_full(&Policy::default()).any());
+}
+
+#[test]
+fn test_from_phc_max_len() {
+    let password = "correct horse battery staple";
+    let stored = HashBuilder::new()
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(matches!(
+        HashBuilder::from_phc_max_len(&stored, stored.len() - 1),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+    let checker = HashBuilder::from_phc_max_len(&stored, stored.len()).unwrap();
+    assert!(checker.is_valid(password));
+    assert!(!checker.is_valid("bad password"));
+
+    let oversized = format!("{}{}", stored, "A".repeat(4096));
+    assert!(matches!(
+        HashBuilder::from_phc(&oversized),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+    let checker = HashBuilder::from_phc_max_len(&oversized, oversized.len()).unwrap();
+    assert!(!checker.is_valid(password));
+}
+
+#[test]
+fn test_from_phc_bytes() {
+    let phc = "$pbkdf2$iter=45000,hmac=sha512$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+    let checker = HashBuilder::from_phc_bytes(phc.as_bytes()).unwrap();
+    let reference = HashBuilder::from_phc(phc).unwrap();
+    assert_eq!(checker.algorithm(), reference.algorithm());
+    assert_eq!(checker.parameters(), reference.parameters());
+
+    let mut data = phc.as_bytes().to_vec();
+    data[10] = 0xff;
+    assert!(matches!(
+        HashBuilder::from_phc_bytes(&data),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+    let mut data = phc.as_bytes().to_vec();
+    data.push(b'\n');
+    assert!(matches!(
+        HashBuilder::from_phc_bytes(&data),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+    assert!(matches!(
+        HashBuilder::from_phc_bytes(
+            b"$2b$12$R9h/cIPz0gi.URNNX3kh2OPST9/PgBkqquzi.Ss7KIUgO2t0jWMUW"
+        ),
+        Err(ErrorCode::AlgorithmNotAvailable)
+    ));
+}
+
+#[test]
+fn test_verify_only() {
+    let password = "correct horse battery staple";
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .add_param("iter", "10000")
+        .verify_only(true)
+        .finalize()
+        .unwrap();
+    assert!(matches!(
+        hasher.hash(password),
+        Err(ErrorCode::IncompatibleOption)
+    ));
+    assert!(matches!(
+        hasher.hash_and_copy(password, &mut [0; PASSWORD_STORAGE_LEN]),
+        Err(ErrorCode::IncompatibleOption)
+    ));
+    assert!(matches!(
+        hasher.derive_raw(password, b"some salt"),
+        Err(ErrorCode::IncompatibleOption)
+    ));
+
+    let stored = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .add_param("iter", "10000")
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    let mut checker = HashBuilder::from_phc(&stored).unwrap();
+    assert!(checker.hash(password).is_ok());
+    checker.verify_only(true);
+    assert!(checker.is_valid(password));
+    assert!(!checker.is_valid("bad password"));
+    assert!(matches!(
+        checker.hash(password),
+        Err(ErrorCode::IncompatibleOption)
+    ));
+    assert!(matches!(
+        checker.rebless(2),
+        Err(ErrorCode::IncompatibleOption)
+    ));
+    checker.verify_only(false);
+    assert!(checker.rebless(2).is_ok());
+}
+
+#[test]
+fn test_case_fold() {
+    for case_fold in [false, true] {
+        let hasher = HashBuilder::new()
+            .algorithm(Algorithm::Pbkdf2)
+            .add_param("iter", "10000")
+            .case_fold(case_fold)
+            .finalize()
+            .unwrap();
+        let stored = hasher.hash("Password\u{c9}").unwrap();
+        assert_eq!(stored.contains("fold=1"), case_fold);
+        let checker = HashBuilder::from_phc(&stored).unwrap();
+        assert!(checker.is_valid("Password\u{c9}"));
+        assert_eq!(checker.is_valid("password\u{e9}"), case_fold);
+        assert_eq!(checker.is_valid("PASSWORDE\u{301}"), case_fold);
+        assert!(!checker.is_valid("Passw0rd\u{c9}"));
+    }
+
+    assert!(matches!(
+        HashBuilder::new()
+            .case_fold(true)
+            .xhmac_before(b"pepper")
+            .finalize(),
+        Err(ErrorCode::IncompatibleOption)
+    ));
+    let phc = "$pbkdf2$iter=10000,fold=0$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+    assert!(matches!(
+        HashBuilder::from_phc(phc),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+}
+
+#[test]
+fn test_foreign_parameters() {
+    let phcs = [
+        "$argon2$iter=10000,passes=3,mem=12$c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc",
+        "$argon2$hmac=sha256$c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc",
+        "$argon2id$v=19$m=65536,t=2,p=1,iter=1$c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc",
+        "$pbkdf2$iter=10000,mem=12$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+        "$pbkdf2$iter=10000,passes=3$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc",
+    ];
+    for phc in phcs {
+        assert!(matches!(
+            HashBuilder::from_phc(phc),
+            Err(ErrorCode::InvalidPasswordFormat)
+        ));
+    }
+}
+
+#[test]
+fn test_check_policy_too_short() {
+    let hasher = HashBuilder::new().min_len(12).finalize().unwrap();
+    let err = hasher.check_policy("0123456789a").unwrap_err();
+    assert_eq!(err, PasswordPolicyError::TooShort { min: 12 });
+    assert_eq!(err.to_string(), "too short: minimum 12");
+    assert!(matches!(ErrorCode::from(err), ErrorCode::PasswordTooShort));
+    assert!(hasher.check_policy("0123456789ab").is_ok());
+}
+
+#[test]
+fn test_check_policy_too_long() {
+    let hasher = HashBuilder::new().max_len(8).finalize().unwrap();
+    let err = hasher.check_policy("012345678").unwrap_err();
+    assert_eq!(err, PasswordPolicyError::TooLong { max: 8 });
+    assert_eq!(err.to_string(), "too long: maximum 8");
+    assert!(matches!(ErrorCode::from(err), ErrorCode::PasswordTooLong));
+    assert!(hasher.check_policy("01234567").is_ok());
+}
+
+#[test]
+fn test_check_policy_unassigned() {
+    let hasher = HashBuilder::new().strict_unicode(true).finalize().unwrap();
+    let err = hasher.check_policy("pass\u{0378}word").unwrap_err();
+    assert_eq!(err, PasswordPolicyError::ContainsUnassigned);
+    assert!(matches!(
+        ErrorCode::from(err),
+        ErrorCode::PasswordContainsUnassigned
+    ));
+    assert!(hasher.check_policy("pass\u{00e9}word").is_ok());
+}
+
+#[test]
+fn test_integrity_key() {
+    let password = "correct horse battery staple";
+    let secret = b"server-side secret";
+    let stored = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .add_param("iter", "80000")
+        .integrity_key(secret)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(PHCData::from_str(&stored)
+        .unwrap()
+        .parameters
+        .contains_key("mac"));
+    let checker = HashBuilder::from_phc_integrity(&stored, secret).unwrap();
+    assert!(checker.is_valid(password));
+    let rehashed = checker.hash(password).unwrap();
+    assert!(HashBuilder::from_phc_integrity(&rehashed, secret).is_ok());
+
+    // The secret is required.
+    assert!(HashBuilder::from_phc(&stored).is_err());
+    assert!(HashBuilder::from_phc_integrity(&stored, b"wrong secret").is_err());
+
+    // Flipped hash byte, altered salt and altered parameters.
+    let mut flipped = PHCData::from_str(&stored).unwrap();
+    if let Some(ref mut hash) = flipped.hash {
+        hash[0] ^= 0x01;
+    }
+    let mut salted = PHCData::from_str(&stored).unwrap();
+    salted.salt = Some(b"another salt".to_vec());
+    let tampered = [
+        flipped.to_string().unwrap(),
+        salted.to_string().unwrap(),
+        stored.replace("iter=80000", "iter=10000"),
+    ];
+    for t in tampered.iter() {
+        assert_ne!(*t, stored);
+        assert!(matches!(
+            HashBuilder::from_phc_integrity(t, secret),
+            Err(ErrorCode::InvalidPasswordFormat)
+        ));
+    }
+
+    // Missing tag.
+    let unauthenticated = HashBuilder::new()
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(matches!(
+        HashBuilder::from_phc_integrity(&unauthenticated, secret),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+
+    assert!(matches!(
+        HashBuilder::new()
+            .integrity_key(secret)
+            .param_mac(secret)
+            .finalize(),
+        Err(ErrorCode::IncompatibleOption)
+    ));
+}
+
+#[test]
+fn test_from_phc_with_layout() {
+    let password = "correct horse battery staple";
+    let stored = HashBuilder::new()
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    let mut segments: Vec<&str> = stored.split('$').collect();
+    let len = segments.len();
+    segments.swap(len - 2, len - 1);
+    let swapped = segments.join("$");
+
+    let checker = HashBuilder::from_phc_with_layout(&swapped, SegmentLayout::HashSalt).unwrap();
+    assert!(checker.is_valid(password));
+    assert!(!checker.is_valid("incorrect horse battery staple"));
+    assert!(!HashBuilder::from_phc(&swapped).unwrap().is_valid(password));
+
+    let checker = HashBuilder::from_phc_with_layout(&stored, SegmentLayout::default()).unwrap();
+    assert!(checker.is_valid(password));
+    assert!(matches!(
+        HashBuilder::from_phc_with_layout("$pbkdf2$iter=45000$RSF4Aw", SegmentLayout::HashSalt),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+}
+
+#[test]
+fn test_timestamp_hashes() {
+    let password = "correct horse battery staple";
+    let stored = HashBuilder::new()
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(!PHCData::from_str(&stored)
+        .unwrap()
+        .parameters
+        .contains_key("ts"));
+    assert_eq!(HashBuilder::from_phc(&stored).unwrap().created_at(), None);
+
+    let before = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let stored = HashBuilder::new()
+        .timestamp_hashes(true)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    let ts = PHCData::from_str(&stored).unwrap().parameters["ts"].clone();
+    let checker = HashBuilder::from_phc(&stored).unwrap();
+    let created_at = checker.created_at().unwrap();
+    assert_eq!(created_at.to_string(), ts);
+    assert!(created_at >= before);
+    assert!(checker.is_valid(password));
+
+    // The timestamp does not affect the derivation.
+    let other_time = stored.replace(&format!("ts={}", ts), "ts=946684800");
+    let checker = HashBuilder::from_phc(&other_time).unwrap();
+    assert_eq!(checker.created_at(), Some(946_684_800));
+    assert!(checker.is_valid(password));
+
+    let invalid = stored.replace(&format!("ts={}", ts), "ts=yesterday");
+    assert!(matches!(
+        HashBuilder::from_phc(&invalid),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+}
+
+#[test]
+fn test_auto_salt_len() {
+    let cases = [
+        (Algorithm::Pbkdf2, Some("sha256"), 32),
+        (Algorithm::Pbkdf2, Some("sha1"), 20),
+        (Algorithm::Pbkdf2, None, 64),
+        (Algorithm::Argon2, None, 16),
+    ];
+    for (algorithm, hmac, expected) in cases {
+        let mut builder = HashBuilder::new();
+        builder.algorithm(algorithm).auto_salt_len(true);
+        if let Some(hmac) = hmac {
+            builder.add_param("hmac", hmac);
+        }
+        let hasher = builder.finalize().unwrap();
+        assert_eq!(hasher.salt_len, expected);
+        let stored = hasher.hash("correct horse battery staple").unwrap();
+        let checker = HashBuilder::from_phc(&stored).unwrap();
+        assert_eq!(checker.ref_salt.unwrap().len(), expected);
+    }
+
+    // An explicit salt length takes precedence.
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .salt_len(24)
+        .auto_salt_len(true)
+        .finalize()
+        .unwrap();
+    assert_eq!(hasher.salt_len, 24);
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .auto_salt_len(true)
+        .salt_len_bits(128)
+        .finalize()
+        .unwrap();
+    assert_eq!(hasher.salt_len, 16);
+}
+
+#[test]
+fn test_skip_length_check_on_verify() {
+    let password = "correct horse battery staple";
+    let stored = HashBuilder::new()
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    // The maximal length has been tightened since the password was hashed.
+    let tightened = stored.replace("pmax=128", "pmax=16");
+    assert_ne!(tightened, stored);
+
+    let mut checker = HashBuilder::from_phc(&tightened).unwrap();
+    assert!(!checker.is_valid(password));
+    checker.skip_length_check_on_verify(true);
+    assert!(checker.is_valid(password));
+    assert!(!checker.is_valid("incorrect horse battery staple"));
+    assert!(matches!(
+        checker.hash(password),
+        Err(ErrorCode::PasswordTooLong)
+    ));
+
+    let hasher = HashBuilder::new()
+        .min_len(12)
+        .max_len(16)
+        .skip_length_check_on_verify(true)
+        .finalize()
+        .unwrap();
+    assert!(matches!(
+        hasher.hash(password),
+        Err(ErrorCode::PasswordTooLong)
+    ));
+    assert!(matches!(
+        hasher.hash("short"),
+        Err(ErrorCode::PasswordTooShort)
+    ));
+}
+
+#[test]
+fn test_reject_whitespace_only() {
+    let password = "        ";
+    let hasher = HashBuilder::new().finalize().unwrap();
+    assert!(hasher.hash(password).is_ok());
+
+    let hasher = HashBuilder::new()
+        .reject_whitespace_only(true)
+        .finalize()
+        .unwrap();
+    assert!(matches!(
+        hasher.hash(password),
+        Err(ErrorCode::PasswordWhitespaceOnly)
+    ));
+    assert!(matches!(
+        hasher.hash("\t\u{3000}\u{2003}  \n \u{a0}"),
+        Err(ErrorCode::PasswordWhitespaceOnly)
+    ));
+    assert_eq!(
+        hasher.check_policy(password),
+        Err(PasswordPolicyError::WhitespaceOnly)
+    );
+    assert!(hasher.hash("  correct horse  ").is_ok());
+}
+
+#[test]
+fn test_salt() {
+    let hasher = HashBuilder::new().finalize().unwrap();
+    assert_eq!(hasher.salt(), None);
+    let stored = hasher.hash("correct horse battery staple").unwrap();
+    let checker = HashBuilder::from_phc(&stored).unwrap();
+    let salt = PHCData::from_str(&stored).unwrap().salt.unwrap();
+    assert_eq!(checker.salt(), Some(salt.as_slice()));
+    assert_eq!(salt.len(), checker.salt_len);
+
+    let checker =
+        HashBuilder::from_phc("$pbkdf2$iter=45000,hmac=sha512$RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc")
+            .unwrap();
+    assert_eq!(checker.salt(), Some(&b"E!x\x03"[..]));
+}
+
+#[test]
+fn test_argon2_stored_hash_len() {
+    // Produced by the reference implementation with a 32-byte hash.
+    let config = ::argon2::Config {
+        hash_length: 32,
+        mem_cost: 256,
+        time_cost: 2,
+        variant: ::argon2::Variant::Argon2id,
+        ..::argon2::Config::default()
+    };
+    let stored = ::argon2::hash_encoded(b"password", b"somesalt", &config).unwrap();
+    assert_eq!(PHCData::from_str(&stored).unwrap().hash.unwrap().len(), 32);
+    let checker = HashBuilder::from_phc(&stored).unwrap();
+    assert!(checker.is_valid("password"));
+    assert!(!checker.is_valid("bad password"));
+
+    // Without the `len` parameter, the length of the stored hash is used.
+    let stored = HashBuilder::new()
+        .add_param("len", "32")
+        .finalize()
+        .unwrap()
+        .hash("password")
+        .unwrap();
+    let stored = stored.replace("len=32,", "").replace(",len=32", "");
+    assert!(!stored.contains("len=32"));
+    let checker = HashBuilder::from_phc(&stored).unwrap();
+    assert!(checker.is_valid("password"));
+    assert!(!checker.is_valid("bad password"));
+}
+
+/// Test case 1 from RFC 5869.
+#[test]
+fn test_hkdf_sha256() {
+    use crate::pass::hasher::hkdf_sha256;
+
+    let ikm = [0x0b; 22];
+    let salt = hex::decode("000102030405060708090a0b0c").unwrap();
+    let info = hex::decode("f0f1f2f3f4f5f6f7f8f9").unwrap();
+    let okm = hkdf_sha256(&salt, &ikm, &info, 42).unwrap();
+    assert_eq!(
+        hex::encode(okm),
+        "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"
+    );
+    assert_eq!(hkdf_sha256(&salt, &ikm, &info, 8160).unwrap().len(), 8160);
+    assert!(matches!(
+        hkdf_sha256(&salt, &ikm, &info, 8161),
+        Err(ErrorCode::InvalidKeyLen)
+    ));
+}
+
+#[test]
+fn test_derive_key() {
+    let password = "correct horse battery staple";
+    let stored = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    let hasher = HashBuilder::from_phc(&stored).unwrap();
+    let auth_key = hasher.derive_key(password, b"authentication", 32).unwrap();
+    let enc_key = hasher.derive_key(password, b"file-encryption", 32).unwrap();
+    assert_eq!(auth_key.len(), 32);
+    assert_ne!(auth_key, enc_key);
+    assert_eq!(
+        HashBuilder::from_phc(&stored)
+            .unwrap()
+            .derive_key(password, b"authentication", 32)
+            .unwrap(),
+        auth_key
+    );
+    assert_ne!(
+        hasher
+            .derive_key("wrong password", b"authentication", 32)
+            .unwrap(),
+        auth_key
+    );
+    let long_key = hasher.derive_key(password, b"authentication", 64).unwrap();
+    assert_eq!(&long_key[..32], auth_key.as_slice());
+
+    // The key cannot be computed from the fields of the stored hash.
+    let phc = PHCData::from_str(&stored).unwrap();
+    let salt = phc.salt.unwrap();
+    let hash = phc.hash.unwrap();
+    assert_ne!(
+        super::hasher::hkdf_sha256(&salt, &hash, b"authentication", 32).unwrap(),
+        auth_key
+    );
+    assert_ne!(&long_key[..], &hash[..64]);
+
+    // A salt is required.
+    assert!(matches!(
+        HashBuilder::new()
+            .finalize()
+            .unwrap()
+            .derive_key(password, b"authentication", 32),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+}
+
+#[test]
+fn test_internal_version_bump() {
+    assert_eq!(super::internal_version(), INTERNAL_VERSION);
+    let password = "correct horse battery staple";
+    let stored = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .version(3)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(stored.contains(&format!("ver={}", 3 + INTERNAL_VERSION)));
+    let mut checker = HashBuilder::from_phc(&stored).unwrap();
+    assert!(!checker.needs_update(Some(3)));
+    assert!(!checker.needs_update(None));
+
+    // Simulate a release which bumps the internal version.
+    let bumped = INTERNAL_VERSION + 1;
+    checker.internal_version(bumped);
+    assert!(checker.is_valid(password));
+    assert!(checker.needs_update(Some(3)));
+    assert!(!checker.needs_update(Some(2)));
+    assert_eq!(checker.version(), 2);
+
+    let new_stored = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .version(3)
+        .internal_version(bumped)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(new_stored.contains(&format!("ver={}", 3 + bumped)));
+    let mut checker = HashBuilder::from_phc(&new_stored).unwrap();
+    checker.internal_version(bumped);
+    assert!(checker.is_valid(password));
+    assert!(!checker.needs_update(Some(3)));
+    assert!(checker.needs_update(Some(4)));
+    assert_eq!(checker.version(), 3);
+
+    // The order of the builder options does not matter.
+    let hasher = HashBuilder::new()
+        .internal_version(bumped)
+        .version(3)
+        .finalize()
+        .unwrap();
+    assert_eq!(hasher.version, 3 + bumped);
+    assert_eq!(hasher.version(), 3);
+}
+
+#[test]
+fn test_implausible_cost_rejected() {
+    let salt_hash = "RSF4Aw$LwCbGeQoBZIraYoDZ8Oe/PxdJHc";
+    let argon2_salt_hash = "c29tZXNhbHQ$CTFhFdXPJO1aFaMaO6Mm5c8y7cJHAph8ArZWb2GRPPc";
+    for phc in [
+        format!("$pbkdf2$iter=4000000000${}", salt_hash),
+        format!("$pbkdf2$iter=200001${}", salt_hash),
+        format!("$pbkdf2$iter=99999999999999999999${}", salt_hash),
+        format!(
+            "$argon2id$v=19$m=65536,t=4000000000,p=1${}",
+            argon2_salt_hash
+        ),
+        format!("$argon2id$v=19$m=4294967295,t=2,p=1${}", argon2_salt_hash),
+        format!("$argon2id$v=19$m=2147483648,t=2,p=1${}", argon2_salt_hash),
+        format!(
+            "$argon2id$v=19$m=65536,t=2,p=4000000000${}",
+            argon2_salt_hash
+        ),
+        format!("$argon2$passes=2,mem=31,lanes=1${}", argon2_salt_hash),
+    ] {
+        assert!(matches!(
+            HashBuilder::from_phc(&phc),
+            Err(ErrorCode::InvalidPasswordFormat)
+        ));
+        assert!(!super::is_valid_phc(&phc));
+    }
+
+    // No builder option lifts those bounds.
+    assert!(matches!(
+        HashBuilder::new()
+            .algorithm(Algorithm::Pbkdf2)
+            .add_param("iter", "4000000000")
+            .finalize(),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+}
+
+#[test]
+fn test_forbid_hash() {
+    let password = "correct horse battery staple";
+    let legacy = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .add_param("hmac", "sha1")
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(legacy.contains("hmac=sha1"));
+
+    // Creating a new SHA-1 hash is refused.
+    assert!(matches!(
+        HashBuilder::new()
+            .algorithm(Algorithm::Pbkdf2)
+            .add_param("hmac", "sha1")
+            .forbid_hash("sha1")
+            .finalize(),
+        Err(ErrorCode::IncompatibleOption)
+    ));
+    assert!(matches!(
+        HashBuilder::new()
+            .algorithm(Algorithm::Pbkdf2)
+            .add_param("hash", "SHA1")
+            .forbid_hash("md5")
+            .finalize(),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+
+    // The legacy hash can still be verified and replaced.
+    let checker = HashBuilder::from_phc(&legacy).unwrap();
+    assert!(checker.is_valid(password));
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .forbid_hash("sha1")
+        .forbid_hash("sha256")
+        .finalize()
+        .unwrap();
+    let stored = hasher.hash(password).unwrap();
+    assert!(!stored.contains("hmac=sha1"));
+    assert!(HashBuilder::from_phc(&stored).unwrap().is_valid(password));
+
+    // The option has no effect on Argon2.
+    assert!(HashBuilder::new().forbid_hash("sha1").finalize().is_ok());
+}
+
+#[test]
+fn test_metadata() {
+    let password = "correct horse battery staple";
+    let metadata = r#"{"enrollment":"sms","risk":0.2}"#;
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .metadata(metadata)
+        .finalize()
+        .unwrap();
+    assert_eq!(hasher.metadata().as_deref(), Some(metadata));
+    let stored = hasher.hash(password).unwrap();
+    assert!(stored.contains("meta="));
+    assert!(!stored.contains("enrollment"));
+    let checker = HashBuilder::from_phc(&stored).unwrap();
+    assert_eq!(checker.metadata().as_deref(), Some(metadata));
+    assert!(checker.is_valid(password));
+    assert!(super::is_valid_phc(&stored));
+
+    // The metadata does not change the derivation.
+    let without = HashBuilder::from_phc(&stored.replace(
+        &format!("meta={}", super::phc::encode_metadata(metadata)),
+        "meta=e30",
+    ))
+    .unwrap();
+    assert_eq!(without.metadata().as_deref(), Some("{}"));
+    assert!(without.is_valid(password));
+    assert!(HashBuilder::new().finalize().unwrap().metadata().is_none());
+}
+
+#[test]
+fn test_metadata_integrity() {
+    let secret = b"integrity secret";
+    let stored = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .metadata(r#"{"risk":1}"#)
+        .integrity_key(secret)
+        .finalize()
+        .unwrap()
+        .hash("correct horse battery staple")
+        .unwrap();
+    let checker = HashBuilder::from_phc_integrity(&stored, secret).unwrap();
+    assert_eq!(checker.metadata().as_deref(), Some(r#"{"risk":1}"#));
+    let tampered = stored.replace(
+        &format!("meta={}", super::phc::encode_metadata(r#"{"risk":1}"#)),
+        &format!("meta={}", super::phc::encode_metadata(r#"{"risk":0}"#)),
+    );
+    assert_ne!(tampered, stored);
+    assert!(matches!(
+        HashBuilder::from_phc_integrity(&tampered, secret),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+}
+
+#[test]
+fn test_metadata_size_limit() {
+    let max = "a".repeat(256);
+    let hasher = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .metadata(&max)
+        .finalize()
+        .unwrap();
+    let stored = hasher.hash("correct horse battery staple").unwrap();
+    assert_eq!(
+        HashBuilder::from_phc(&stored).unwrap().metadata().unwrap(),
+        max
+    );
+    assert!(matches!(
+        HashBuilder::new().metadata(&"a".repeat(257)).finalize(),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+    let too_long = stored.replace(
+        &super::phc::encode_metadata(&max),
+        &super::phc::encode_metadata(&"a".repeat(257)),
+    );
+    assert!(matches!(
+        HashBuilder::from_phc(&too_long),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+    assert!(!super::is_valid_phc(&too_long));
+}
+
+#[test]
+fn test_deterministic_for_docs() {
+    let password = "correct horse battery staple";
+    let expected = "$pbkdf2$fixedsalt=1,hmac=sha512,iter=10000,len-calc=chars,norm=nfkc,pmax=128,pmin=8,ver=1,xhmac=none$bm90IHJhbmRvbQ$y88i6LwhgkFuw1B40I7GLjNs1H1EcsjgM8aNPJGw85zvhlVi3KOlpfT4ZZOl/F+MJTx5gucUsaXWsxRE1l3XaA";
+    let hash = || {
+        HashBuilder::new()
+            .algorithm(Algorithm::Pbkdf2)
+            .add_param("iter", "10000")
+            .deterministic_for_docs(b"not random")
+            .finalize()
+            .unwrap()
+            .hash(password)
+            .unwrap()
+    };
+    assert_eq!(hash(), expected);
+    assert_eq!(hash(), hash());
+    assert!(HashBuilder::from_phc(expected).unwrap().is_valid(password));
+}
+
+#[test]
+fn test_used_fixed_salt() {
+    let password = "correct horse battery staple";
+    let stored = HashBuilder::new()
+        .deterministic_for_docs(b"not random")
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(stored.contains("fixedsalt=1"));
+    let checker = HashBuilder::from_phc(&stored).unwrap();
+    assert!(checker.used_fixed_salt());
+    assert!(checker.is_valid(password));
+    assert!(checker.rebless(2).unwrap().contains("fixedsalt=1"));
+
+    let stored = HashBuilder::new()
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    assert!(!stored.contains("fixedsalt"));
+    let checker = HashBuilder::from_phc(&stored).unwrap();
+    assert!(!checker.used_fixed_salt());
+    assert!(checker.is_valid(password));
+
+    let invalid = stored.replacen("$argon2$", "$argon2$fixedsalt=0,", 1);
+    assert_ne!(invalid, stored);
+    assert!(matches!(
+        HashBuilder::from_phc(&invalid),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+}
+
+#[test]
+fn test_allowed_algorithms() {
+    let password = "correct horse battery staple";
+    let pbkdf2_hash = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    let argon2_hash = HashBuilder::new()
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+
+    let mut argon2_only = HashBuilder::new();
+    argon2_only.allowed_algorithms(&[Algorithm::Argon2]);
+    assert!(matches!(
+        argon2_only.load_phc(&pbkdf2_hash),
+        Err(ErrorCode::AlgorithmNotAllowed)
+    ));
+    assert!(argon2_only
+        .load_phc(&argon2_hash)
+        .unwrap()
+        .is_valid(password));
+    assert!(argon2_only.finalize().is_ok());
+    assert!(matches!(
+        argon2_only.algorithm(Algorithm::Pbkdf2).finalize(),
+        Err(ErrorCode::AlgorithmNotAllowed)
+    ));
+
+    // The other constructors do not enforce the allow-list.
+    assert!(HashBuilder::from_phc(&pbkdf2_hash)
+        .unwrap()
+        .is_valid(password));
+
+    // The allow-list does not hide other errors.
+    assert!(matches!(
+        argon2_only.load_phc("not a hash"),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+    assert!(matches!(
+        argon2_only.load_phc("$2b$12$R9h/cIPz0gi.URNNX3kh2OPST9/PgBkqquzi.Ss7KIUgO2t0jWMUW"),
+        Err(ErrorCode::AlgorithmNotAvailable)
+    ));
+
+    // Without an allow-list, every available algorithm is accepted.
+    let builder = HashBuilder::new();
+    assert!(builder.load_phc(&pbkdf2_hash).unwrap().is_valid(password));
+    assert!(HashBuilder::from_phc(&pbkdf2_hash)
+        .unwrap()
+        .is_valid(password));
+}
+
+#[test]
+fn test_phc_segments() {
+    let password = "correct horse battery staple";
+    let stored = HashBuilder::new()
+        .algorithm(Algorithm::Pbkdf2)
+        .finalize()
+        .unwrap()
+        .hash(password)
+        .unwrap();
+    let (head, hash) = stored.rsplit_once('$').unwrap();
+    let (params, salt) = head.rsplit_once('$').unwrap();
+    let mut scratch = super::VerifyScratch::new();
+
+    // Salt and hash: the password can be verified.
+    let checker = HashBuilder::from_phc(&stored).unwrap();
+    assert!(matches!(
+        checker.verify_into(password, &mut scratch),
+        Ok(true)
+    ));
+
+    // Salt without a hash: the Hasher may hash passwords but cannot verify them.
+    let salt_only = format!("{}${}", params, salt);
+    let checker = HashBuilder::from_phc(&salt_only).unwrap();
+    assert!(checker.salt().is_some());
+    assert!(!checker.is_valid(password));
+    assert!(matches!(
+        checker.verify_into(password, &mut scratch),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+    assert!(matches!(
+        checker.verify_with_timeout(password, std::time::Duration::from_secs(60)),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+    assert!(super::validate_phc(&salt_only).is_err());
+    let new_hash = checker.hash(password).unwrap();
+    assert!(new_hash.contains(&format!("${}$", salt)));
+    assert!(HashBuilder::from_phc(&new_hash).unwrap().is_valid(password));
+
+    // Neither salt nor hash: same as above, with a random salt.
+    for no_salt in [params.to_string(), format!("{}$", params)] {
+        let checker = HashBuilder::from_phc(&no_salt).unwrap();
+        assert!(checker.salt().is_none());
+        assert!(matches!(
+            checker.verify_into(password, &mut scratch),
+            Err(ErrorCode::InvalidPasswordFormat)
+        ));
+    }
+
+    // A hash without a salt is rejected.
+    let hash_only = format!("{}$${}", params, hash);
+    assert!(matches!(
+        HashBuilder::from_phc(&hash_only),
+        Err(ErrorCode::InvalidPasswordFormat)
+    ));
+    assert!(!super::is_valid_phc(&hash_only));
+}
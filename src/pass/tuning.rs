@@ -0,0 +1,87 @@
+use super::pbkdf2::{Pbkdf2Hash, MAX_ITER, MIN_ITER};
+use super::{ErrorCode, HashingFunction};
+use std::time::{Duration, Instant};
+
+const CALIBRATION_ITER: u32 = MIN_ITER;
+const CALIBRATION_SAMPLES: usize = 5;
+
+/// Compute the number of PBKDF2 iterations so that hashing a password using the specified hash
+/// function (e.g. `sha256`) takes about the target duration on this machine.
+///
+/// Since the cost of PBKDF2 is linear in the number of iterations, a fixed number of iterations
+/// is timed and scaled proportionally. The measure is preceded by a warm-up run and the fastest
+/// of several samples is kept, which is the least affected by the other processes. The result
+/// is bounded by the minimal and maximal numbers of iterations accepted by this library.
+///
+/// ## Examples
+/// ```
+/// use libreauth::pass::calibrate_pbkdf2;
+/// use std::time::Duration;
+///
+/// let iterations = calibrate_pbkdf2("sha256", Duration::from_millis(50)).unwrap();
+/// assert!(iterations >= 10_000);
+/// ```
+pub fn calibrate_pbkdf2(hash: &str, target: Duration) -> Result<u32, ErrorCode> {
+    let mut hash_func = Pbkdf2Hash::new();
+    hash_func.set_parameter("hmac", hash)?;
+    hash_func.set_parameter("iter", &CALIBRATION_ITER.to_string())?;
+    let password = b"correct horse battery staple";
+    hash_func.hash(password);
+    let elapsed = (0..CALIBRATION_SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            hash_func.hash(password);
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+        .as_secs_f64();
+    if elapsed <= 0.0 {
+        return Ok(MAX_ITER);
+    }
+    let iterations = f64::from(CALIBRATION_ITER) * target.as_secs_f64() / elapsed;
+    Ok((iterations as u32).clamp(MIN_ITER, MAX_ITER))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calibrate_pbkdf2() {
+        let target = Duration::from_millis(100);
+        let iterations = calibrate_pbkdf2("sha256", target).unwrap();
+        assert!((MIN_ITER..=MAX_ITER).contains(&iterations));
+        if iterations == MIN_ITER || iterations == MAX_ITER {
+            return;
+        }
+        let mut hash_func = Pbkdf2Hash::new();
+        hash_func.set_parameter("hmac", "sha256").unwrap();
+        hash_func
+            .set_parameter("iter", &iterations.to_string())
+            .unwrap();
+        let elapsed = (0..3)
+            .map(|_| {
+                let start = Instant::now();
+                hash_func.hash(b"correct horse battery staple");
+                start.elapsed()
+            })
+            .min()
+            .unwrap();
+        assert!(elapsed >= target / 4, "{:?}", elapsed);
+        assert!(elapsed <= target * 4, "{:?}", elapsed);
+    }
+
+    #[test]
+    fn test_calibrate_pbkdf2_bounds() {
+        assert_eq!(calibrate_pbkdf2("sha1", Duration::ZERO).unwrap(), MIN_ITER);
+        assert_eq!(
+            calibrate_pbkdf2("sha1", Duration::from_secs(3600)).unwrap(),
+            MAX_ITER
+        );
+        assert!(matches!(
+            calibrate_pbkdf2("md5", Duration::from_millis(10)),
+            Err(ErrorCode::InvalidPasswordFormat)
+        ));
+    }
+}
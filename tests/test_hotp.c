@@ -58,6 +58,7 @@ static uint32_t test_basic_hotp(void) {
     assert(cfg.output_len == DEFAULT_BUFF_LEN);
     assert(cfg.output_base == NULL);
     assert(cfg.hash_function == LIBREAUTH_HASH_SHA_1);
+    assert(cfg.min_key_len == 16);
     assert(cfg.look_ahead == 0);
 
     cfg.key = key;
@@ -93,6 +94,7 @@ static uint32_t test_hotp_sync(void) {
     assert(cfg.output_len == DEFAULT_BUFF_LEN);
     assert(cfg.output_base == NULL);
     assert(cfg.hash_function == LIBREAUTH_HASH_SHA_1);
+    assert(cfg.min_key_len == 16);
     assert(cfg.look_ahead == 0);
 
     cfg.look_ahead = 3;
@@ -302,6 +304,28 @@ static uint32_t test_invalid_code(void) {
     return 1;
 }
 
+static uint32_t test_min_key_len(void) {
+    test_name("hotp: test_min_key_len");
+
+    struct libreauth_hotp_cfg cfg;
+    const char key[] = "1234567890";
+    char code[DEFAULT_BUFF_LEN + 1];
+
+    libreauth_hotp_init(&cfg);
+
+    cfg.key = key;
+    cfg.key_len = strlen(key);
+
+    uint32_t ret = libreauth_hotp_generate(&cfg, code);
+    assert(ret == LIBREAUTH_OATH_KEY_TOO_SHORT);
+
+    cfg.min_key_len = 10;
+    ret = libreauth_hotp_generate(&cfg, code);
+    assert(ret == LIBREAUTH_OATH_SUCCESS);
+
+    return 1;
+}
+
 uint32_t test_hotp(void) {
     uint32_t nb_tests = 0;
 
@@ -311,6 +335,7 @@ uint32_t test_hotp(void) {
     nb_tests += test_init_null_ptr();
     nb_tests += test_generate_null_ptr();
     nb_tests += test_invalid_base();
+    nb_tests += test_min_key_len();
     nb_tests += test_invalid_code();
 
     return nb_tests;
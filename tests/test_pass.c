@@ -168,6 +168,50 @@ static uint32_t test_xhmac(void) {
     return 1;
 }
 
+static uint32_t test_verify_and_upgrade(void) {
+    test_name("pass: test_verify_and_upgrade");
+
+    struct libreauth_pass_cfg   cfg;
+    const char password[] = "correct horse battery staple",
+          invalid_pass[] = "invalid password";
+    char reference[LIBREAUTH_PASSWORD_STORAGE_LEN];
+    char storage[LIBREAUTH_PASSWORD_STORAGE_LEN];
+    char upgraded[LIBREAUTH_PASSWORD_STORAGE_LEN];
+
+    uint32_t ret = libreauth_pass_init(&cfg);
+    assert(ret == LIBREAUTH_PASS_SUCCESS);
+    cfg.algorithm = LIBREAUTH_PASS_PBKDF2;
+    ret = libreauth_pass_hash(&cfg, password, reference, LIBREAUTH_PASSWORD_STORAGE_LEN);
+    assert(ret == LIBREAUTH_PASS_SUCCESS);
+
+    ret = libreauth_pass_init(&cfg);
+    assert(ret == LIBREAUTH_PASS_SUCCESS);
+
+    ret = libreauth_pass_verify_and_upgrade(password, reference, &cfg, upgraded, LIBREAUTH_PASSWORD_STORAGE_LEN);
+    assert(ret == LIBREAUTH_PASS_SUCCESS);
+    assert(strncmp(upgraded, "$argon2$", 8) == 0);
+    assert(libreauth_pass_is_valid(password, upgraded));
+
+    ret = libreauth_pass_verify_and_upgrade(password, upgraded, &cfg, storage, LIBREAUTH_PASSWORD_STORAGE_LEN);
+    assert(ret == LIBREAUTH_PASS_SUCCESS);
+    assert(storage[0] == 0);
+
+    cfg.version = 2;
+    ret = libreauth_pass_verify_and_upgrade(password, upgraded, &cfg, storage, LIBREAUTH_PASSWORD_STORAGE_LEN);
+    assert(ret == LIBREAUTH_PASS_SUCCESS);
+    assert(libreauth_pass_is_valid(password, storage));
+    ret = libreauth_pass_verify_and_upgrade(password, storage, &cfg, upgraded, LIBREAUTH_PASSWORD_STORAGE_LEN);
+    assert(ret == LIBREAUTH_PASS_SUCCESS);
+    assert(upgraded[0] == 0);
+
+    ret = libreauth_pass_verify_and_upgrade(invalid_pass, reference, &cfg, storage, LIBREAUTH_PASSWORD_STORAGE_LEN);
+    assert(ret == LIBREAUTH_PASS_PASSWORD_MISMATCH);
+    ret = libreauth_pass_verify_and_upgrade(invalid_pass, storage, &cfg, upgraded, LIBREAUTH_PASSWORD_STORAGE_LEN);
+    assert(ret == LIBREAUTH_PASS_PASSWORD_MISMATCH);
+
+    return 1;
+}
+
 uint32_t test_pass(void) {
     int nb_tests = 0;
 
@@ -175,6 +219,7 @@ uint32_t test_pass(void) {
     nb_tests += test_nist_pass();
     nb_tests += test_invalid_pass();
     nb_tests += test_xhmac();
+    nb_tests += test_verify_and_upgrade();
 
     return nb_tests;
 }
@@ -64,6 +64,7 @@ static uint32_t test_basic_totp(void) {
     assert(cfg.output_len == DEFAULT_BUFF_LEN);
     assert(cfg.output_base == NULL);
     assert(cfg.hash_function == LIBREAUTH_HASH_SHA_1);
+    assert(cfg.min_key_len == 16);
 
     cfg.key = key;
     cfg.key_len = strlen(key);
@@ -241,6 +242,28 @@ static uint32_t test_invalid_base(void) {
     return 1;
 }
 
+static uint32_t test_min_key_len(void) {
+    test_name("totp: test_min_key_len");
+
+    struct libreauth_totp_cfg cfg;
+    const char key[] = "1234567890";
+    char code[DEFAULT_BUFF_LEN + 1];
+
+    libreauth_totp_init(&cfg);
+
+    cfg.key = key;
+    cfg.key_len = strlen(key);
+
+    uint32_t ret = libreauth_totp_generate(&cfg, code);
+    assert(ret == LIBREAUTH_OATH_KEY_TOO_SHORT);
+
+    cfg.min_key_len = 10;
+    ret = libreauth_totp_generate(&cfg, code);
+    assert(ret == LIBREAUTH_OATH_SUCCESS);
+
+    return 1;
+}
+
 uint32_t test_totp(void) {
     uint32_t nb_tests = 0;
 
@@ -251,6 +274,7 @@ uint32_t test_totp(void) {
     nb_tests += test_init_null_ptr();
     nb_tests += test_generate_null_ptr();
     nb_tests += test_invalid_base();
+    nb_tests += test_min_key_len();
 
     return nb_tests;
 }